@@ -3,7 +3,6 @@ use std::ops::{Add, Sub, Mul};
 use std::cmp::Ordering;
 use rust_decimal::Decimal;
 use thiserror::Error;
-use rust_decimal_macros::*;
 
 #[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
 pub enum Currency {
@@ -35,11 +34,17 @@ pub struct Exchange {
     rates: HashMap<ExchangeRateQuery, Decimal>,
 }
 
+impl Default for Exchange {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Exchange {
     pub fn new() -> Exchange {
-        return Exchange{
+        Exchange{
             rates: HashMap::new(),
-        } 
+        }
     }
 
     pub fn set_rate(&mut self, from: Currency, to: Currency, rate: Decimal){
@@ -65,8 +70,8 @@ impl Exchange {
         }
 
         let rate = self.get_rate(money.currency, currency)?;
-        let converted_money = Money { amount: money.amount * rate, currency: currency };
-        return Ok(converted_money);
+        let converted_money = Money { amount: money.amount * rate, currency };
+        Ok(converted_money)
     }
     
     pub fn add(&self, first: Money, second: Money, output_currency: Currency) -> Result<Money, MoneyError> {
@@ -147,13 +152,16 @@ impl Exchange {
     }
 }
 
+// Money in different currencies has no ordering without an Exchange, so
+// partial_cmp deliberately returns None instead of deferring to cmp.
+#[allow(clippy::non_canonical_partial_ord_impl)]
 impl PartialOrd for Money {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
         if self.currency != other.currency {
             return None;
         }
 
-        return self.amount.partial_cmp(&other.amount)
+        self.amount.partial_cmp(&other.amount)
     }
 }
 
@@ -213,7 +221,7 @@ impl RoundedEq for Money{
         let rounded_self_amount = self.amount.round_dp(dp);
         let rounded_other_amount = other.amount.round_dp(dp);
 
-        return rounded_self_amount == rounded_other_amount;
+        rounded_self_amount == rounded_other_amount
     }
 }
 
@@ -232,8 +240,10 @@ macro_rules! usd_money {
 }
 
 #[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
 mod tests {
     use super::*;
+    use rust_decimal_macros::*;
 
     macro_rules! assert_rounded_eq {
         ($lhs:expr, $rhs:expr) => {
@@ -254,7 +264,7 @@ mod tests {
             Currency::CAD, 
             rate,
         );
-        return exchange;
+        exchange
     }
 
     #[test]
@@ -0,0 +1,285 @@
+use chrono::{Datelike, NaiveDate};
+use rust_decimal::prelude::*;
+use rust_decimal_macros::*;
+use simple_money::*;
+use crate::{TaxDeduction, TaxDeductionCategory, TaxError};
+
+/// Something that changes the shares held or their cost. Amounts can be in
+/// any currency and are converted to CAD at the transaction date.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AcbTransactionKind {
+    /// `cost` includes commissions.
+    Buy { shares: Decimal, cost: Money },
+    /// `proceeds` are net of commissions.
+    Sell { shares: Decimal, proceeds: Money },
+    /// A cash distribution used to buy more units. The distribution itself is
+    /// income and is reported separately.
+    ReinvestedDistribution { shares: Decimal, amount: Money },
+    /// Reduces the ACB. Any excess over the ACB is a capital gain.
+    ReturnOfCapital { amount: Money },
+    /// A capital gains distribution paid in units that are immediately
+    /// consolidated, so the ACB rises without any change in units held.
+    PhantomDistribution { amount: Money },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AcbTransaction {
+    pub date: NaiveDate,
+    pub kind: AcbTransactionKind,
+}
+
+/// A disposition, or a return of capital in excess of the ACB, in CAD.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RealizedGain {
+    pub date: NaiveDate,
+    pub shares: Decimal,
+    pub proceeds: Money,
+    pub adjusted_cost_base: Money,
+    /// Part of a loss denied by the superficial loss rule.
+    pub denied_loss: Money,
+    /// Negative for a loss, after any denied loss.
+    pub gain: Money,
+}
+
+impl RealizedGain {
+    /// The claim for a schedule whose capital gains rule holds the inclusion
+    /// rate. The full gain must already be in income.
+    pub fn tax_deduction(&self) -> TaxDeduction {
+        TaxDeduction {
+            tax_deduction_type: TaxDeductionCategory::CapitalGains,
+            money_to_deduct: self.gain,
+        }
+    }
+}
+
+/// The adjusted cost base of one security under the identical-property rule:
+/// every share held has the same average cost.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AdjustedCostBase {
+    pub security: String,
+    shares: Decimal,
+    total_cost: Money,
+    realized_gains: Vec<RealizedGain>,
+}
+
+impl AdjustedCostBase {
+    pub fn new(security: &str) -> AdjustedCostBase {
+        AdjustedCostBase {
+            security: security.to_string(),
+            shares: dec!(0),
+            total_cost: cad_money!(0),
+            realized_gains: vec![],
+        }
+    }
+
+    pub fn shares(&self) -> Decimal {
+        self.shares
+    }
+
+    pub fn total_cost(&self) -> Money {
+        self.total_cost
+    }
+
+    pub fn cost_per_share(&self) -> Money {
+        if self.shares.is_zero() {
+            cad_money!(0)
+        }else{
+            Money { amount: self.total_cost.amount / self.shares, currency: Currency::CAD }
+        }
+    }
+
+    pub fn realized_gains(&self) -> &[RealizedGain] {
+        &self.realized_gains
+    }
+
+    /// Net gain realized in a calendar year.
+    pub fn net_gain_for_year(&self, year: u32) -> Money {
+        self.realized_gains
+            .iter()
+            .filter(|realized| realized.date.year() as u32 == year)
+            .fold(cad_money!(0), |acc, realized| acc + realized.gain)
+    }
+
+    /// Adds a denied loss to the cost of the shares held, or about to be
+    /// bought, in this account.
+    pub(crate) fn add_to_cost(&mut self, amount: Money) {
+        self.total_cost = self.total_cost + amount;
+    }
+
+    /// Denies part of the loss on the most recent disposition.
+    pub(crate) fn deny_loss(&mut self, amount: Money) {
+        if let Some(realized) = self.realized_gains.last_mut() {
+            realized.denied_loss = realized.denied_loss + amount;
+            realized.gain = realized.gain + amount;
+        }
+    }
+
+    /// Applies a transaction, converting its amount with `exchange`, which
+    /// must hold the rates for the transaction date. Transactions must be
+    /// recorded in date order.
+    pub fn record(
+        &mut self,
+        transaction: &AcbTransaction,
+        exchange: &Exchange,
+    ) -> Result<Option<RealizedGain>, TaxError> {
+        let to_cad = |money: Money| exchange.convert(money, Currency::CAD);
+        let zero = cad_money!(0);
+        if let AcbTransactionKind::Buy { shares, .. }
+        | AcbTransactionKind::Sell { shares, .. }
+        | AcbTransactionKind::ReinvestedDistribution { shares, .. } = transaction.kind
+        {
+            if shares <= dec!(0) {
+                return Err(TaxError::NonPositiveShares(shares));
+            }
+        }
+
+        let realized = match transaction.kind {
+            AcbTransactionKind::Buy { shares, cost } => {
+                self.shares += shares;
+                self.total_cost = self.total_cost + to_cad(cost)?;
+                None
+            }
+            AcbTransactionKind::ReinvestedDistribution { shares, amount } => {
+                self.shares += shares;
+                self.total_cost = self.total_cost + to_cad(amount)?;
+                None
+            }
+            AcbTransactionKind::PhantomDistribution { amount } => {
+                self.total_cost = self.total_cost + to_cad(amount)?;
+                None
+            }
+            AcbTransactionKind::ReturnOfCapital { amount } => {
+                let amount = to_cad(amount)?;
+                let excess = (amount - self.total_cost).max(zero);
+                self.total_cost = (self.total_cost - amount).max(zero);
+                if excess.amount > dec!(0) {
+                    Some(RealizedGain {
+                        date: transaction.date,
+                        shares: dec!(0),
+                        proceeds: excess,
+                        adjusted_cost_base: zero,
+                        denied_loss: zero,
+                        gain: excess,
+                    })
+                }else{
+                    None
+                }
+            }
+            AcbTransactionKind::Sell { shares, proceeds } => {
+                if shares > self.shares {
+                    return Err(TaxError::InsufficientShares);
+                }
+                let proceeds = to_cad(proceeds)?;
+                let adjusted_cost_base = Money {
+                    amount: self.total_cost.amount * shares / self.shares,
+                    currency: Currency::CAD,
+                };
+                self.shares -= shares;
+                self.total_cost = if self.shares.is_zero() {
+                    zero
+                }else{
+                    self.total_cost - adjusted_cost_base
+                };
+                Some(RealizedGain {
+                    date: transaction.date,
+                    shares,
+                    proceeds,
+                    adjusted_cost_base,
+                    denied_loss: zero,
+                    gain: proceeds - adjusted_cost_base,
+                })
+            }
+        };
+
+        if let Some(realized) = realized {
+            self.realized_gains.push(realized);
+        }
+        Ok(realized)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    fn exchange(usd_to_cad: Decimal) -> Exchange {
+        let mut exchange = Exchange::new();
+        exchange.set_rate(Currency::USD, Currency::CAD, usd_to_cad);
+        exchange
+    }
+
+    #[test]
+    fn us_listed_shares_at_transaction_date_rates() {
+        let mut acb = AdjustedCostBase::new("VTI");
+        let buy = |day, cost| AcbTransaction { date: date(2024, 1, day), kind: AcbTransactionKind::Buy { shares: dec!(100), cost } };
+        acb.record(&buy(10, usd_money!(1_000)), &exchange(dec!(1.35))).unwrap();
+        acb.record(&buy(20, usd_money!(1_200)), &exchange(dec!(1.30))).unwrap();
+        assert_eq!(acb.cost_per_share(), cad_money!(14.55));
+
+        let sell = AcbTransaction {
+            date: date(2024, 6, 3),
+            kind: AcbTransactionKind::Sell { shares: dec!(50), proceeds: usd_money!(800) },
+        };
+        let realized = acb.record(&sell, &exchange(dec!(1.40))).unwrap().unwrap();
+        assert_eq!(realized.proceeds, cad_money!(1_120));
+        assert_eq!(realized.adjusted_cost_base, cad_money!(727.5));
+        assert_eq!(realized.gain, cad_money!(392.5));
+        assert_eq!(acb.total_cost(), cad_money!(2_182.5));
+        assert_eq!(acb.net_gain_for_year(2024), cad_money!(392.5));
+
+        let missing_rate = acb.record(&sell, &Exchange::new()).unwrap_err();
+        assert_eq!(missing_rate, TaxError::CouldNotFindExchangeRate);
+    }
+
+    #[test]
+    fn distributions_adjust_the_cost_base() {
+        let mut acb = AdjustedCostBase::new("XEQT");
+        let cad = Exchange::new();
+        let record = |acb: &mut AdjustedCostBase, kind| acb.record(&AcbTransaction { date: date(2024, 12, 31), kind }, &cad);
+
+        record(&mut acb, AcbTransactionKind::Buy { shares: dec!(100), cost: cad_money!(1_000) }).unwrap();
+        record(&mut acb, AcbTransactionKind::ReinvestedDistribution { shares: dec!(5), amount: cad_money!(60) }).unwrap();
+        record(&mut acb, AcbTransactionKind::PhantomDistribution { amount: cad_money!(40) }).unwrap();
+        record(&mut acb, AcbTransactionKind::ReturnOfCapital { amount: cad_money!(30) }).unwrap();
+        assert_eq!(acb.shares(), dec!(105));
+        assert_eq!(acb.total_cost(), cad_money!(1_070));
+
+        let excess = record(&mut acb, AcbTransactionKind::ReturnOfCapital { amount: cad_money!(2_000) }).unwrap();
+        assert_eq!(excess.unwrap().gain, cad_money!(930));
+        assert_eq!(acb.total_cost(), cad_money!(0));
+    }
+
+    #[test]
+    fn cannot_sell_more_than_held() {
+        let mut acb = AdjustedCostBase::new("XEQT");
+        let sell = AcbTransaction {
+            date: date(2024, 3, 1),
+            kind: AcbTransactionKind::Sell { shares: dec!(1), proceeds: cad_money!(30) },
+        };
+        assert_eq!(acb.record(&sell, &Exchange::new()).unwrap_err(), TaxError::InsufficientShares);
+    }
+
+    #[test]
+    fn share_counts_must_be_positive() {
+        let mut acb = AdjustedCostBase::new("XEQT");
+        let cad = Exchange::new();
+        let record = |acb: &mut AdjustedCostBase, kind| acb.record(&AcbTransaction { date: date(2024, 3, 1), kind }, &cad);
+
+        let empty_sell = record(&mut acb, AcbTransactionKind::Sell { shares: dec!(0), proceeds: cad_money!(0) });
+        assert_eq!(empty_sell.unwrap_err(), TaxError::NonPositiveShares(dec!(0)));
+
+        record(&mut acb, AcbTransactionKind::Buy { shares: dec!(10), cost: cad_money!(100) }).unwrap();
+        let negative_sell = record(&mut acb, AcbTransactionKind::Sell { shares: dec!(-5), proceeds: cad_money!(-50) });
+        assert_eq!(negative_sell.unwrap_err(), TaxError::NonPositiveShares(dec!(-5)));
+        let negative_buy = record(&mut acb, AcbTransactionKind::Buy { shares: dec!(-1), cost: cad_money!(10) });
+        assert_eq!(negative_buy.unwrap_err(), TaxError::NonPositiveShares(dec!(-1)));
+        let empty_distribution = record(&mut acb, AcbTransactionKind::ReinvestedDistribution { shares: dec!(0), amount: cad_money!(5) });
+        assert_eq!(empty_distribution.unwrap_err(), TaxError::NonPositiveShares(dec!(0)));
+        assert_eq!(acb.shares(), dec!(10));
+        assert_eq!(acb.total_cost(), cad_money!(100));
+    }
+}
@@ -0,0 +1,287 @@
+use rust_decimal::prelude::*;
+use rust_decimal_macros::*;
+use simple_money::*;
+use crate::TaxError;
+use super::{federal_schedule, CAPITAL_GAINS_INCLUSION_RATE, CanadianJurisdiction, Dividend};
+
+/// Share of the benefits the stock option deduction was claimed on that is
+/// added back for the AMT.
+const STOCK_OPTION_ADD_BACK: Decimal = dec!(0.3);
+
+/// AMT paid can be recovered against regular tax in the following seven years.
+const CARRYFORWARD_YEARS: u32 = 7;
+
+/// The flat federal AMT rate and the exemption, which since 2024 is indexed to
+/// the start of the 29% bracket.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CanadianAmtParameters {
+    pub exemption: Money,
+    pub rate: Decimal,
+}
+
+impl CanadianAmtParameters {
+    pub fn for_year(year: u32) -> Result<CanadianAmtParameters, TaxError> {
+        match year {
+            2024 => Ok(CanadianAmtParameters { exemption: cad_money!(173_205), rate: dec!(0.205) }),
+            _ => Err(TaxError::UnsupportedYear(year)),
+        }
+    }
+}
+
+/// Provincial AMT as a share of the federal AMT. Quebec levies its own
+/// minimum tax on a separate base, which is not modelled here.
+pub fn provincial_amt_factor(jurisdiction: CanadianJurisdiction) -> Option<Decimal> {
+    match jurisdiction {
+        CanadianJurisdiction::Alberta => Some(dec!(0.35)),
+        CanadianJurisdiction::BritishColumbia => Some(dec!(0.337)),
+        CanadianJurisdiction::Ontario => Some(dec!(0.3367)),
+        CanadianJurisdiction::Federal | CanadianJurisdiction::Quebec => None,
+    }
+}
+
+/// A year's income as both the regular tax and the AMT see it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CanadianAmtIncome {
+    /// Taxable income for the regular tax, with the taxable half of capital
+    /// gains, grossed-up dividends and the stock option deduction applied.
+    pub taxable_income: Money,
+    /// Net capital gains realized in the year, before the inclusion rate.
+    pub capital_gains: Money,
+    /// Benefits the stock option deduction was claimed on.
+    pub stock_option_deductible_benefits: Money,
+    pub dividends: Vec<Dividend>,
+    /// Tax value of credits the AMT allows in full, such as the basic
+    /// personal amount and CPP and EI contributions.
+    pub credits_allowed_in_full: Money,
+    /// Tax value of the other non-refundable credits, half of which the AMT
+    /// allows.
+    pub credits_allowed_at_half: Money,
+}
+
+impl CanadianAmtIncome {
+    fn validate(&self) -> Result<(), TaxError> {
+        let amounts = [
+            self.taxable_income,
+            self.capital_gains,
+            self.stock_option_deductible_benefits,
+            self.credits_allowed_in_full,
+            self.credits_allowed_at_half,
+        ];
+        if amounts.iter().any(|amount| amount.currency != Currency::CAD)
+            || self.dividends.iter().any(|dividend| dividend.amount.currency != Currency::CAD)
+        {
+            Err(TaxError::MismatchedCurrencies)
+        }else{
+            Ok(())
+        }
+    }
+
+    /// Capital gains are fully included, part of the stock option deduction is
+    /// added back, and dividends count at their cash amount.
+    pub fn adjusted_taxable_income(&self) -> Money {
+        let gross_up = self
+            .dividends
+            .iter()
+            .fold(cad_money!(0), |acc, dividend| acc + dividend.grossed_up_amount() - dividend.amount);
+        let capital_gains = self.capital_gains.max(cad_money!(0));
+
+        self.taxable_income
+            + capital_gains * (dec!(1) - CAPITAL_GAINS_INCLUSION_RATE)
+            + self.stock_option_deductible_benefits * STOCK_OPTION_ADD_BACK
+            - gross_up
+    }
+}
+
+/// AMT paid in earlier years, by the year it was paid.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AmtCarryforward {
+    balances: Vec<(u32, Money)>,
+}
+
+impl AmtCarryforward {
+    pub fn new() -> AmtCarryforward {
+        AmtCarryforward { balances: vec![] }
+    }
+
+    pub fn add(&mut self, year: u32, amount: Money) {
+        if amount.amount > dec!(0) {
+            self.balances.push((year, amount));
+            self.balances.sort_by_key(|(year, _)| *year);
+        }
+    }
+
+    pub fn balances(&self) -> &[(u32, Money)] {
+        &self.balances
+    }
+
+    fn usable_in(paid_year: u32, year: u32) -> bool {
+        paid_year < year && year <= paid_year + CARRYFORWARD_YEARS
+    }
+
+    /// Carryforward that can still be used in `year`.
+    pub fn available(&self, year: u32) -> Money {
+        self.balances
+            .iter()
+            .filter(|(paid_year, _)| Self::usable_in(*paid_year, year))
+            .fold(cad_money!(0), |acc, (_, amount)| acc + *amount)
+    }
+
+    /// Uses up to `limit` of the carryforward in `year`, oldest first, and
+    /// drops balances that have expired.
+    fn apply(&mut self, year: u32, limit: Money) -> Money {
+        let mut remaining = limit;
+        for (paid_year, amount) in self.balances.iter_mut() {
+            if !Self::usable_in(*paid_year, year) {
+                continue;
+            }
+            let used = remaining.min(*amount);
+            *amount = *amount - used;
+            remaining = remaining - used;
+        }
+        self.balances
+            .retain(|(paid_year, amount)| amount.amount > dec!(0) && *paid_year + CARRYFORWARD_YEARS > year);
+
+        limit - remaining
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CanadianAmtResult {
+    pub adjusted_taxable_income: Money,
+    pub exemption: Money,
+    /// Tax at the AMT rate on income over the exemption, less allowed credits.
+    pub minimum_amount: Money,
+    /// Basic federal tax after non-refundable credits.
+    pub regular_tax: Money,
+    pub alternative_minimum_tax: Money,
+    /// Earlier years' AMT recovered against this year's regular tax.
+    pub credit_used: Money,
+}
+
+impl CanadianAmtResult {
+    pub fn total_tax(&self) -> Money {
+        self.regular_tax + self.alternative_minimum_tax - self.credit_used
+    }
+
+    /// Provinces outside Quebec charge their share of the federal AMT.
+    pub fn provincial_minimum_tax(&self, jurisdiction: CanadianJurisdiction) -> Money {
+        provincial_amt_factor(jurisdiction)
+            .map_or(cad_money!(0), |factor| self.alternative_minimum_tax * factor)
+    }
+
+    /// The provincial share of the federal carryforward used this year.
+    pub fn provincial_credit_used(&self, jurisdiction: CanadianJurisdiction) -> Money {
+        provincial_amt_factor(jurisdiction).map_or(cad_money!(0), |factor| self.credit_used * factor)
+    }
+}
+
+/// Calculates the federal AMT for a year alongside basic federal tax.
+///
+/// Carryforward from the seven previous years is used against any excess of
+/// regular tax over the minimum amount, and this year's AMT is added to
+/// `carryforward`.
+pub fn alternative_minimum_tax(
+    year: u32,
+    income: &CanadianAmtIncome,
+    carryforward: &mut AmtCarryforward,
+) -> Result<CanadianAmtResult, TaxError> {
+    income.validate()?;
+    let parameters = CanadianAmtParameters::for_year(year)?;
+    let schedule = federal_schedule(year)?;
+    let zero = cad_money!(0);
+
+    let dividend_credits = income.dividends.iter().map(|dividend| dividend.tax_credit()).collect();
+    let tax_after_dividend_credits = schedule.calculate_tax_with_credits(income.taxable_income, vec![], dividend_credits)?;
+    let regular_tax = (tax_after_dividend_credits - income.credits_allowed_in_full - income.credits_allowed_at_half)
+        .max(zero);
+
+    let adjusted_taxable_income = income.adjusted_taxable_income();
+    let taxable_excess = (adjusted_taxable_income - parameters.exemption).max(zero);
+    let allowed_credits = income.credits_allowed_in_full + income.credits_allowed_at_half * dec!(0.5);
+    let minimum_amount = (taxable_excess * parameters.rate - allowed_credits).max(zero);
+
+    let alternative_minimum_tax = (minimum_amount - regular_tax).max(zero);
+    let credit_used = carryforward.apply(year, (regular_tax - minimum_amount).max(zero));
+    carryforward.add(year, alternative_minimum_tax);
+
+    Ok(CanadianAmtResult {
+        adjusted_taxable_income,
+        exemption: parameters.exemption,
+        minimum_amount,
+        regular_tax,
+        alternative_minimum_tax,
+        credit_used,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ca::DividendType;
+
+    fn ipo_year() -> CanadianAmtIncome {
+        CanadianAmtIncome {
+            taxable_income: cad_money!(730_000),
+            capital_gains: cad_money!(1_000_000),
+            stock_option_deductible_benefits: cad_money!(300_000),
+            dividends: vec![],
+            credits_allowed_in_full: cad_money!(2_123.4),
+            credits_allowed_at_half: cad_money!(0),
+        }
+    }
+
+    #[test]
+    fn ipo_year_pays_amt() {
+        let mut carryforward = AmtCarryforward::new();
+        let result = alternative_minimum_tax(2024, &ipo_year(), &mut carryforward).unwrap();
+
+        assert_eq!(result.adjusted_taxable_income, cad_money!(1_320_000));
+        assert_eq!(result.minimum_amount, cad_money!(232_969.575));
+        assert_eq!(result.regular_tax, cad_money!(214_492.37));
+        assert_eq!(result.alternative_minimum_tax, cad_money!(18_477.205));
+        assert_eq!(result.provincial_minimum_tax(CanadianJurisdiction::Ontario), cad_money!(6_221.2749235));
+        assert_eq!(result.provincial_minimum_tax(CanadianJurisdiction::Quebec), cad_money!(0));
+        assert_eq!(carryforward.balances(), &[(2024, cad_money!(18_477.205))]);
+    }
+
+    #[test]
+    fn dividends_count_at_their_cash_amount() {
+        let income = CanadianAmtIncome {
+            taxable_income: cad_money!(138_000),
+            capital_gains: cad_money!(0),
+            stock_option_deductible_benefits: cad_money!(0),
+            dividends: vec![Dividend { dividend_type: DividendType::Eligible, amount: cad_money!(100_000) }],
+            credits_allowed_in_full: cad_money!(0),
+            credits_allowed_at_half: cad_money!(0),
+        };
+        assert_eq!(income.adjusted_taxable_income(), cad_money!(100_000));
+
+        let result = alternative_minimum_tax(2024, &income, &mut AmtCarryforward::new()).unwrap();
+        assert_eq!(result.minimum_amount, cad_money!(0));
+    }
+
+    #[test]
+    fn carryforward_recovered_and_expires() {
+        let mut carryforward = AmtCarryforward::new();
+        carryforward.add(2016, cad_money!(5_000));
+        carryforward.add(2020, cad_money!(100_000));
+        assert_eq!(carryforward.available(2024), cad_money!(100_000));
+
+        let income = CanadianAmtIncome {
+            taxable_income: cad_money!(300_000),
+            capital_gains: cad_money!(0),
+            stock_option_deductible_benefits: cad_money!(0),
+            ..ipo_year()
+        };
+        let result = alternative_minimum_tax(2024, &income, &mut carryforward).unwrap();
+        assert_eq!(result.alternative_minimum_tax, cad_money!(0));
+        assert_eq!(result.credit_used, cad_money!(48_722.795));
+        assert_eq!(carryforward.balances(), &[(2020, cad_money!(100_000) - result.credit_used)]);
+    }
+
+    #[test]
+    fn unsupported_years() {
+        let error = alternative_minimum_tax(2023, &ipo_year(), &mut AmtCarryforward::new()).unwrap_err();
+        assert_eq!(error, TaxError::UnsupportedYear(2023));
+    }
+}
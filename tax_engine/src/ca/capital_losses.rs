@@ -0,0 +1,211 @@
+use rust_decimal::prelude::*;
+use rust_decimal_macros::*;
+use simple_money::*;
+use crate::TaxError;
+use super::CAPITAL_GAINS_INCLUSION_RATE;
+
+/// Net capital losses can be carried back this many years.
+const CARRY_BACK_YEARS: u32 = 3;
+
+/// The inclusion rate for capital gains realized in a year. The rate for
+/// 2000 changed twice during the year; the rate for most of it is used.
+pub fn capital_gains_inclusion_rate(year: u32) -> Decimal {
+    match year {
+        1988 | 1989 => dec!(2) / dec!(3),
+        1990..=1999 => dec!(0.75),
+        2000 => dec!(2) / dec!(3),
+        _ => CAPITAL_GAINS_INCLUSION_RATE,
+    }
+}
+
+/// The capital loss from one year, kept before the inclusion rate so it can
+/// be restated at the rate of whichever year it is applied in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NetCapitalLoss {
+    pub year: u32,
+    pub capital_loss: Money,
+    pub remaining: Money,
+}
+
+impl NetCapitalLoss {
+    /// The loss as it was reported, at the inclusion rate of its year.
+    pub fn net_capital_loss(&self) -> Money {
+        self.capital_loss * capital_gains_inclusion_rate(self.year)
+    }
+
+    /// What is left, restated at the inclusion rate of `year`.
+    pub fn remaining_at(&self, year: u32) -> Money {
+        self.remaining * capital_gains_inclusion_rate(year)
+    }
+}
+
+/// Net capital loss claimed against another year's taxable capital gains.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LossApplication {
+    pub loss_year: u32,
+    pub applied_year: u32,
+    pub deduction: Money,
+}
+
+/// An earlier year the loss can be carried back to, with the taxable capital
+/// gains still unsheltered in it and the marginal rate they were taxed at.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CarryBackYear {
+    pub year: u32,
+    pub taxable_capital_gains: Money,
+    pub marginal_rate: Decimal,
+}
+
+/// A taxpayer's net capital losses across years, and where they were used.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct NetCapitalLossPool {
+    losses: Vec<NetCapitalLoss>,
+    applications: Vec<LossApplication>,
+}
+
+impl NetCapitalLossPool {
+    pub fn new() -> NetCapitalLossPool {
+        NetCapitalLossPool { losses: vec![], applications: vec![] }
+    }
+
+    /// Each year's loss and the balance still unused.
+    pub fn losses(&self) -> &[NetCapitalLoss] {
+        &self.losses
+    }
+
+    pub fn applications(&self) -> &[LossApplication] {
+        &self.applications
+    }
+
+    /// Total unused losses restated at the inclusion rate of `year`.
+    pub fn balance_at(&self, year: u32) -> Money {
+        self.losses.iter().fold(cad_money!(0), |acc, loss| acc + loss.remaining_at(year))
+    }
+
+    /// Records a year's net capital loss, given as the loss before inclusion.
+    pub fn add_loss(&mut self, year: u32, capital_loss: Money) -> Result<(), TaxError> {
+        if capital_loss.currency != Currency::CAD {
+            return Err(TaxError::MismatchedCurrencies);
+        }
+        if capital_loss.amount > dec!(0) {
+            self.losses.push(NetCapitalLoss { year, capital_loss, remaining: capital_loss });
+            self.losses.sort_by_key(|loss| loss.year);
+        }
+        Ok(())
+    }
+
+    /// Uses up to `limit`, stated at `applied_year`'s inclusion rate, of the
+    /// loss at `index`.
+    fn use_loss(&mut self, index: usize, applied_year: u32, limit: Money) -> Money {
+        let rate = capital_gains_inclusion_rate(applied_year);
+        let loss = &mut self.losses[index];
+        let deduction = limit.min(loss.remaining_at(applied_year));
+        if deduction.amount <= dec!(0) {
+            return cad_money!(0);
+        }
+        loss.remaining = Money { amount: loss.remaining.amount - deduction.amount / rate, currency: Currency::CAD };
+        self.applications.push(LossApplication { loss_year: loss.year, applied_year, deduction });
+        deduction
+    }
+
+    /// Carries the loss from `loss_year` back against the three preceding
+    /// years (Form T1A), highest marginal rate first so the refund is as
+    /// large as possible. Ties go to the earliest year, which is the first
+    /// to fall out of reach.
+    pub fn carry_back(
+        &mut self,
+        loss_year: u32,
+        prior_years: &[CarryBackYear],
+    ) -> Result<Vec<LossApplication>, TaxError> {
+        if prior_years.iter().any(|prior| prior.taxable_capital_gains.currency != Currency::CAD) {
+            return Err(TaxError::MismatchedCurrencies);
+        }
+        let index = match self.losses.iter().position(|loss| loss.year == loss_year) {
+            Some(index) => index,
+            None => return Ok(vec![]),
+        };
+
+        let mut eligible: Vec<&CarryBackYear> = prior_years
+            .iter()
+            .filter(|prior| prior.year < loss_year && prior.year + CARRY_BACK_YEARS >= loss_year)
+            .collect();
+        eligible.sort_by(|first, second| {
+            second.marginal_rate.cmp(&first.marginal_rate).then(first.year.cmp(&second.year))
+        });
+
+        let first_application = self.applications.len();
+        for prior in eligible {
+            self.use_loss(index, prior.year, prior.taxable_capital_gains);
+        }
+        Ok(self.applications[first_application..].to_vec())
+    }
+
+    /// Claims losses from earlier years against a year's taxable capital
+    /// gains, oldest first, and returns the deduction. Net capital losses
+    /// never expire, so the order only matters for the ledger.
+    pub fn apply(&mut self, year: u32, taxable_capital_gains: Money) -> Result<Money, TaxError> {
+        if taxable_capital_gains.currency != Currency::CAD {
+            return Err(TaxError::MismatchedCurrencies);
+        }
+        let mut remaining = taxable_capital_gains.max(cad_money!(0));
+        for index in 0..self.losses.len() {
+            if self.losses[index].year >= year {
+                break;
+            }
+            remaining = remaining - self.use_loss(index, year, remaining);
+        }
+        Ok(taxable_capital_gains.max(cad_money!(0)) - remaining)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn carry_back_to_the_highest_rate_years_first() {
+        let mut pool = NetCapitalLossPool::new();
+        pool.add_loss(2024, cad_money!(30_000)).unwrap();
+
+        let prior_years = [
+            CarryBackYear { year: 2020, taxable_capital_gains: cad_money!(50_000), marginal_rate: dec!(0.5) },
+            CarryBackYear { year: 2021, taxable_capital_gains: cad_money!(4_000), marginal_rate: dec!(0.3) },
+            CarryBackYear { year: 2022, taxable_capital_gains: cad_money!(10_000), marginal_rate: dec!(0.45) },
+            CarryBackYear { year: 2023, taxable_capital_gains: cad_money!(5_000), marginal_rate: dec!(0.2) },
+        ];
+        let applied = pool.carry_back(2024, &prior_years).unwrap();
+        assert_eq!(applied, vec![
+            LossApplication { loss_year: 2024, applied_year: 2022, deduction: cad_money!(10_000) },
+            LossApplication { loss_year: 2024, applied_year: 2021, deduction: cad_money!(4_000) },
+            LossApplication { loss_year: 2024, applied_year: 2023, deduction: cad_money!(1_000) },
+        ]);
+        assert_eq!(pool.balance_at(2025), cad_money!(0));
+    }
+
+    #[test]
+    fn old_losses_are_restated_at_the_current_inclusion_rate() {
+        let mut pool = NetCapitalLossPool::new();
+        pool.add_loss(1995, cad_money!(10_000)).unwrap();
+        assert_eq!(pool.losses()[0].net_capital_loss(), cad_money!(7_500));
+
+        let deduction = pool.apply(2024, cad_money!(20_000)).unwrap();
+        assert_eq!(deduction, cad_money!(5_000));
+        assert_eq!(pool.losses()[0].remaining, cad_money!(0));
+    }
+
+    #[test]
+    fn forward_application_uses_oldest_losses_first() {
+        let mut pool = NetCapitalLossPool::new();
+        pool.add_loss(2022, cad_money!(8_000)).unwrap();
+        pool.add_loss(2019, cad_money!(6_000)).unwrap();
+
+        let deduction = pool.apply(2024, cad_money!(5_000)).unwrap();
+        assert_eq!(deduction, cad_money!(5_000));
+        assert_eq!(pool.losses()[0].remaining, cad_money!(0));
+        assert_eq!(pool.losses()[1].remaining, cad_money!(4_000));
+        assert_eq!(pool.balance_at(2025), cad_money!(2_000));
+
+        // Losses from the same or a later year cannot be used.
+        assert_eq!(pool.apply(2022, cad_money!(5_000)).unwrap(), cad_money!(0));
+    }
+}
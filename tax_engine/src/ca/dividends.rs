@@ -0,0 +1,163 @@
+use rust_decimal::prelude::*;
+use rust_decimal_macros::*;
+use simple_money::*;
+use crate::{TaxCredit, TaxCreditCategory, TaxCreditRule, TaxError, TaxSchedule};
+use super::CanadianJurisdiction;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum DividendType {
+    /// Paid out of income taxed at the general corporate rate, usually by
+    /// public companies.
+    Eligible,
+    /// Paid out of income that benefited from the small business deduction.
+    NonEligible,
+}
+
+impl DividendType {
+    /// Quebec uses the same gross-up as the federal government.
+    pub fn gross_up_rate(&self) -> Decimal {
+        match self {
+            DividendType::Eligible => dec!(0.38),
+            DividendType::NonEligible => dec!(0.15),
+        }
+    }
+
+    fn credit_category(&self) -> TaxCreditCategory {
+        match self {
+            DividendType::Eligible => TaxCreditCategory::EligibleDividends,
+            DividendType::NonEligible => TaxCreditCategory::NonEligibleDividends,
+        }
+    }
+}
+
+/// Dividend tax credit rate as a fraction of the grossed-up dividend.
+pub fn dividend_tax_credit_rate(
+    jurisdiction: CanadianJurisdiction,
+    year: u32,
+    dividend_type: DividendType,
+) -> Result<Decimal, TaxError> {
+    use CanadianJurisdiction::*;
+    use DividendType::*;
+
+    if year != 2023 && year != 2024 {
+        return Err(TaxError::UnsupportedYear(year));
+    }
+
+    let rate = match (jurisdiction, dividend_type) {
+        (Federal, Eligible) => dec!(0.150198),
+        (Federal, NonEligible) => dec!(0.090301),
+        (Alberta, Eligible) => dec!(0.0812),
+        (Alberta, NonEligible) => dec!(0.0218),
+        (BritishColumbia, Eligible) => dec!(0.12),
+        (BritishColumbia, NonEligible) => dec!(0.0196),
+        (Ontario, Eligible) => dec!(0.10),
+        (Ontario, NonEligible) => dec!(0.029863),
+        (Quebec, Eligible) => dec!(0.117),
+        (Quebec, NonEligible) => dec!(0.0342),
+    };
+
+    Ok(rate)
+}
+
+/// Registers the dividend tax credit for both dividend types on a
+/// jurisdiction's schedule.
+///
+/// The gross-up is income, not a deduction: the income passed to the schedule
+/// must include each dividend's `grossed_up_amount`.
+pub fn set_dividend_rules(
+    schedule: &mut TaxSchedule,
+    jurisdiction: CanadianJurisdiction,
+    year: u32,
+) -> Result<(), TaxError> {
+    for dividend_type in [DividendType::Eligible, DividendType::NonEligible].iter() {
+        schedule.set_credit(dividend_type.credit_category(), TaxCreditRule {
+            tax_credit_type: dividend_type.credit_category(),
+            rate: dividend_tax_credit_rate(jurisdiction, year, *dividend_type)?,
+        });
+    }
+
+    Ok(())
+}
+
+/// A cash dividend received from a taxable Canadian corporation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Dividend {
+    pub dividend_type: DividendType,
+    pub amount: Money,
+}
+
+impl Dividend {
+    /// The amount included in taxable income.
+    pub fn grossed_up_amount(&self) -> Money {
+        self.amount * (dec!(1) + self.dividend_type.gross_up_rate())
+    }
+
+    pub fn tax_credit(&self) -> TaxCredit {
+        TaxCredit {
+            tax_credit_type: self.dividend_type.credit_category(),
+            credit_base: self.grossed_up_amount(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TaxBracket;
+
+    fn flat_schedule(rate: Decimal) -> TaxSchedule {
+        let bracket = TaxBracket::new(cad_money!(0), None, rate).unwrap();
+        TaxSchedule::new(vec![bracket], Currency::CAD).unwrap()
+    }
+
+    #[test]
+    fn eligible_dividend_gross_up_and_credit() {
+        let mut schedule = flat_schedule(dec!(0.15));
+        set_dividend_rules(&mut schedule, CanadianJurisdiction::Federal, 2024).unwrap();
+
+        let dividend = Dividend { dividend_type: DividendType::Eligible, amount: cad_money!(10_000) };
+        assert_eq!(dividend.grossed_up_amount(), cad_money!(13_800));
+
+        let tax = schedule.calculate_tax_with_credits(
+            cad_money!(50_000) + dividend.grossed_up_amount(),
+            vec![],
+            vec![dividend.tax_credit()],
+        ).unwrap();
+        assert_eq!(tax, cad_money!(7_497.2676));
+    }
+
+    #[test]
+    fn non_eligible_dividend_in_quebec() {
+        let mut schedule = flat_schedule(dec!(0.14));
+        set_dividend_rules(&mut schedule, CanadianJurisdiction::Quebec, 2024).unwrap();
+
+        let dividend = Dividend { dividend_type: DividendType::NonEligible, amount: cad_money!(2_000) };
+        let tax = schedule.calculate_tax_with_credits(
+            dividend.grossed_up_amount(),
+            vec![],
+            vec![dividend.tax_credit()],
+        ).unwrap();
+        assert_eq!(tax, cad_money!(243.34));
+    }
+
+    #[test]
+    fn credit_cannot_make_tax_negative() {
+        let mut schedule = flat_schedule(dec!(0.05));
+        set_dividend_rules(&mut schedule, CanadianJurisdiction::Federal, 2023).unwrap();
+
+        let dividend = Dividend { dividend_type: DividendType::Eligible, amount: cad_money!(1_000) };
+        let tax = schedule.calculate_tax_with_credits(
+            dividend.grossed_up_amount(),
+            vec![],
+            vec![dividend.tax_credit()],
+        ).unwrap();
+        assert_eq!(tax, cad_money!(0));
+    }
+
+    #[test]
+    fn unsupported_years() {
+        let mut schedule = flat_schedule(dec!(0.15));
+        let error = set_dividend_rules(&mut schedule, CanadianJurisdiction::Alberta, 2015).unwrap_err();
+        assert_eq!(error, TaxError::UnsupportedYear(2015));
+    }
+}
@@ -0,0 +1,85 @@
+use rust_decimal::prelude::*;
+use rust_decimal_macros::*;
+use simple_money::*;
+use crate::{TaxError, TaxSchedule};
+use super::{set_dividend_rules, stock_option_deduction_rule, CanadianJurisdiction};
+
+const FEDERAL_RATES: [Decimal; 5] = [
+    dec!(0.15),
+    dec!(0.205),
+    dec!(0.26),
+    dec!(0.29),
+    dec!(0.33),
+];
+
+/// Rate used to turn non-refundable credit amounts into tax credits.
+pub const LOWEST_FEDERAL_RATE: Decimal = dec!(0.15);
+
+/// Lower thresholds of the 20.5% through 33% brackets, as indexed by the CRA
+/// for each year.
+fn federal_thresholds(year: u32) -> Result<[Decimal; 4], TaxError> {
+    let thresholds = match year {
+        2023 => [dec!(53_359), dec!(106_717), dec!(165_430), dec!(235_675)],
+        2024 => [dec!(55_867), dec!(111_733), dec!(173_205), dec!(246_752)],
+        _ => return Err(TaxError::UnsupportedYear(year)),
+    };
+
+    Ok(thresholds)
+}
+
+/// Builds the federal schedule for a year with the dividend tax credits and the
+/// stock option deduction registered. Dividends go in taxable income at their
+/// grossed-up amount.
+pub fn federal_schedule(year: u32) -> Result<TaxSchedule, TaxError> {
+    let mut schedule = TaxSchedule::from_thresholds(Currency::CAD, &federal_thresholds(year)?, &FEDERAL_RATES)?;
+    set_dividend_rules(&mut schedule, CanadianJurisdiction::Federal, year)?;
+    let stock_option_rule = stock_option_deduction_rule(CanadianJurisdiction::Federal);
+    schedule.set_deduction(stock_option_rule.tax_deduction_type, stock_option_rule);
+
+    Ok(schedule)
+}
+
+/// The federal basic personal amount. The enhanced part added since 2020
+/// phases out between the tops of the 26% and 29% brackets.
+pub fn basic_personal_amount(year: u32, net_income: Money) -> Result<Money, TaxError> {
+    let (maximum, minimum) = match year {
+        2023 => (dec!(15_000), dec!(13_521)),
+        2024 => (dec!(15_705), dec!(14_156)),
+        _ => return Err(TaxError::UnsupportedYear(year)),
+    };
+    let thresholds = federal_thresholds(year)?;
+    let phase_out_start = thresholds[2];
+    let phase_out_end = thresholds[3];
+
+    let income = net_income.amount.max(phase_out_start).min(phase_out_end);
+    let reduction = (maximum - minimum) * (income - phase_out_start) / (phase_out_end - phase_out_start);
+
+    Ok(Money { amount: maximum - reduction, currency: Currency::CAD })
+}
+
+/// Federal tax on taxable income after the basic personal amount credit,
+/// before other credits.
+pub fn basic_federal_tax(year: u32, taxable_income: Money) -> Result<Money, TaxError> {
+    let tax = federal_schedule(year)?.calculate_tax(taxable_income)
+        - basic_personal_amount(year, taxable_income)? * LOWEST_FEDERAL_RATE;
+    Ok(tax.max(cad_money!(0)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn federal_tax_by_bracket() {
+        let schedule = federal_schedule(2024).unwrap();
+        assert_eq!(schedule.calculate_tax(cad_money!(100_000)), cad_money!(17_427.315));
+        assert_eq!(schedule.calculate_tax(cad_money!(300_000)), cad_money!(74_715.77));
+    }
+
+    #[test]
+    fn basic_personal_amount_phase_out() {
+        assert_eq!(basic_personal_amount(2024, cad_money!(80_000)).unwrap(), cad_money!(15_705));
+        assert_eq!(basic_personal_amount(2024, cad_money!(300_000)).unwrap(), cad_money!(14_156));
+        assert_eq!(basic_personal_amount(2021, cad_money!(80_000)).unwrap_err(), TaxError::UnsupportedYear(2021));
+    }
+}
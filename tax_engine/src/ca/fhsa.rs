@@ -0,0 +1,263 @@
+use chrono::{Datelike, NaiveDate};
+use rust_decimal_macros::*;
+use simple_money::*;
+use crate::TaxError;
+use super::REGISTERED_PENALTY_RATE_PER_MONTH;
+
+/// Participation room added each year the account is open.
+pub const FHSA_ANNUAL_LIMIT: Money = Money { amount: dec!(8_000), currency: Currency::CAD };
+
+/// Most that can ever be contributed to a holder's FHSAs.
+pub const FHSA_LIFETIME_LIMIT: Money = Money { amount: dec!(40_000), currency: Currency::CAD };
+
+/// FHSAs could first be opened in 2023.
+const FIRST_FHSA_YEAR: u32 = 2023;
+
+/// The home a withdrawal is made to buy.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FirstHomePurchase {
+    /// The holder did not live in a home they or their spouse owned in the
+    /// year of the withdrawal or the four calendar years before it.
+    pub first_time_home_buyer: bool,
+    pub intends_to_occupy: bool,
+    pub agreement_date: NaiveDate,
+    pub acquisition_date: NaiveDate,
+}
+
+impl FirstHomePurchase {
+    /// Whether a withdrawal made on `withdrawal_date` is tax free. The home
+    /// must be bought, or agreed to be bought, before October 1 of the
+    /// following year.
+    pub fn qualifies(&self, withdrawal_date: NaiveDate) -> bool {
+        let deadline = NaiveDate::from_ymd_opt(withdrawal_date.year() + 1, 10, 1).unwrap();
+        self.first_time_home_buyer
+            && self.intends_to_occupy
+            && self.agreement_date < deadline
+            && self.acquisition_date < deadline
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FhsaTransactionKind {
+    Contribution(Money),
+    /// Moved in from an RRSP. Uses room but is not deductible.
+    TransferFromRrsp(Money),
+    /// Tax free when `home_purchase` qualifies, otherwise included in income.
+    Withdrawal { amount: Money, home_purchase: Option<FirstHomePurchase> },
+    TransferToRrsp(Money),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FhsaTransaction {
+    pub date: NaiveDate,
+    pub kind: FhsaTransactionKind,
+}
+
+/// One year of FHSA activity, as on Form RC727.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FhsaYear {
+    pub year: u32,
+    pub participation_room: Money,
+    /// Contributions and transfers from an RRSP.
+    pub contributions: Money,
+    /// Room left at the end of the year. Negative while there is an excess.
+    pub unused_room: Money,
+    pub qualifying_withdrawals: Money,
+    /// Withdrawals that must be included in income.
+    pub taxable_withdrawals: Money,
+    pub highest_excess: Money,
+    pub penalty_tax: Money,
+    /// The account must be closed by this date once a qualifying withdrawal
+    /// has been made.
+    pub closing_deadline: Option<NaiveDate>,
+}
+
+/// One person's FHSA participation room, tracked across years. Withdrawals
+/// and transfers out never restore room, though they can cancel an excess.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FhsaRoom {
+    opened_year: u32,
+    last_recorded_year: Option<u32>,
+    unused_room: Money,
+    lifetime_contributions: Money,
+    closing_deadline: Option<NaiveDate>,
+    ledger: Vec<FhsaYear>,
+}
+
+impl FhsaRoom {
+    /// Room only starts accruing in the year the first FHSA is opened.
+    pub fn new(opened_year: u32) -> Result<FhsaRoom, TaxError> {
+        if opened_year < FIRST_FHSA_YEAR {
+            return Err(TaxError::UnsupportedYear(opened_year));
+        }
+        Ok(FhsaRoom {
+            opened_year,
+            last_recorded_year: None,
+            unused_room: cad_money!(0),
+            lifetime_contributions: cad_money!(0),
+            closing_deadline: None,
+            ledger: vec![],
+        })
+    }
+
+    pub fn lifetime_contributions(&self) -> Money {
+        self.lifetime_contributions
+    }
+
+    pub fn ledger(&self) -> &[FhsaYear] {
+        &self.ledger
+    }
+
+    /// The annual limit plus up to one year's worth of room left unused last
+    /// year, within what remains of the lifetime limit.
+    pub fn participation_room(&self, year: u32) -> Money {
+        if year < self.opened_year {
+            return cad_money!(0);
+        }
+        let carried = match self.last_recorded_year {
+            Some(last) if last + 1 == year => self.unused_room.min(FHSA_ANNUAL_LIMIT),
+            _ if year > self.opened_year => FHSA_ANNUAL_LIMIT,
+            _ => cad_money!(0),
+        };
+        (FHSA_ANNUAL_LIMIT + carried).min(FHSA_LIFETIME_LIMIT - self.lifetime_contributions)
+    }
+
+    /// Records a year's contributions, transfers and withdrawals.
+    pub fn record_year(&mut self, year: u32, transactions: &[FhsaTransaction]) -> Result<FhsaYear, TaxError> {
+        let participation_room = self.participation_room(year);
+        let zero = cad_money!(0);
+        let mut transactions: Vec<&FhsaTransaction> =
+            transactions.iter().filter(|transaction| transaction.date.year() as u32 == year).collect();
+        transactions.sort_by_key(|transaction| transaction.date);
+
+        let mut available = participation_room;
+        let mut contributions = zero;
+        let mut qualifying_withdrawals = zero;
+        let mut taxable_withdrawals = zero;
+        let mut highest_excess = zero;
+        let mut penalty_tax = zero;
+        for month in 1..=12 {
+            let mut month_excess = (zero - available).max(zero);
+            for transaction in transactions.iter().filter(|transaction| transaction.date.month() == month) {
+                let amount = match transaction.kind {
+                    FhsaTransactionKind::Contribution(amount) | FhsaTransactionKind::TransferFromRrsp(amount) => amount,
+                    FhsaTransactionKind::Withdrawal { amount, .. } | FhsaTransactionKind::TransferToRrsp(amount) => amount,
+                };
+                if amount.currency != Currency::CAD {
+                    return Err(TaxError::MismatchedCurrencies);
+                }
+                match transaction.kind {
+                    FhsaTransactionKind::Contribution(_) | FhsaTransactionKind::TransferFromRrsp(_) => {
+                        available = available - amount;
+                        contributions = contributions + amount;
+                    }
+                    FhsaTransactionKind::Withdrawal { home_purchase, .. } => {
+                        available = available + amount.min((zero - available).max(zero));
+                        if home_purchase.is_some_and(|purchase| purchase.qualifies(transaction.date)) {
+                            qualifying_withdrawals = qualifying_withdrawals + amount;
+                            if self.closing_deadline.is_none() {
+                                self.closing_deadline = NaiveDate::from_ymd_opt(transaction.date.year() + 1, 12, 31);
+                            }
+                        }else{
+                            taxable_withdrawals = taxable_withdrawals + amount;
+                        }
+                    }
+                    FhsaTransactionKind::TransferToRrsp(_) => {
+                        available = available + amount.min((zero - available).max(zero));
+                    }
+                }
+                month_excess = month_excess.max((zero - available).max(zero));
+            }
+            highest_excess = highest_excess.max(month_excess);
+            penalty_tax = penalty_tax + month_excess * REGISTERED_PENALTY_RATE_PER_MONTH;
+        }
+
+        self.last_recorded_year = Some(year);
+        self.unused_room = available;
+        self.lifetime_contributions = self.lifetime_contributions + contributions;
+        let entry = FhsaYear {
+            year,
+            participation_room,
+            contributions,
+            unused_room: available,
+            qualifying_withdrawals,
+            taxable_withdrawals,
+            highest_excess,
+            penalty_tax,
+            closing_deadline: self.closing_deadline,
+        };
+        self.ledger.push(entry);
+        Ok(entry)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    fn contribution(year: i32, amount: Money) -> FhsaTransaction {
+        FhsaTransaction { date: date(year, 3, 1), kind: FhsaTransactionKind::Contribution(amount) }
+    }
+
+    fn home_purchase(agreement_date: NaiveDate) -> FirstHomePurchase {
+        FirstHomePurchase {
+            first_time_home_buyer: true,
+            intends_to_occupy: true,
+            agreement_date,
+            acquisition_date: agreement_date,
+        }
+    }
+
+    #[test]
+    fn one_year_carry_forward_within_the_lifetime_limit() {
+        let mut room = FhsaRoom::new(2023).unwrap();
+        room.record_year(2023, &[contribution(2023, cad_money!(3_000))]).unwrap();
+        assert_eq!(room.participation_room(2024), cad_money!(13_000));
+
+        room.record_year(2024, &[contribution(2024, cad_money!(13_000))]).unwrap();
+        room.record_year(2025, &[contribution(2025, cad_money!(8_000))]).unwrap();
+        // Nothing was recorded for 2026, so its whole limit carries to 2027.
+        assert_eq!(room.participation_room(2027), cad_money!(16_000));
+
+        room.record_year(2027, &[contribution(2027, cad_money!(16_000))]).unwrap();
+        assert_eq!(room.lifetime_contributions(), FHSA_LIFETIME_LIMIT);
+        assert_eq!(room.participation_room(2028), cad_money!(0));
+    }
+
+    #[test]
+    fn qualifying_withdrawal_cancels_an_excess_and_starts_the_closing_clock() {
+        let mut room = FhsaRoom::new(2024).unwrap();
+        let year = room
+            .record_year(2024, &[
+                FhsaTransaction { date: date(2024, 2, 1), kind: FhsaTransactionKind::Contribution(cad_money!(10_000)) },
+                FhsaTransaction {
+                    date: date(2024, 5, 15),
+                    kind: FhsaTransactionKind::Withdrawal {
+                        amount: cad_money!(12_000),
+                        home_purchase: Some(home_purchase(date(2024, 5, 1))),
+                    },
+                },
+            ])
+            .unwrap();
+        assert_eq!(year.highest_excess, cad_money!(2_000));
+        assert_eq!(year.penalty_tax, cad_money!(80));
+        assert_eq!(year.qualifying_withdrawals, cad_money!(12_000));
+        assert_eq!(year.taxable_withdrawals, cad_money!(0));
+        assert_eq!(year.closing_deadline, Some(date(2025, 12, 31)));
+        assert_eq!(year.unused_room, cad_money!(0));
+    }
+
+    #[test]
+    fn late_or_repeat_buyers_do_not_qualify() {
+        let withdrawal_date = date(2024, 5, 15);
+        assert!(home_purchase(date(2025, 9, 30)).qualifies(withdrawal_date));
+        assert!(!home_purchase(date(2025, 10, 1)).qualifies(withdrawal_date));
+        let repeat_buyer = FirstHomePurchase { first_time_home_buyer: false, ..home_purchase(date(2024, 5, 1)) };
+        assert!(!repeat_buyer.qualifies(withdrawal_date));
+        assert_eq!(FhsaRoom::new(2022).unwrap_err(), TaxError::UnsupportedYear(2022));
+    }
+}
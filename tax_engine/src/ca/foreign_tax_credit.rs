@@ -0,0 +1,202 @@
+use rust_decimal::prelude::*;
+use rust_decimal_macros::*;
+use simple_money::*;
+use crate::TaxError;
+
+/// Individuals can only credit foreign tax on income from property up to
+/// this share of the income. The rest is deductible under subsection 20(11).
+pub const PROPERTY_INCOME_CREDIT_LIMIT: Decimal = dec!(0.15);
+
+/// Years unused business-income tax can be carried forward.
+const BUSINESS_CARRYFORWARD_YEARS: u32 = 10;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ForeignIncomeKind {
+    /// Income from a business carried on in the foreign country.
+    Business,
+    /// Non-business income other than from property, such as employment
+    /// income or pensions.
+    NonBusiness,
+    /// Non-business income from property, such as dividends and interest.
+    Property,
+}
+
+/// Income from one foreign country and the tax paid on it, in Canadian
+/// dollars.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ForeignIncome {
+    pub kind: ForeignIncomeKind,
+    pub income: Money,
+    pub tax_paid: Money,
+}
+
+/// One year's section 126 credits for one country, as on Form T2209.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CanadianForeignTaxCreditYear {
+    pub year: u32,
+    pub federal_non_business_credit: Money,
+    pub federal_business_credit: Money,
+    /// Non-business tax left over after the federal credit, credited
+    /// against provincial tax.
+    pub provincial_credit: Money,
+    /// Tax on property income above the 15% limit.
+    pub deductible_foreign_tax: Money,
+    pub business_carryforward_used: Money,
+    /// This year's business-income tax that could not be credited.
+    pub unused_business_tax: Money,
+}
+
+impl CanadianForeignTaxCreditYear {
+    pub fn total(&self) -> Money {
+        self.federal_non_business_credit + self.federal_business_credit + self.provincial_credit
+    }
+}
+
+/// Section 126 foreign tax credits for income from one country, tracked
+/// across years. The limits are worked out country by country, so keep one
+/// per country. Unused business-income tax is carried forward ten years;
+/// carrying it back means reassessing earlier years and is left to the
+/// caller.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CanadianForeignTaxCredit {
+    carryforwards: Vec<(u32, Money)>,
+    ledger: Vec<CanadianForeignTaxCreditYear>,
+}
+
+impl CanadianForeignTaxCredit {
+    pub fn new() -> CanadianForeignTaxCredit {
+        CanadianForeignTaxCredit { carryforwards: vec![], ledger: vec![] }
+    }
+
+    pub fn ledger(&self) -> &[CanadianForeignTaxCreditYear] {
+        &self.ledger
+    }
+
+    /// Unused business-income tax that can still be claimed in `year`.
+    pub fn business_carryforward(&self, year: u32) -> Money {
+        self.carryforwards
+            .iter()
+            .filter(|(from, _)| *from < year && year <= from + BUSINESS_CARRYFORWARD_YEARS)
+            .fold(cad_money!(0), |sum, (_, amount)| sum + *amount)
+    }
+
+    /// Credits for one year. `net_income` is the denominator of the
+    /// limitation fractions. `federal_tax` and `provincial_tax` are the tax
+    /// otherwise payable, after non-refundable credits.
+    pub fn record_year(
+        &mut self,
+        year: u32,
+        net_income: Money,
+        federal_tax: Money,
+        provincial_tax: Money,
+        foreign_income: &[ForeignIncome],
+    ) -> Result<CanadianForeignTaxCreditYear, TaxError> {
+        let mut amounts = vec![net_income, federal_tax, provincial_tax];
+        amounts.extend(foreign_income.iter().flat_map(|income| [income.income, income.tax_paid]));
+        if amounts.iter().any(|money| money.currency != Currency::CAD) {
+            return Err(TaxError::MismatchedCurrencies);
+        }
+
+        let zero = cad_money!(0);
+        let sum = |kinds: &[ForeignIncomeKind], field: fn(&ForeignIncome) -> Money| {
+            foreign_income
+                .iter()
+                .filter(|income| kinds.contains(&income.kind))
+                .fold(zero, |sum, income| sum + field(income))
+        };
+        let share = |income: Money| {
+            if net_income > zero {
+                (income.amount / net_income.amount).max(dec!(0)).min(dec!(1))
+            }else{
+                dec!(0)
+            }
+        };
+
+        let property_income = sum(&[ForeignIncomeKind::Property], |income| income.income);
+        let property_tax = sum(&[ForeignIncomeKind::Property], |income| income.tax_paid);
+        let creditable_property_tax = property_tax.min(property_income.max(zero) * PROPERTY_INCOME_CREDIT_LIMIT);
+        let non_business_income = sum(&[ForeignIncomeKind::NonBusiness, ForeignIncomeKind::Property], |income| income.income);
+        let non_business_tax = sum(&[ForeignIncomeKind::NonBusiness], |income| income.tax_paid) + creditable_property_tax;
+
+        let federal_non_business_credit = non_business_tax.min(federal_tax * share(non_business_income));
+        let provincial_credit =
+            (non_business_tax - federal_non_business_credit).min(provincial_tax * share(non_business_income));
+
+        // This year's business tax is used before amounts carried forward,
+        // and those are used oldest first.
+        let business_income = sum(&[ForeignIncomeKind::Business], |income| income.income);
+        let business_tax = sum(&[ForeignIncomeKind::Business], |income| income.tax_paid);
+        let business_limit = (federal_tax * share(business_income)).min(federal_tax - federal_non_business_credit);
+        let current_business_credit = business_tax.min(business_limit);
+        let unused_business_tax = business_tax - current_business_credit;
+
+        self.carryforwards.retain(|(from, _)| year <= from + BUSINESS_CARRYFORWARD_YEARS);
+        self.carryforwards.sort_by_key(|(from, _)| *from);
+        let mut room = business_limit - current_business_credit;
+        let mut business_carryforward_used = zero;
+        for (_, amount) in self.carryforwards.iter_mut().filter(|(from, _)| *from < year) {
+            let used = (*amount).min(room);
+            *amount = *amount - used;
+            room = room - used;
+            business_carryforward_used = business_carryforward_used + used;
+        }
+        self.carryforwards.retain(|(_, amount)| *amount > zero);
+        if unused_business_tax > zero {
+            self.carryforwards.push((year, unused_business_tax));
+        }
+
+        let entry = CanadianForeignTaxCreditYear {
+            year,
+            federal_non_business_credit,
+            federal_business_credit: current_business_credit + business_carryforward_used,
+            provincial_credit,
+            deductible_foreign_tax: property_tax - creditable_property_tax,
+            business_carryforward_used,
+            unused_business_tax,
+        };
+        self.ledger.push(entry);
+        Ok(entry)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn property_income_credit_is_limited_to_fifteen_percent() {
+        let mut credit = CanadianForeignTaxCredit::new();
+        let dividends = ForeignIncome { kind: ForeignIncomeKind::Property, income: cad_money!(10_000), tax_paid: cad_money!(3_000) };
+        let year = credit.record_year(2024, cad_money!(100_000), cad_money!(15_000), cad_money!(6_000), &[dividends]).unwrap();
+        assert_eq!(year.federal_non_business_credit, cad_money!(1_500));
+        assert_eq!(year.provincial_credit, cad_money!(0));
+        assert_eq!(year.deductible_foreign_tax, cad_money!(1_500));
+    }
+
+    #[test]
+    fn non_business_excess_goes_to_the_province() {
+        let mut credit = CanadianForeignTaxCredit::new();
+        let wages = ForeignIncome { kind: ForeignIncomeKind::NonBusiness, income: cad_money!(50_000), tax_paid: cad_money!(12_000) };
+        let year = credit.record_year(2024, cad_money!(100_000), cad_money!(16_000), cad_money!(7_000), &[wages]).unwrap();
+        assert_eq!(year.federal_non_business_credit, cad_money!(8_000));
+        assert_eq!(year.provincial_credit, cad_money!(3_500));
+        assert_eq!(year.total(), cad_money!(11_500));
+    }
+
+    #[test]
+    fn unused_business_tax_is_carried_forward_ten_years() {
+        let mut credit = CanadianForeignTaxCredit::new();
+        let business = ForeignIncome { kind: ForeignIncomeKind::Business, income: cad_money!(20_000), tax_paid: cad_money!(6_000) };
+        let year = credit.record_year(2024, cad_money!(100_000), cad_money!(20_000), cad_money!(8_000), &[business]).unwrap();
+        assert_eq!(year.federal_business_credit, cad_money!(4_000));
+        assert_eq!(year.unused_business_tax, cad_money!(2_000));
+        assert_eq!(credit.business_carryforward(2034), cad_money!(2_000));
+        assert_eq!(credit.business_carryforward(2035), cad_money!(0));
+
+        let business = ForeignIncome { kind: ForeignIncomeKind::Business, income: cad_money!(20_000), tax_paid: cad_money!(3_000) };
+        let year = credit.record_year(2025, cad_money!(100_000), cad_money!(20_000), cad_money!(8_000), &[business]).unwrap();
+        assert_eq!(year.business_carryforward_used, cad_money!(1_000));
+        assert_eq!(year.federal_business_credit, cad_money!(4_000));
+        assert_eq!(credit.business_carryforward(2026), cad_money!(1_000));
+    }
+}
@@ -0,0 +1,315 @@
+use chrono::{Datelike, Duration, NaiveDate};
+use rust_decimal::prelude::*;
+use rust_decimal_macros::*;
+use simple_money::*;
+use crate::{days_in_year, next_business_day, round_cents, TaxError};
+use super::{basic_federal_tax, federal_schedule, provincial_tax, CanadianJurisdiction};
+
+/// Installments are required when net tax owing is above this amount in the
+/// current year and in either of the two previous years. Quebec residents
+/// use $1,800 of federal tax instead.
+pub const INSTALLMENT_THRESHOLD: Money = Money { amount: dec!(3_000), currency: Currency::CAD };
+
+/// Installment interest above this amount attracts the installment penalty.
+const INSTALLMENT_PENALTY_THRESHOLD: Money = Money { amount: dec!(1_000), currency: Currency::CAD };
+
+/// The prescribed rate on overdue taxes, set each quarter. It is
+/// compounded daily.
+///
+/// Source: the CRA's quarterly prescribed interest rate announcements,
+/// <https://www.canada.ca/en/revenue-agency/services/tax/prescribed-interest-rates.html>.
+pub fn overdue_interest_rate(date: NaiveDate) -> Result<Decimal, TaxError> {
+    let quarter = (date.month() - 1) / 3 + 1;
+    let rate = match (date.year(), quarter) {
+        (2024, 1..=3) => dec!(0.10),
+        (2024, _) => dec!(0.09),
+        (2025, 1) => dec!(0.08),
+        (2025, 2) => dec!(0.08),
+        (year, _) => return Err(TaxError::UnsupportedYear(year as u32)),
+    };
+
+    Ok(rate)
+}
+
+/// Ways of working out the installments due, as described in the CRA's
+/// installment guide.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum InstallmentOption {
+    /// The amounts on the CRA's installment reminders, based on the two
+    /// previous years. Paying these never attracts interest.
+    NoCalculation,
+    PriorYear,
+    CurrentYear,
+}
+
+/// Income received during the year and any tax withheld from it at source.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InstallmentEvent {
+    pub date: NaiveDate,
+    /// The amount added to taxable income, such as salary or the taxable
+    /// part of a capital gain.
+    pub taxable_income: Money,
+    pub tax_withheld: Money,
+}
+
+const INSTALLMENT_OPTIONS: [InstallmentOption; 3] =
+    [InstallmentOption::NoCalculation, InstallmentOption::PriorYear, InstallmentOption::CurrentYear];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InstallmentPayment {
+    pub due_date: NaiveDate,
+    pub amount: Money,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CanadianInstallmentPlan {
+    pub required: bool,
+    /// Estimated for the current year from the events recorded so far.
+    pub net_tax_owing: Money,
+    /// The option asking for the least. Empty when installments are not
+    /// required.
+    pub option: Option<InstallmentOption>,
+    pub payments: Vec<InstallmentPayment>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InstallmentInterest {
+    pub interest: Money,
+    pub penalty: Money,
+}
+
+/// Plans quarterly installments for a Canadian resident from the year's
+/// income events and the net tax owing in the two previous years.
+#[derive(Clone, Debug)]
+pub struct CanadianInstallmentPlanner {
+    year: u32,
+    province: CanadianJurisdiction,
+    net_tax_owing_last_year: Money,
+    net_tax_owing_two_years_ago: Money,
+    events: Vec<InstallmentEvent>,
+}
+
+impl CanadianInstallmentPlanner {
+    pub fn new(
+        year: u32,
+        province: CanadianJurisdiction,
+        net_tax_owing_last_year: Money,
+        net_tax_owing_two_years_ago: Money,
+    ) -> Result<CanadianInstallmentPlanner, TaxError> {
+        if net_tax_owing_last_year.currency != Currency::CAD || net_tax_owing_two_years_ago.currency != Currency::CAD {
+            return Err(TaxError::MismatchedCurrencies);
+        }
+        federal_schedule(year)?;
+        provincial_tax(province, year, cad_money!(0), cad_money!(0))?;
+        Ok(CanadianInstallmentPlanner {
+            year,
+            province,
+            net_tax_owing_last_year,
+            net_tax_owing_two_years_ago,
+            events: vec![],
+        })
+    }
+
+    pub fn record(&mut self, event: InstallmentEvent) -> Result<(), TaxError> {
+        if event.taxable_income.currency != Currency::CAD || event.tax_withheld.currency != Currency::CAD {
+            return Err(TaxError::MismatchedCurrencies);
+        }
+        self.events.push(event);
+        Ok(())
+    }
+
+    /// March 15, June 15, September 15 and December 15, moved off weekends.
+    pub fn due_dates(&self) -> Vec<NaiveDate> {
+        [3, 6, 9, 12]
+            .iter()
+            .map(|month| next_business_day(NaiveDate::from_ymd_opt(self.year as i32, *month, 15).unwrap()))
+            .collect()
+    }
+
+    /// Federal and provincial tax on the income recorded so far, less tax
+    /// withheld at source.
+    pub fn net_tax_owing(&self) -> Result<Money, TaxError> {
+        let zero = cad_money!(0);
+        let taxable_income = self.events.iter().fold(zero, |sum, event| sum + event.taxable_income);
+        let withheld = self.events.iter().fold(zero, |sum, event| sum + event.tax_withheld);
+        let federal_tax = basic_federal_tax(self.year, taxable_income)?;
+        let provincial_tax = provincial_tax(self.province, self.year, taxable_income, zero)?;
+
+        Ok((federal_tax + provincial_tax - withheld).max(zero))
+    }
+
+    pub fn installments_required(&self) -> Result<bool, TaxError> {
+        Ok(self.net_tax_owing()? > INSTALLMENT_THRESHOLD
+            && (self.net_tax_owing_last_year > INSTALLMENT_THRESHOLD
+                || self.net_tax_owing_two_years_ago > INSTALLMENT_THRESHOLD))
+    }
+
+    pub fn payments(&self, option: InstallmentOption) -> Result<Vec<InstallmentPayment>, TaxError> {
+        let quarter = dec!(0.25);
+        let amounts = match option {
+            InstallmentOption::NoCalculation => {
+                let early = self.net_tax_owing_two_years_ago * quarter;
+                let late = ((self.net_tax_owing_last_year - early * dec!(2)) * dec!(0.5)).max(cad_money!(0));
+                [early, early, late, late]
+            }
+            InstallmentOption::PriorYear => [self.net_tax_owing_last_year * quarter; 4],
+            InstallmentOption::CurrentYear => [self.net_tax_owing()? * quarter; 4],
+        };
+
+        Ok(self
+            .due_dates()
+            .into_iter()
+            .zip(amounts.iter())
+            .map(|(due_date, amount)| InstallmentPayment { due_date, amount: *amount })
+            .collect())
+    }
+
+    /// Uses the option asking for the least in total. Ties go to the
+    /// reminder amounts, which defer more of the tax to later installments.
+    pub fn plan(&self) -> Result<CanadianInstallmentPlan, TaxError> {
+        let net_tax_owing = self.net_tax_owing()?;
+        if !self.installments_required()? {
+            return Ok(CanadianInstallmentPlan { required: false, net_tax_owing, option: None, payments: vec![] });
+        }
+
+        let mut plans = vec![];
+        for option in INSTALLMENT_OPTIONS.iter() {
+            plans.push((*option, self.payments(*option)?));
+        }
+        let (option, payments) = plans.into_iter().min_by_key(|(_, payments)| total(payments)).unwrap();
+
+        Ok(CanadianInstallmentPlan { required: true, net_tax_owing, option: Some(option), payments })
+    }
+
+    /// Installment interest on the payments actually made, up to the balance
+    /// due date of April 30 next year. The CRA charges the least interest of
+    /// the three options and credits early payments against late ones. Half
+    /// of the interest above the greater of $1,000 and a quarter of the
+    /// interest with no installments is charged again as a penalty.
+    pub fn interest(&self, payments_made: &[(NaiveDate, Money)]) -> Result<InstallmentInterest, TaxError> {
+        if payments_made.iter().any(|(_, amount)| amount.currency != Currency::CAD) {
+            return Err(TaxError::MismatchedCurrencies);
+        }
+        if !self.installments_required()? {
+            return Ok(InstallmentInterest { interest: cad_money!(0), penalty: cad_money!(0) });
+        }
+
+        let mut interest: Option<Money> = None;
+        let mut interest_without_payments: Option<Money> = None;
+        for option in INSTALLMENT_OPTIONS.iter() {
+            let required = self.payments(*option)?;
+            let charged = self.interest_on(&required, payments_made)?;
+            let unpaid = self.interest_on(&required, &[])?;
+            interest = Some(interest.map_or(charged, |least| least.min(charged)));
+            interest_without_payments = Some(interest_without_payments.map_or(unpaid, |least| least.min(unpaid)));
+        }
+        let interest = interest.unwrap();
+
+        let allowance = INSTALLMENT_PENALTY_THRESHOLD.max(interest_without_payments.unwrap() * dec!(0.25));
+        let penalty = if interest > INSTALLMENT_PENALTY_THRESHOLD {
+            (interest - allowance).max(cad_money!(0)) * dec!(0.5)
+        }else{
+            cad_money!(0)
+        };
+
+        Ok(InstallmentInterest { interest: round_cents(interest), penalty: round_cents(penalty) })
+    }
+
+    /// Compounds interest daily on the shortfall between `required` and
+    /// `payments_made`. A surplus earns credit at the same rate, which only
+    /// offsets interest charged.
+    fn interest_on(&self, required: &[InstallmentPayment], payments_made: &[(NaiveDate, Money)]) -> Result<Money, TaxError> {
+        let zero = cad_money!(0);
+        let balance_due_date = NaiveDate::from_ymd_opt(self.year as i32 + 1, 4, 30).unwrap();
+        let mut date = required
+            .iter()
+            .map(|payment| payment.due_date)
+            .chain(payments_made.iter().map(|(paid, _)| *paid))
+            .min()
+            .unwrap();
+        let mut shortfall = zero;
+        let mut interest = zero;
+        while date < balance_due_date {
+            shortfall = shortfall
+                + required.iter().filter(|payment| payment.due_date == date).fold(zero, |sum, payment| sum + payment.amount)
+                - payments_made.iter().filter(|(paid, _)| *paid == date).fold(zero, |sum, (_, amount)| sum + *amount);
+            interest = interest + (shortfall + interest) * (overdue_interest_rate(date)? / days_in_year(date));
+            date += Duration::days(1);
+        }
+
+        Ok(interest.max(zero))
+    }
+}
+
+fn total(payments: &[InstallmentPayment]) -> Money {
+    payments.iter().fold(cad_money!(0), |sum, payment| sum + payment.amount)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    fn planner() -> CanadianInstallmentPlanner {
+        let mut planner =
+            CanadianInstallmentPlanner::new(2024, CanadianJurisdiction::Ontario, cad_money!(8_000), cad_money!(4_000)).unwrap();
+        planner
+            .record(InstallmentEvent { date: date(2024, 1, 31), taxable_income: cad_money!(100_000), tax_withheld: cad_money!(0) })
+            .unwrap();
+        planner
+    }
+
+    #[test]
+    fn reminder_amounts_win_ties_with_the_prior_year_option() {
+        let plan = planner().plan().unwrap();
+        assert!(plan.required);
+        assert_eq!(plan.net_tax_owing, cad_money!(22_408.2424));
+        assert_eq!(plan.option, Some(InstallmentOption::NoCalculation));
+        assert_eq!(
+            plan.payments,
+            vec![
+                InstallmentPayment { due_date: date(2024, 3, 15), amount: cad_money!(1_000) },
+                InstallmentPayment { due_date: date(2024, 6, 17), amount: cad_money!(1_000) },
+                InstallmentPayment { due_date: date(2024, 9, 16), amount: cad_money!(3_000) },
+                InstallmentPayment { due_date: date(2024, 12, 16), amount: cad_money!(3_000) },
+            ]
+        );
+    }
+
+    #[test]
+    fn withholding_below_the_threshold_needs_no_installments() {
+        let mut planner = planner();
+        planner
+            .record(InstallmentEvent { date: date(2024, 12, 31), taxable_income: cad_money!(0), tax_withheld: cad_money!(20_000) })
+            .unwrap();
+        assert!(!planner.plan().unwrap().required);
+        assert_eq!(planner.interest(&[]).unwrap().interest, cad_money!(0));
+    }
+
+    #[test]
+    fn interest_on_late_installments() {
+        assert_eq!(overdue_interest_rate(date(2024, 9, 30)).unwrap(), dec!(0.10));
+        assert_eq!(overdue_interest_rate(date(2024, 10, 1)).unwrap(), dec!(0.09));
+        assert_eq!(overdue_interest_rate(date(2025, 4, 29)).unwrap(), dec!(0.08));
+
+        let planner = planner();
+        assert_eq!(planner.interest(&[]).unwrap().interest, cad_money!(443.67));
+        let late = planner.interest(&[(date(2024, 12, 16), cad_money!(8_000))]).unwrap();
+        assert_eq!(late.interest, cad_money!(199.92));
+        assert_eq!(late.penalty, cad_money!(0));
+
+        let mut planner =
+            CanadianInstallmentPlanner::new(2024, CanadianJurisdiction::Ontario, cad_money!(80_000), cad_money!(40_000)).unwrap();
+        planner
+            .record(InstallmentEvent { date: date(2024, 5, 1), taxable_income: cad_money!(300_000), tax_withheld: cad_money!(0) })
+            .unwrap();
+        let missed = planner.interest(&[]).unwrap();
+        assert_eq!(missed.interest, cad_money!(4_436.70));
+        assert_eq!(missed.penalty, cad_money!(1_663.76));
+        let early = planner.interest(&[(date(2024, 1, 15), cad_money!(80_000))]).unwrap();
+        assert_eq!(early.interest, cad_money!(0));
+    }
+}
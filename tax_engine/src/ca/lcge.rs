@@ -0,0 +1,158 @@
+use chrono::{Datelike, NaiveDate};
+use rust_decimal_macros::*;
+use simple_money::*;
+use crate::TaxError;
+use super::CAPITAL_GAINS_INCLUSION_RATE;
+
+/// Lifetime limit on capital gains from qualified small business
+/// corporation shares that can be sheltered, as of the disposition date.
+pub fn lifetime_capital_gains_limit(disposition_date: NaiveDate) -> Result<Money, TaxError> {
+    let increase_date = NaiveDate::from_ymd_opt(2024, 6, 25).unwrap();
+    let year = disposition_date.year() as u32;
+
+    match year {
+        2023 => Ok(cad_money!(971_190)),
+        2024 if disposition_date < increase_date => Ok(cad_money!(1_016_836)),
+        2024 | 2025 => Ok(cad_money!(1_250_000)),
+        _ => Err(TaxError::UnsupportedYear(year)),
+    }
+}
+
+/// The deduction allowed for one year's QSBC share gains.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LcgeClaim {
+    pub taxable_capital_gain: Money,
+    /// Deducted from taxable income, so the matching gain is tax free.
+    pub deduction: Money,
+    /// Deduction that was otherwise available but blocked by the cumulative
+    /// net investment loss.
+    pub restricted_by_cnil: Money,
+}
+
+/// An individual's lifetime capital gains exemption, tracked across years.
+///
+/// All amounts are kept at the taxable (included) level, as on Form T657.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LifetimeCapitalGainsExemption {
+    deductions_claimed: Money,
+    cumulative_taxable_capital_gains: Money,
+    cumulative_investment_income: Money,
+    cumulative_investment_expenses: Money,
+}
+
+impl Default for LifetimeCapitalGainsExemption {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LifetimeCapitalGainsExemption {
+    pub fn new() -> LifetimeCapitalGainsExemption {
+        LifetimeCapitalGainsExemption {
+            deductions_claimed: cad_money!(0),
+            cumulative_taxable_capital_gains: cad_money!(0),
+            cumulative_investment_income: cad_money!(0),
+            cumulative_investment_expenses: cad_money!(0),
+        }
+    }
+
+    pub fn deductions_claimed(&self) -> Money {
+        self.deductions_claimed
+    }
+
+    /// Adds a year's investment income and carrying charges, such as interest
+    /// on money borrowed to invest, to the running CNIL account.
+    pub fn record_investment_income(&mut self, income: Money, expenses: Money) -> Result<(), TaxError> {
+        if income.currency != Currency::CAD || expenses.currency != Currency::CAD {
+            return Err(TaxError::MismatchedCurrencies);
+        }
+        self.cumulative_investment_income = self.cumulative_investment_income + income;
+        self.cumulative_investment_expenses = self.cumulative_investment_expenses + expenses;
+        Ok(())
+    }
+
+    /// Investment expenses claimed in excess of investment income since 1988.
+    pub fn cumulative_net_investment_loss(&self) -> Money {
+        (self.cumulative_investment_expenses - self.cumulative_investment_income).max(cad_money!(0))
+    }
+
+    /// Deduction room left under the lifetime limit on `disposition_date`.
+    pub fn unused_deduction(&self, disposition_date: NaiveDate) -> Result<Money, TaxError> {
+        let limit = lifetime_capital_gains_limit(disposition_date)? * CAPITAL_GAINS_INCLUSION_RATE;
+        Ok((limit - self.deductions_claimed).max(cad_money!(0)))
+    }
+
+    /// Claims the deduction for a year's net gain on QSBC shares and records
+    /// it against the lifetime limit.
+    ///
+    /// The claim is the least of the unused lifetime room, this year's taxable
+    /// gain, and the cumulative gains limit: taxable gains to date, less
+    /// deductions already claimed and the CNIL.
+    pub fn claim(&mut self, disposition_date: NaiveDate, capital_gain: Money) -> Result<LcgeClaim, TaxError> {
+        if capital_gain.currency != Currency::CAD {
+            return Err(TaxError::MismatchedCurrencies);
+        }
+        let zero = cad_money!(0);
+        let taxable_capital_gain = capital_gain.max(zero) * CAPITAL_GAINS_INCLUSION_RATE;
+        self.cumulative_taxable_capital_gains = self.cumulative_taxable_capital_gains + taxable_capital_gain;
+
+        let annual_limit = self.unused_deduction(disposition_date)?.min(taxable_capital_gain);
+        let cumulative_gains_limit = (self.cumulative_taxable_capital_gains - self.deductions_claimed).max(zero);
+        let before_cnil = annual_limit.min(cumulative_gains_limit);
+        let deduction = before_cnil.min((cumulative_gains_limit - self.cumulative_net_investment_loss()).max(zero));
+        self.deductions_claimed = self.deductions_claimed + deduction;
+
+        Ok(LcgeClaim {
+            taxable_capital_gain,
+            deduction,
+            restricted_by_cnil: before_cnil - deduction,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn limit_increase_mid_2024() {
+        assert_eq!(lifetime_capital_gains_limit(date(2024, 6, 24)).unwrap(), cad_money!(1_016_836));
+        assert_eq!(lifetime_capital_gains_limit(date(2024, 6, 25)).unwrap(), cad_money!(1_250_000));
+        assert_eq!(lifetime_capital_gains_limit(date(2019, 1, 1)).unwrap_err(), TaxError::UnsupportedYear(2019));
+    }
+
+    #[test]
+    fn lifetime_usage_across_years() {
+        let mut exemption = LifetimeCapitalGainsExemption::new();
+        let first = exemption.claim(date(2023, 5, 1), cad_money!(600_000)).unwrap();
+        assert_eq!(first.deduction, cad_money!(300_000));
+        assert_eq!(exemption.unused_deduction(date(2023, 12, 31)).unwrap(), cad_money!(185_595));
+
+        // The higher 2024 limit adds room for the earlier founder.
+        let second = exemption.claim(date(2024, 9, 1), cad_money!(1_000_000)).unwrap();
+        assert_eq!(second.taxable_capital_gain, cad_money!(500_000));
+        assert_eq!(second.deduction, cad_money!(325_000));
+        assert_eq!(exemption.deductions_claimed(), cad_money!(625_000));
+        assert_eq!(exemption.unused_deduction(date(2025, 1, 1)).unwrap(), cad_money!(0));
+    }
+
+    #[test]
+    fn cnil_restricts_the_claim() {
+        let mut exemption = LifetimeCapitalGainsExemption::new();
+        exemption.record_investment_income(cad_money!(5_000), cad_money!(45_000)).unwrap();
+        assert_eq!(exemption.cumulative_net_investment_loss(), cad_money!(40_000));
+
+        let restricted = exemption.claim(date(2024, 8, 1), cad_money!(200_000)).unwrap();
+        assert_eq!(restricted.deduction, cad_money!(60_000));
+        assert_eq!(restricted.restricted_by_cnil, cad_money!(40_000));
+
+        exemption.record_investment_income(cad_money!(40_000), cad_money!(0)).unwrap();
+        let later = exemption.claim(date(2025, 3, 1), cad_money!(100_000)).unwrap();
+        assert_eq!(later.deduction, cad_money!(50_000));
+        assert_eq!(later.restricted_by_cnil, cad_money!(0));
+    }
+}
@@ -0,0 +1,48 @@
+mod acb;
+mod amt;
+mod capital_losses;
+mod dividends;
+mod federal;
+mod fhsa;
+mod foreign_tax_credit;
+mod installments;
+mod lcge;
+mod payroll;
+mod provincial;
+mod rrsp;
+mod stock_options;
+mod superficial_loss;
+mod tfsa;
+
+pub use acb::*;
+pub use amt::*;
+pub use capital_losses::*;
+pub use dividends::*;
+pub use federal::*;
+pub use fhsa::*;
+pub use foreign_tax_credit::*;
+pub use installments::*;
+pub use lcge::*;
+pub use payroll::*;
+pub use provincial::*;
+pub use rrsp::*;
+pub use stock_options::*;
+pub use superficial_loss::*;
+pub use tfsa::*;
+
+use rust_decimal::prelude::*;
+use rust_decimal_macros::*;
+
+/// Share of a capital gain included in taxable income.
+pub const CAPITAL_GAINS_INCLUSION_RATE: Decimal = dec!(0.5);
+
+/// The Canadian governments that levy personal income tax in the regions
+/// finsim supports.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum CanadianJurisdiction {
+    Federal,
+    Alberta,
+    BritishColumbia,
+    Ontario,
+    Quebec,
+}
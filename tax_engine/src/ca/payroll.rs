@@ -0,0 +1,322 @@
+use rust_decimal::prelude::*;
+use rust_decimal_macros::*;
+use simple_money::*;
+use crate::{round_cents, PayFrequency, TaxError, TaxSchedule};
+use super::{
+    basic_personal_amount, federal_schedule, provincial_schedule, provincial_tax, CanadianJurisdiction,
+    LOWEST_FEDERAL_RATE,
+};
+
+/// CPP and EI figures published with the payroll deductions formulas.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PayrollParameters {
+    pub cpp_rate: Decimal,
+    /// The part of the CPP rate that predates the enhancement. Only this
+    /// part earns a credit; the rest is deducted from income.
+    pub cpp_base_rate: Decimal,
+    pub cpp_basic_exemption: Money,
+    pub year_maximum_pensionable_earnings: Money,
+    pub maximum_cpp: Money,
+    /// The second additional contribution on earnings between the YMPE and
+    /// the year's additional maximum pensionable earnings.
+    pub cpp2_rate: Decimal,
+    pub year_additional_maximum_pensionable_earnings: Money,
+    pub maximum_cpp2: Money,
+    pub ei_rate: Decimal,
+    pub maximum_insurable_earnings: Money,
+    pub maximum_ei: Money,
+    pub canada_employment_amount: Money,
+}
+
+impl PayrollParameters {
+    pub fn for_year(year: u32) -> Result<PayrollParameters, TaxError> {
+        match year {
+            2024 => Ok(PayrollParameters {
+                cpp_rate: dec!(0.0595),
+                cpp_base_rate: dec!(0.0495),
+                cpp_basic_exemption: cad_money!(3_500),
+                year_maximum_pensionable_earnings: cad_money!(68_500),
+                maximum_cpp: cad_money!(3_867.50),
+                cpp2_rate: dec!(0.04),
+                year_additional_maximum_pensionable_earnings: cad_money!(73_200),
+                maximum_cpp2: cad_money!(188),
+                ei_rate: dec!(0.0166),
+                maximum_insurable_earnings: cad_money!(63_200),
+                maximum_ei: cad_money!(1_049.12),
+                canada_employment_amount: cad_money!(1_433),
+            }),
+            _ => Err(TaxError::UnsupportedYear(year)),
+        }
+    }
+
+    /// Most base CPP that can be credited in a year.
+    fn maximum_base_cpp(&self) -> Money {
+        (self.year_maximum_pensionable_earnings - self.cpp_basic_exemption) * self.cpp_base_rate
+    }
+
+    /// The enhanced part of first CPP contributions.
+    fn enhanced_share(&self) -> Decimal {
+        (self.cpp_rate - self.cpp_base_rate) / self.cpp_rate
+    }
+}
+
+/// Source deductions withheld from one payment.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PayStub {
+    pub gross: Money,
+    pub cpp: Money,
+    pub cpp2: Money,
+    pub ei: Money,
+    pub federal_tax: Money,
+    pub provincial_tax: Money,
+    pub net_pay: Money,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Contributions {
+    cpp: Money,
+    cpp2: Money,
+    ei: Money,
+}
+
+/// Withholds source deductions from an employee's pay with the CRA payroll
+/// deductions formulas (T4127). Each period's pay is annualized, tax is
+/// worked out on the annual amount and the result is spread back over the
+/// periods. CPP and EI stop once the year's maximums have been withheld.
+#[derive(Clone, Debug)]
+pub struct CanadianPayroll {
+    year: u32,
+    province: CanadianJurisdiction,
+    frequency: PayFrequency,
+    parameters: PayrollParameters,
+    federal_schedule: TaxSchedule,
+    pensionable_earnings: Money,
+    cpp: Money,
+    cpp2: Money,
+    ei: Money,
+    ledger: Vec<PayStub>,
+}
+
+impl CanadianPayroll {
+    /// `province` is the province of employment. Quebec runs its own payroll
+    /// formulas and is not supported.
+    pub fn new(year: u32, province: CanadianJurisdiction, frequency: PayFrequency) -> Result<CanadianPayroll, TaxError> {
+        provincial_schedule(province, year)?;
+        Ok(CanadianPayroll {
+            year,
+            province,
+            frequency,
+            parameters: PayrollParameters::for_year(year)?,
+            federal_schedule: federal_schedule(year)?,
+            pensionable_earnings: cad_money!(0),
+            cpp: cad_money!(0),
+            cpp2: cad_money!(0),
+            ei: cad_money!(0),
+            ledger: vec![],
+        })
+    }
+
+    pub fn ledger(&self) -> &[PayStub] {
+        &self.ledger
+    }
+
+    /// Deductions on a regular payment. `deductions` are amounts withheld
+    /// before tax, such as RRSP contributions or union dues.
+    pub fn pay(&mut self, gross: Money, deductions: Money) -> Result<PayStub, TaxError> {
+        check_currency(&[gross, deductions])?;
+        let contributions = self.contributions(gross, true);
+        let annual_income = self.annual_income(gross - deductions, contributions);
+        let (federal_tax, provincial_tax) = self.annual_tax(annual_income, contributions)?;
+        let periods = self.periods();
+        Ok(self.record(gross, contributions, federal_tax * (dec!(1) / periods), provincial_tax * (dec!(1) / periods)))
+    }
+
+    /// Deductions on a bonus paid on its own, with the bonus method: the tax
+    /// is the difference between annual tax with and without the bonus.
+    /// `regular_pay` is the employee's gross pay in a regular period.
+    pub fn pay_bonus(&mut self, bonus: Money, regular_pay: Money) -> Result<PayStub, TaxError> {
+        check_currency(&[bonus, regular_pay])?;
+        let regular = self.contributions(regular_pay, true);
+        let annual_income = self.annual_income(regular_pay, regular);
+        let contributions = self.contributions(bonus, false);
+        let with_bonus = annual_income + bonus - self.enhanced_contributions(contributions);
+
+        let (federal_without, provincial_without) = self.annual_tax(annual_income, regular)?;
+        let (federal_with, provincial_with) = self.annual_tax(with_bonus, regular)?;
+        Ok(self.record(bonus, contributions, federal_with - federal_without, provincial_with - provincial_without))
+    }
+
+    /// Deductions on a retroactive increase of `increase_per_period` covering
+    /// `periods` past pay periods. Tax is the extra tax each of those periods
+    /// would have had with the increase, added up.
+    pub fn pay_retroactive(
+        &mut self,
+        increase_per_period: Money,
+        periods: u32,
+        regular_pay: Money,
+    ) -> Result<PayStub, TaxError> {
+        check_currency(&[increase_per_period, regular_pay])?;
+        let count = Decimal::from(periods);
+        let gross = increase_per_period * count;
+        let regular = self.contributions(regular_pay, true);
+        let annual_income = self.annual_income(regular_pay, regular);
+        let contributions = self.contributions(gross, false);
+        let increase = increase_per_period - self.enhanced_contributions(contributions) * (dec!(1) / count.max(dec!(1)));
+        let with_increase = annual_income + increase * self.periods();
+
+        let (federal_without, provincial_without) = self.annual_tax(annual_income, regular)?;
+        let (federal_with, provincial_with) = self.annual_tax(with_increase, regular)?;
+        let share = count / self.periods();
+        Ok(self.record(
+            gross,
+            contributions,
+            (federal_with - federal_without) * share,
+            (provincial_with - provincial_without) * share,
+        ))
+    }
+
+    fn periods(&self) -> Decimal {
+        Decimal::from(self.frequency.periods_per_year())
+    }
+
+    /// CPP, CPP2 and EI on a payment given what has been withheld so far.
+    /// Only regular pay gets a share of the CPP basic exemption.
+    fn contributions(&self, gross: Money, regular: bool) -> Contributions {
+        let parameters = &self.parameters;
+        let zero = cad_money!(0);
+        let exemption = if regular {
+            parameters.cpp_basic_exemption * (dec!(1) / self.periods())
+        }else{
+            zero
+        };
+        let cpp = ((gross - exemption).max(zero) * parameters.cpp_rate)
+            .min(parameters.maximum_cpp - self.cpp)
+            .max(zero);
+
+        let already_above = self.pensionable_earnings.max(parameters.year_maximum_pensionable_earnings);
+        let cpp2 = ((self.pensionable_earnings + gross - already_above) * parameters.cpp2_rate)
+            .min(parameters.maximum_cpp2 - self.cpp2)
+            .max(zero);
+
+        let ei = (gross * parameters.ei_rate).min(parameters.maximum_ei - self.ei).max(zero);
+
+        Contributions { cpp: round_cents(cpp), cpp2: round_cents(cpp2), ei: round_cents(ei) }
+    }
+
+    /// Enhanced CPP and CPP2 are deducted from income rather than credited.
+    fn enhanced_contributions(&self, contributions: Contributions) -> Money {
+        contributions.cpp * self.parameters.enhanced_share() + contributions.cpp2
+    }
+
+    /// Annual taxable income implied by one period's pay.
+    fn annual_income(&self, taxable_pay: Money, contributions: Contributions) -> Money {
+        ((taxable_pay - self.enhanced_contributions(contributions)) * self.periods()).max(cad_money!(0))
+    }
+
+    /// Annual federal and provincial tax on `annual_income`, with credits
+    /// for the basic personal amounts, base CPP and EI projected from this
+    /// period's contributions, and the Canada employment amount.
+    fn annual_tax(&self, annual_income: Money, contributions: Contributions) -> Result<(Money, Money), TaxError> {
+        let parameters = &self.parameters;
+        let zero = cad_money!(0);
+        let periods = self.periods();
+        let base_cpp = (contributions.cpp * (dec!(1) - parameters.enhanced_share()) * periods).min(parameters.maximum_base_cpp());
+        let ei = (contributions.ei * periods).min(parameters.maximum_ei);
+
+        let federal_credits = basic_personal_amount(self.year, annual_income)?
+            + base_cpp
+            + ei
+            + annual_income.min(parameters.canada_employment_amount);
+        let federal_tax = (self.federal_schedule.calculate_tax(annual_income) - federal_credits * LOWEST_FEDERAL_RATE).max(zero);
+
+        let provincial_tax = provincial_tax(self.province, self.year, annual_income, base_cpp + ei)?;
+
+        Ok((federal_tax, provincial_tax))
+    }
+
+    fn record(&mut self, gross: Money, contributions: Contributions, federal_tax: Money, provincial_tax: Money) -> PayStub {
+        let zero = cad_money!(0);
+        let federal_tax = round_cents(federal_tax.max(zero));
+        let provincial_tax = round_cents(provincial_tax.max(zero));
+        self.pensionable_earnings = self.pensionable_earnings + gross;
+        self.cpp = self.cpp + contributions.cpp;
+        self.cpp2 = self.cpp2 + contributions.cpp2;
+        self.ei = self.ei + contributions.ei;
+
+        let stub = PayStub {
+            gross,
+            cpp: contributions.cpp,
+            cpp2: contributions.cpp2,
+            ei: contributions.ei,
+            federal_tax,
+            provincial_tax,
+            net_pay: gross - contributions.cpp - contributions.cpp2 - contributions.ei - federal_tax - provincial_tax,
+        };
+        self.ledger.push(stub);
+        stub
+    }
+}
+
+fn check_currency(amounts: &[Money]) -> Result<(), TaxError> {
+    if amounts.iter().any(|money| money.currency != Currency::CAD) {
+        return Err(TaxError::MismatchedCurrencies);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn biweekly_ontario_pay() {
+        let mut payroll = CanadianPayroll::new(2024, CanadianJurisdiction::Ontario, PayFrequency::Biweekly).unwrap();
+        let stub = payroll.pay(cad_money!(2_000), cad_money!(0)).unwrap();
+        assert_eq!(stub.cpp, cad_money!(110.99));
+        assert_eq!(stub.cpp2, cad_money!(0));
+        assert_eq!(stub.ei, cad_money!(33.20));
+        assert_eq!(stub.federal_tax, cad_money!(179.50));
+        assert_eq!(stub.provincial_tax, cad_money!(92.82));
+        assert_eq!(stub.net_pay, cad_money!(1_583.49));
+        assert_eq!(
+            CanadianPayroll::new(2024, CanadianJurisdiction::Quebec, PayFrequency::Monthly).unwrap_err(),
+            TaxError::UnsupportedJurisdiction
+        );
+    }
+
+    #[test]
+    fn contributions_stop_at_the_yearly_maximums() {
+        let mut payroll = CanadianPayroll::new(2024, CanadianJurisdiction::Alberta, PayFrequency::Monthly).unwrap();
+        for _ in 0..12 {
+            payroll.pay(cad_money!(10_000), cad_money!(0)).unwrap();
+        }
+        let ledger = payroll.ledger();
+        let total = |field: fn(&PayStub) -> Money| ledger.iter().fold(cad_money!(0), |sum, stub| sum + field(stub));
+        assert_eq!(total(|stub| stub.cpp), cad_money!(3_867.50));
+        assert_eq!(total(|stub| stub.cpp2), cad_money!(188));
+        assert_eq!(total(|stub| stub.ei), cad_money!(1_049.12));
+        // July crosses both the CPP maximum and the YMPE.
+        assert_eq!(ledger[6].cpp, cad_money!(401.60));
+        assert_eq!(ledger[6].cpp2, cad_money!(60));
+        assert_eq!(ledger[6].ei, cad_money!(53.12));
+        assert_eq!(ledger[8].cpp2, cad_money!(0));
+    }
+
+    #[test]
+    fn bonus_and_retroactive_methods() {
+        let mut payroll = CanadianPayroll::new(2024, CanadianJurisdiction::BritishColumbia, PayFrequency::SemiMonthly).unwrap();
+        let bonus = payroll.pay_bonus(cad_money!(5_000), cad_money!(3_000)).unwrap();
+        // A bonus gets no share of the CPP basic exemption and is taxed
+        // entirely in the 20.5% federal and 7.7% BC brackets.
+        assert_eq!(bonus.cpp, cad_money!(297.50));
+        assert_eq!(bonus.federal_tax, cad_money!(1_014.75));
+        assert_eq!(bonus.provincial_tax, cad_money!(381.15));
+
+        let retroactive = payroll.pay_retroactive(cad_money!(100), 6, cad_money!(3_000)).unwrap();
+        assert_eq!(retroactive.gross, cad_money!(600));
+        assert_eq!(retroactive.cpp, cad_money!(35.70));
+        assert_eq!(retroactive.federal_tax, cad_money!(121.77));
+        assert_eq!(retroactive.provincial_tax, cad_money!(45.74));
+        assert_eq!(retroactive.net_pay, cad_money!(386.83));
+    }
+}
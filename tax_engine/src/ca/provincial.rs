@@ -0,0 +1,158 @@
+use rust_decimal::prelude::*;
+use rust_decimal_macros::*;
+use simple_money::*;
+use crate::{TaxError, TaxSchedule};
+use super::CanadianJurisdiction;
+
+/// Thresholds and rates for a province's brackets. Quebec administers its own
+/// income tax and is not covered.
+fn provincial_brackets(jurisdiction: CanadianJurisdiction, year: u32) -> Result<(Vec<Decimal>, Vec<Decimal>), TaxError> {
+    use CanadianJurisdiction::*;
+
+    let brackets = match (jurisdiction, year) {
+        (Alberta, 2024) => (
+            vec![dec!(148_269), dec!(177_922), dec!(237_230), dec!(355_845)],
+            vec![dec!(0.10), dec!(0.12), dec!(0.13), dec!(0.14), dec!(0.15)],
+        ),
+        (BritishColumbia, 2024) => (
+            vec![dec!(47_937), dec!(95_875), dec!(110_076), dec!(133_664), dec!(181_232), dec!(252_752)],
+            vec![dec!(0.0506), dec!(0.077), dec!(0.105), dec!(0.1229), dec!(0.147), dec!(0.168), dec!(0.205)],
+        ),
+        (Ontario, 2024) => (
+            vec![dec!(51_446), dec!(102_894), dec!(150_000), dec!(220_000)],
+            vec![dec!(0.0505), dec!(0.0915), dec!(0.1116), dec!(0.1216), dec!(0.1316)],
+        ),
+        (Federal, _) | (Quebec, _) => return Err(TaxError::UnsupportedJurisdiction),
+        _ => return Err(TaxError::UnsupportedYear(year)),
+    };
+
+    Ok(brackets)
+}
+
+pub fn provincial_schedule(jurisdiction: CanadianJurisdiction, year: u32) -> Result<TaxSchedule, TaxError> {
+    let (thresholds, rates) = provincial_brackets(jurisdiction, year)?;
+    TaxSchedule::from_thresholds(Currency::CAD, &thresholds, &rates)
+}
+
+/// Rate used to turn provincial non-refundable credit amounts into credits.
+pub fn lowest_provincial_rate(jurisdiction: CanadianJurisdiction, year: u32) -> Result<Decimal, TaxError> {
+    Ok(provincial_brackets(jurisdiction, year)?.1[0])
+}
+
+pub fn provincial_basic_personal_amount(jurisdiction: CanadianJurisdiction, year: u32) -> Result<Money, TaxError> {
+    use CanadianJurisdiction::*;
+
+    match (jurisdiction, year) {
+        (Alberta, 2024) => Ok(cad_money!(21_885)),
+        (BritishColumbia, 2024) => Ok(cad_money!(12_580)),
+        (Ontario, 2024) => Ok(cad_money!(12_399)),
+        (Federal, _) | (Quebec, _) => Err(TaxError::UnsupportedJurisdiction),
+        _ => Err(TaxError::UnsupportedYear(year)),
+    }
+}
+
+/// Ontario's surtax on basic provincial tax above two thresholds.
+pub fn ontario_surtax(year: u32, basic_provincial_tax: Money) -> Result<Money, TaxError> {
+    let (first_threshold, second_threshold) = match year {
+        2024 => (cad_money!(5_554), cad_money!(7_108)),
+        _ => return Err(TaxError::UnsupportedYear(year)),
+    };
+    let zero = cad_money!(0);
+    Ok((basic_provincial_tax - first_threshold).max(zero) * dec!(0.20)
+        + (basic_provincial_tax - second_threshold).max(zero) * dec!(0.36))
+}
+
+/// The Ontario Health Premium, which steps up over fixed income bands that
+/// are not indexed.
+pub fn ontario_health_premium(taxable_income: Money) -> Money {
+    let steps = [
+        (dec!(20_000), dec!(0), dec!(0.06), dec!(300)),
+        (dec!(36_000), dec!(300), dec!(0.06), dec!(450)),
+        (dec!(48_000), dec!(450), dec!(0.25), dec!(600)),
+        (dec!(72_000), dec!(600), dec!(0.25), dec!(750)),
+        (dec!(200_000), dec!(750), dec!(0.25), dec!(900)),
+    ];
+    let income = taxable_income.amount;
+    let premium = steps
+        .iter()
+        .rev()
+        .find(|(threshold, ..)| income > *threshold)
+        .map_or(dec!(0), |(threshold, base, rate, cap)| (*base + (income - *threshold) * *rate).min(*cap));
+    Money { amount: premium, currency: Currency::CAD }
+}
+
+/// Low-income reductions of provincial tax. Ontario's reduction depends on
+/// the tax itself and BC's on net income; neither goes below zero.
+pub fn provincial_tax_reduction(
+    jurisdiction: CanadianJurisdiction,
+    year: u32,
+    net_income: Money,
+    provincial_tax: Money,
+) -> Result<Money, TaxError> {
+    let zero = cad_money!(0);
+    let reduction = match (jurisdiction, year) {
+        (CanadianJurisdiction::Ontario, 2024) => cad_money!(286) * dec!(2) - provincial_tax,
+        (CanadianJurisdiction::BritishColumbia, 2024) => {
+            cad_money!(562) - (net_income - cad_money!(25_020)).max(zero) * dec!(0.0356)
+        }
+        _ => {
+            provincial_brackets(jurisdiction, year)?;
+            zero
+        }
+    };
+    Ok(reduction.max(zero).min(provincial_tax.max(zero)))
+}
+
+/// Provincial tax on taxable income after the basic personal amount and
+/// `credit_amounts` are credited at the lowest rate. Ontario's surtax and
+/// health premium and the low-income reductions are included.
+pub fn provincial_tax(
+    jurisdiction: CanadianJurisdiction,
+    year: u32,
+    taxable_income: Money,
+    credit_amounts: Money,
+) -> Result<Money, TaxError> {
+    let zero = cad_money!(0);
+    let credits = (provincial_basic_personal_amount(jurisdiction, year)? + credit_amounts)
+        * lowest_provincial_rate(jurisdiction, year)?;
+    let mut tax = (provincial_schedule(jurisdiction, year)?.calculate_tax(taxable_income) - credits).max(zero);
+    if jurisdiction == CanadianJurisdiction::Ontario {
+        tax = tax + ontario_surtax(year, tax)?;
+    }
+    tax = tax - provincial_tax_reduction(jurisdiction, year, taxable_income, tax)?;
+    if jurisdiction == CanadianJurisdiction::Ontario {
+        tax = tax + ontario_health_premium(taxable_income);
+    }
+
+    Ok(tax)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn provincial_brackets_and_credits() {
+        let ontario = provincial_schedule(CanadianJurisdiction::Ontario, 2024).unwrap();
+        assert_eq!(ontario.calculate_tax(cad_money!(100_000)), cad_money!(7_040.714));
+        assert_eq!(lowest_provincial_rate(CanadianJurisdiction::Alberta, 2024).unwrap(), dec!(0.10));
+        assert_eq!(
+            provincial_schedule(CanadianJurisdiction::Quebec, 2024).unwrap_err(),
+            TaxError::UnsupportedJurisdiction
+        );
+        assert_eq!(
+            provincial_basic_personal_amount(CanadianJurisdiction::Ontario, 2023).unwrap_err(),
+            TaxError::UnsupportedYear(2023)
+        );
+    }
+
+    #[test]
+    fn ontario_surtax_health_premium_and_reduction() {
+        assert_eq!(ontario_surtax(2024, cad_money!(8_000)).unwrap(), cad_money!(810.32));
+        assert_eq!(ontario_health_premium(cad_money!(22_000)), cad_money!(120));
+        assert_eq!(ontario_health_premium(cad_money!(60_000)), cad_money!(600));
+        assert_eq!(ontario_health_premium(cad_money!(250_000)), cad_money!(900));
+        let reduction = provincial_tax_reduction(CanadianJurisdiction::Ontario, 2024, cad_money!(20_000), cad_money!(400));
+        assert_eq!(reduction.unwrap(), cad_money!(172));
+    }
+}
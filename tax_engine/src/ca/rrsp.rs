@@ -0,0 +1,298 @@
+use chrono::{Datelike, NaiveDate};
+use rust_decimal::prelude::*;
+use rust_decimal_macros::*;
+use simple_money::*;
+use crate::{TaxDeduction, TaxDeductionCategory, TaxDeductionRule, TaxError};
+
+/// Share of the previous year's earned income that becomes new room.
+pub const RRSP_EARNED_INCOME_RATE: Decimal = dec!(0.18);
+
+/// Excess contributions up to this amount are not penalized.
+pub const RRSP_OVER_CONTRIBUTION_BUFFER: Decimal = dec!(2_000);
+
+/// Tax on the penalized excess for each month it stays in the plan.
+pub const RRSP_PENALTY_RATE_PER_MONTH: Decimal = dec!(0.01);
+
+/// The RRSP dollar limit, which caps the new room earned in a year.
+pub fn rrsp_dollar_limit(year: u32) -> Result<Money, TaxError> {
+    match year {
+        2019 => Ok(cad_money!(26_500)),
+        2020 => Ok(cad_money!(27_230)),
+        2021 => Ok(cad_money!(27_830)),
+        2022 => Ok(cad_money!(29_210)),
+        2023 => Ok(cad_money!(30_780)),
+        2024 => Ok(cad_money!(31_560)),
+        2025 => Ok(cad_money!(32_490)),
+        _ => Err(TaxError::UnsupportedYear(year)),
+    }
+}
+
+/// Reported by employers and pension plans, and subtracted from or added back
+/// to the room so members of registered pension plans don't save twice.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PensionAdjustments {
+    /// The pension adjustment (PA) from the previous year's T4.
+    pub pension_adjustment: Money,
+    /// Past service pension adjustments (PSPA) certified in the year.
+    pub past_service_pension_adjustment: Money,
+    /// Pension adjustment reversals (PAR) reported in the year.
+    pub pension_adjustment_reversal: Money,
+}
+
+impl Default for PensionAdjustments {
+    fn default() -> Self {
+        PensionAdjustments {
+            pension_adjustment: cad_money!(0),
+            past_service_pension_adjustment: cad_money!(0),
+            pension_adjustment_reversal: cad_money!(0),
+        }
+    }
+}
+
+impl PensionAdjustments {
+    fn is_cad(&self) -> bool {
+        self.pension_adjustment.currency == Currency::CAD
+            && self.past_service_pension_adjustment.currency == Currency::CAD
+            && self.pension_adjustment_reversal.currency == Currency::CAD
+    }
+}
+
+/// The room earned for `year`: 18% of the previous year's earned income up to
+/// the dollar limit, less the PA and PSPA, plus any PAR. It can be negative
+/// when a PSPA exceeds the new room, which eats into unused room.
+pub fn new_rrsp_room(
+    year: u32,
+    prior_year_earned_income: Money,
+    adjustments: &PensionAdjustments,
+) -> Result<Money, TaxError> {
+    if prior_year_earned_income.currency != Currency::CAD || !adjustments.is_cad() {
+        return Err(TaxError::MismatchedCurrencies);
+    }
+    let earned = (prior_year_earned_income * RRSP_EARNED_INCOME_RATE).max(cad_money!(0));
+    Ok(earned.min(rrsp_dollar_limit(year)?)
+        - adjustments.pension_adjustment
+        - adjustments.past_service_pension_adjustment
+        + adjustments.pension_adjustment_reversal)
+}
+
+/// Caps RRSP claims at one taxpayer's deduction limit for the year.
+pub fn rrsp_deduction_rule(deduction_limit: Money) -> TaxDeductionRule {
+    TaxDeductionRule {
+        tax_deduction_type: TaxDeductionCategory::RrspContributions,
+        max_amount: Some(deduction_limit),
+        inclusion_rate: dec!(1),
+        phase_out: None,
+    }
+}
+
+/// One year of RRSP activity, as on the notice of assessment and Form T1-OVP.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RrspYear {
+    pub year: u32,
+    /// Unused room carried in plus the room earned for the year.
+    pub deduction_limit: Money,
+    pub contributions: Money,
+    pub deduction: Money,
+    /// Room carried forward to next year.
+    pub unused_room: Money,
+    /// Contributions made but not yet deducted, available in later years.
+    pub undeducted_contributions: Money,
+    /// Undeducted contributions above the limit and the $2,000 buffer at the
+    /// end of the year.
+    pub excess_contributions: Money,
+    pub penalty_tax: Money,
+}
+
+impl RrspYear {
+    /// The claim for a schedule configured with `rrsp_deduction_rule`.
+    pub fn tax_deduction(&self) -> TaxDeduction {
+        TaxDeduction {
+            tax_deduction_type: TaxDeductionCategory::RrspContributions,
+            money_to_deduct: self.deduction,
+        }
+    }
+}
+
+/// A taxpayer's RRSP room and undeducted contributions, tracked across years.
+///
+/// Contributions are counted in the calendar year they are dated in; ones
+/// made in the first 60 days of a year and deducted for the previous year
+/// should be recorded in that previous year.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RrspRoom {
+    unused_room: Money,
+    undeducted_contributions: Money,
+    ledger: Vec<RrspYear>,
+}
+
+impl Default for RrspRoom {
+    fn default() -> Self {
+        Self::new(cad_money!(0), cad_money!(0))
+    }
+}
+
+impl RrspRoom {
+    /// Starts from the unused room and undeducted contributions on the last
+    /// notice of assessment.
+    pub fn new(unused_room: Money, undeducted_contributions: Money) -> RrspRoom {
+        RrspRoom { unused_room, undeducted_contributions, ledger: vec![] }
+    }
+
+    pub fn unused_room(&self) -> Money {
+        self.unused_room
+    }
+
+    pub fn undeducted_contributions(&self) -> Money {
+        self.undeducted_contributions
+    }
+
+    pub fn ledger(&self) -> &[RrspYear] {
+        &self.ledger
+    }
+
+    /// Unused room plus the room earned for `year`.
+    pub fn deduction_limit(
+        &self,
+        year: u32,
+        prior_year_earned_income: Money,
+        adjustments: &PensionAdjustments,
+    ) -> Result<Money, TaxError> {
+        Ok(self.unused_room + new_rrsp_room(year, prior_year_earned_income, adjustments)?)
+    }
+
+    /// Records a year's contributions and deduction. `deduction_requested`
+    /// lets a taxpayer hold a deduction back for a year with a higher
+    /// marginal rate; `None` claims as much as possible.
+    ///
+    /// The penalty tax is 1% a month on undeducted contributions above the
+    /// deduction limit plus the buffer, measured at the end of each month.
+    pub fn record_year(
+        &mut self,
+        year: u32,
+        prior_year_earned_income: Money,
+        adjustments: &PensionAdjustments,
+        contributions: &[(NaiveDate, Money)],
+        deduction_requested: Option<Money>,
+    ) -> Result<RrspYear, TaxError> {
+        if contributions.iter().any(|(_, amount)| amount.currency != Currency::CAD)
+            || deduction_requested.is_some_and(|requested| requested.currency != Currency::CAD)
+        {
+            return Err(TaxError::MismatchedCurrencies);
+        }
+        let zero = cad_money!(0);
+        let deduction_limit = self.deduction_limit(year, prior_year_earned_income, adjustments)?;
+        let buffer = Money { amount: RRSP_OVER_CONTRIBUTION_BUFFER, currency: Currency::CAD };
+        let penalized = |undeducted: Money| (undeducted - deduction_limit.max(zero) - buffer).max(zero);
+
+        let mut penalty_tax = zero;
+        let mut undeducted = self.undeducted_contributions;
+        for month in 1..=12 {
+            undeducted = self.undeducted_contributions
+                + contributions
+                    .iter()
+                    .filter(|(date, _)| date.year() as u32 == year && date.month() <= month)
+                    .fold(zero, |acc, (_, amount)| acc + *amount);
+            penalty_tax = penalty_tax + penalized(undeducted) * RRSP_PENALTY_RATE_PER_MONTH;
+        }
+        let year_contributions = undeducted - self.undeducted_contributions;
+
+        let mut deduction = undeducted.min(deduction_limit).max(zero);
+        if let Some(requested) = deduction_requested {
+            deduction = deduction.min(requested.max(zero));
+        }
+        self.unused_room = deduction_limit - deduction;
+        self.undeducted_contributions = undeducted - deduction;
+
+        let entry = RrspYear {
+            year,
+            deduction_limit,
+            contributions: year_contributions,
+            deduction,
+            unused_room: self.unused_room,
+            undeducted_contributions: self.undeducted_contributions,
+            excess_contributions: penalized(undeducted),
+            penalty_tax,
+        };
+        self.ledger.push(entry);
+        Ok(entry)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn room_from_earned_income_and_pension_adjustments() {
+        let adjustments = PensionAdjustments {
+            pension_adjustment: cad_money!(5_000),
+            pension_adjustment_reversal: cad_money!(1_000),
+            ..PensionAdjustments::default()
+        };
+        assert_eq!(new_rrsp_room(2024, cad_money!(150_000), &adjustments).unwrap(), cad_money!(23_000));
+        assert_eq!(
+            new_rrsp_room(2024, cad_money!(300_000), &PensionAdjustments::default()).unwrap(),
+            cad_money!(31_560)
+        );
+        assert_eq!(
+            new_rrsp_room(2030, cad_money!(100_000), &adjustments).unwrap_err(),
+            TaxError::UnsupportedYear(2030)
+        );
+    }
+
+    #[test]
+    fn deduction_can_be_held_back_for_a_later_year() {
+        let mut room = RrspRoom::new(cad_money!(10_000), cad_money!(0));
+        let adjustments = PensionAdjustments {
+            pension_adjustment: cad_money!(5_000),
+            pension_adjustment_reversal: cad_money!(1_000),
+            ..PensionAdjustments::default()
+        };
+
+        let first = room
+            .record_year(
+                2024,
+                cad_money!(150_000),
+                &adjustments,
+                &[(date(2024, 3, 1), cad_money!(20_000))],
+                Some(cad_money!(15_000)),
+            )
+            .unwrap();
+        assert_eq!(first.deduction_limit, cad_money!(33_000));
+        assert_eq!(first.deduction, cad_money!(15_000));
+        assert_eq!(first.unused_room, cad_money!(18_000));
+        assert_eq!(first.undeducted_contributions, cad_money!(5_000));
+        assert_eq!(first.penalty_tax, cad_money!(0));
+
+        let second = room
+            .record_year(2025, cad_money!(300_000), &PensionAdjustments::default(), &[], None)
+            .unwrap();
+        assert_eq!(second.deduction_limit, cad_money!(50_490));
+        assert_eq!(second.deduction, cad_money!(5_000));
+        assert_eq!(room.unused_room(), cad_money!(45_490));
+        assert_eq!(room.undeducted_contributions(), cad_money!(0));
+        assert_eq!(second.tax_deduction().money_to_deduct, cad_money!(5_000));
+    }
+
+    #[test]
+    fn over_contribution_above_the_buffer_is_taxed_monthly() {
+        let mut room = RrspRoom::new(cad_money!(10_000), cad_money!(0));
+        let contributions = [(date(2024, 1, 15), cad_money!(5_000)), (date(2024, 7, 10), cad_money!(10_000))];
+
+        let year = room
+            .record_year(2024, cad_money!(0), &PensionAdjustments::default(), &contributions, None)
+            .unwrap();
+        assert_eq!(year.deduction, cad_money!(10_000));
+        assert_eq!(year.excess_contributions, cad_money!(3_000));
+        assert_eq!(year.penalty_tax, cad_money!(180));
+        assert_eq!(year.undeducted_contributions, cad_money!(5_000));
+
+        // Without new room the excess stays in the plan all of the next year.
+        let next = room.record_year(2025, cad_money!(0), &PensionAdjustments::default(), &[], None).unwrap();
+        assert_eq!(next.penalty_tax, cad_money!(360));
+    }
+}
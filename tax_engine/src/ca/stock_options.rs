@@ -0,0 +1,319 @@
+use chrono::{Datelike, Months, NaiveDate};
+use rust_decimal::prelude::*;
+use rust_decimal_macros::*;
+use simple_money::*;
+use std::collections::HashMap;
+use crate::{TaxDeduction, TaxDeductionCategory, TaxDeductionRule, TaxError};
+use super::CanadianJurisdiction;
+
+/// Qualified options vesting in one calendar year from a non-CCPC employer
+/// may not exceed this value, measured at the grant date.
+const ANNUAL_VESTING_LIMIT: Decimal = dec!(200_000);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum EmployerType {
+    CanadianControlledPrivateCorporation,
+    Other,
+}
+
+/// Options granted to an employee, all vesting on one date.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StockOptionGrant {
+    pub grant_date: NaiveDate,
+    pub vesting_date: NaiveDate,
+    pub shares: Decimal,
+    pub exercise_price: Money,
+    pub fair_market_value_at_grant: Money,
+    pub employer_type: EmployerType,
+    /// The employer notified the employee that these options are
+    /// non-qualified, keeping the deduction for itself.
+    pub designated_non_qualified: bool,
+    /// The employer told the employee in writing within 30 days of the grant,
+    /// and the CRA with its return for the year of the grant, which options
+    /// are non-qualified. Without this notice the employer cannot deduct the
+    /// benefit on them.
+    pub non_qualified_notice_filed: bool,
+}
+
+impl StockOptionGrant {
+    /// The vesting limit applies to options granted after June 2021 by
+    /// employers that are not CCPCs.
+    pub fn subject_to_vesting_limit(&self) -> bool {
+        self.employer_type == EmployerType::Other
+            && self.grant_date >= NaiveDate::from_ymd_opt(2021, 7, 1).unwrap()
+    }
+
+    /// The 110(1)(d) conditions: the options were not granted in the money.
+    fn priced_at_fair_market_value(&self) -> bool {
+        self.exercise_price >= self.fair_market_value_at_grant
+    }
+}
+
+/// Splits each grant's shares into qualified shares under the $200,000
+/// annual vesting limit, returned in the same order as `grants`.
+///
+/// Room in a vesting year is used by grants in the order they were granted.
+/// Designated non-qualified options do not use room, and a grant that only
+/// partly fits is split.
+pub fn allocate_vesting_limit(grants: &[StockOptionGrant]) -> Result<Vec<Decimal>, TaxError> {
+    if grants.iter().any(|grant| {
+        grant.exercise_price.currency != Currency::CAD || grant.fair_market_value_at_grant.currency != Currency::CAD
+    }) {
+        return Err(TaxError::MismatchedCurrencies);
+    }
+
+    let mut order: Vec<usize> = (0..grants.len()).collect();
+    order.sort_by_key(|index| grants[*index].grant_date);
+
+    let mut qualified_shares = vec![dec!(0); grants.len()];
+    let mut room_by_year: HashMap<i32, Decimal> = HashMap::new();
+    for index in order {
+        let grant = &grants[index];
+        if grant.designated_non_qualified {
+            continue;
+        }
+        if !grant.subject_to_vesting_limit() {
+            qualified_shares[index] = grant.shares;
+            continue;
+        }
+
+        let room = room_by_year.entry(grant.vesting_date.year()).or_insert(ANNUAL_VESTING_LIMIT);
+        let value_per_share = grant.fair_market_value_at_grant.amount;
+        let shares = if value_per_share.is_zero() {
+            grant.shares
+        }else{
+            grant.shares.min(*room / value_per_share)
+        };
+        *room -= shares * value_per_share;
+        qualified_shares[index] = shares;
+    }
+
+    Ok(qualified_shares)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StockOptionExercise {
+    pub exercise_date: NaiveDate,
+    pub shares: Decimal,
+    pub fair_market_value_at_exercise: Money,
+    /// When the acquired shares were sold, if they have been.
+    pub sale_date: Option<NaiveDate>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StockOptionBenefit {
+    /// The year the benefit is included in income, or `None` for CCPC shares
+    /// that have not been sold yet.
+    pub taxation_year: Option<u32>,
+    pub taxable_benefit: Money,
+    /// The part of the benefit the employee can claim the deduction on.
+    pub deductible_benefit: Money,
+    /// The part of the benefit the employer may deduct because the options
+    /// were non-qualified. Only employers subject to the vesting limit get
+    /// this deduction, and only if they filed the non-qualified notice.
+    pub employer_deductible_benefit: Money,
+}
+
+impl StockOptionBenefit {
+    /// Quebec only allows half the federal deduction.
+    pub fn deduction_rate(jurisdiction: CanadianJurisdiction) -> Decimal {
+        match jurisdiction {
+            CanadianJurisdiction::Quebec => dec!(0.25),
+            _ => dec!(0.5),
+        }
+    }
+
+    pub fn deduction(&self, jurisdiction: CanadianJurisdiction) -> Money {
+        self.deductible_benefit * Self::deduction_rate(jurisdiction)
+    }
+
+    /// The claim for a schedule configured with `stock_option_deduction_rule`.
+    pub fn tax_deduction(&self) -> TaxDeduction {
+        TaxDeduction {
+            tax_deduction_type: TaxDeductionCategory::EmployeeStockOptions,
+            money_to_deduct: self.deductible_benefit,
+        }
+    }
+}
+
+pub fn stock_option_deduction_rule(jurisdiction: CanadianJurisdiction) -> TaxDeductionRule {
+    TaxDeductionRule {
+        tax_deduction_type: TaxDeductionCategory::EmployeeStockOptions,
+        max_amount: None,
+        inclusion_rate: StockOptionBenefit::deduction_rate(jurisdiction),
+        phase_out: None,
+    }
+}
+
+/// Calculates the employment benefit from exercising part of a grant, where
+/// `qualified_shares` comes from `allocate_vesting_limit`. Exercised shares
+/// are treated as qualified in the same proportion as the whole grant.
+///
+/// CCPC employees are taxed when they sell the shares rather than when they
+/// exercise, and keep the deduction if they held the shares for two years
+/// even when the options were granted in the money.
+pub fn calculate_benefit(
+    grant: &StockOptionGrant,
+    qualified_shares: Decimal,
+    exercise: &StockOptionExercise,
+) -> Result<StockOptionBenefit, TaxError> {
+    if exercise.fair_market_value_at_exercise.currency != grant.exercise_price.currency {
+        return Err(TaxError::MismatchedCurrencies);
+    }
+
+    let spread_per_share = exercise.fair_market_value_at_exercise - grant.exercise_price;
+    let taxable_benefit = if spread_per_share.amount > dec!(0) {
+        spread_per_share * exercise.shares
+    }else{
+        Money { amount: dec!(0), currency: spread_per_share.currency }
+    };
+    let qualified_benefit = if grant.shares.is_zero() {
+        Money { amount: dec!(0), currency: taxable_benefit.currency }
+    }else{
+        Money {
+            amount: taxable_benefit.amount * qualified_shares / grant.shares,
+            currency: taxable_benefit.currency,
+        }
+    };
+    let non_qualified_benefit = taxable_benefit - qualified_benefit;
+    let zero = Money { amount: dec!(0), currency: taxable_benefit.currency };
+
+    let (taxation_year, deductible_benefit) = match grant.employer_type {
+        EmployerType::CanadianControlledPrivateCorporation => {
+            let held_two_years = exercise.sale_date.is_some_and(|sale_date| {
+                sale_date >= exercise.exercise_date + Months::new(24)
+            });
+            let deductible = if grant.priced_at_fair_market_value() || held_two_years {
+                qualified_benefit
+            }else{
+                zero
+            };
+            (exercise.sale_date.map(|sale_date| sale_date.year() as u32), deductible)
+        }
+        EmployerType::Other => {
+            let deductible = if grant.priced_at_fair_market_value() {
+                qualified_benefit
+            }else{
+                zero
+            };
+            (Some(exercise.exercise_date.year() as u32), deductible)
+        }
+    };
+
+    let employer_deductible_benefit = if grant.subject_to_vesting_limit() && grant.non_qualified_notice_filed {
+        non_qualified_benefit
+    }else{
+        zero
+    };
+
+    Ok(StockOptionBenefit {
+        taxation_year,
+        taxable_benefit,
+        deductible_benefit,
+        employer_deductible_benefit,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    fn public_grant(grant_date: NaiveDate, shares: Decimal) -> StockOptionGrant {
+        StockOptionGrant {
+            grant_date,
+            vesting_date: date(2024, 3, 1),
+            shares,
+            exercise_price: cad_money!(10),
+            fair_market_value_at_grant: cad_money!(10),
+            employer_type: EmployerType::Other,
+            designated_non_qualified: false,
+            non_qualified_notice_filed: true,
+        }
+    }
+
+    #[test]
+    fn vesting_limit_splits_grants_in_grant_order() {
+        let grants = vec![
+            public_grant(date(2023, 6, 1), dec!(15_000)),
+            public_grant(date(2022, 1, 15), dec!(10_000)),
+            StockOptionGrant { designated_non_qualified: true, ..public_grant(date(2022, 1, 1), dec!(5_000)) },
+            public_grant(date(2021, 1, 1), dec!(50_000)),
+        ];
+
+        let qualified = allocate_vesting_limit(&grants).unwrap();
+        assert_eq!(qualified, vec![dec!(10_000), dec!(10_000), dec!(0), dec!(50_000)]);
+    }
+
+    #[test]
+    fn public_company_benefit_at_exercise() {
+        let grant = public_grant(date(2023, 6, 1), dec!(30_000));
+        let exercise = StockOptionExercise {
+            exercise_date: date(2025, 2, 1),
+            shares: dec!(3_000),
+            fair_market_value_at_exercise: cad_money!(50),
+            sale_date: None,
+        };
+
+        let benefit = calculate_benefit(&grant, dec!(20_000), &exercise).unwrap();
+        assert_eq!(benefit.taxation_year, Some(2025));
+        assert_eq!(benefit.taxable_benefit, cad_money!(120_000));
+        assert_eq!(benefit.deductible_benefit, cad_money!(80_000));
+        assert_eq!(benefit.employer_deductible_benefit, cad_money!(40_000));
+        assert_eq!(benefit.deduction(CanadianJurisdiction::Federal), cad_money!(40_000));
+        assert_eq!(benefit.deduction(CanadianJurisdiction::Quebec), cad_money!(20_000));
+    }
+
+    #[test]
+    fn ccpc_benefit_deferred_to_sale() {
+        let grant = StockOptionGrant {
+            exercise_price: cad_money!(0.10),
+            fair_market_value_at_grant: cad_money!(1),
+            employer_type: EmployerType::CanadianControlledPrivateCorporation,
+            ..public_grant(date(2022, 6, 1), dec!(100_000))
+        };
+        let mut exercise = StockOptionExercise {
+            exercise_date: date(2023, 1, 10),
+            shares: dec!(100_000),
+            fair_market_value_at_exercise: cad_money!(2.10),
+            sale_date: None,
+        };
+        let qualified = allocate_vesting_limit(&[grant]).unwrap();
+        assert_eq!(qualified, vec![dec!(100_000)]);
+
+        let unsold = calculate_benefit(&grant, qualified[0], &exercise).unwrap();
+        assert_eq!(unsold.taxation_year, None);
+        assert_eq!(unsold.deductible_benefit, cad_money!(0));
+
+        exercise.sale_date = Some(date(2025, 6, 1));
+        let sold = calculate_benefit(&grant, qualified[0], &exercise).unwrap();
+        assert_eq!(sold.taxation_year, Some(2025));
+        assert_eq!(sold.taxable_benefit, cad_money!(200_000));
+        assert_eq!(sold.deductible_benefit, cad_money!(200_000));
+    }
+
+    #[test]
+    fn employer_deduction_needs_a_non_ccpc_employer_and_notice() {
+        let exercise = StockOptionExercise {
+            exercise_date: date(2025, 2, 1),
+            shares: dec!(3_000),
+            fair_market_value_at_exercise: cad_money!(50),
+            sale_date: None,
+        };
+
+        let no_notice = StockOptionGrant { non_qualified_notice_filed: false, ..public_grant(date(2023, 6, 1), dec!(30_000)) };
+        let benefit = calculate_benefit(&no_notice, dec!(20_000), &exercise).unwrap();
+        assert_eq!(benefit.deductible_benefit, cad_money!(80_000));
+        assert_eq!(benefit.employer_deductible_benefit, cad_money!(0));
+
+        let ccpc = StockOptionGrant {
+            employer_type: EmployerType::CanadianControlledPrivateCorporation,
+            ..public_grant(date(2023, 6, 1), dec!(30_000))
+        };
+        let benefit = calculate_benefit(&ccpc, dec!(20_000), &exercise).unwrap();
+        assert_eq!(benefit.employer_deductible_benefit, cad_money!(0));
+    }
+}
@@ -0,0 +1,265 @@
+use chrono::{Duration, NaiveDate};
+use rust_decimal::prelude::*;
+use rust_decimal_macros::*;
+use simple_money::*;
+use std::collections::HashMap;
+use crate::{AccountOwner, TaxError};
+use super::{AcbTransaction, AcbTransactionKind, AdjustedCostBase};
+
+/// Purchases this many days either side of a loss can make it superficial.
+const WINDOW_DAYS: i64 = 30;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum CanadianAccountType {
+    NonRegistered,
+    Tfsa,
+    Rrsp,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct CanadianAccount {
+    pub owner: AccountOwner,
+    pub account_type: CanadianAccountType,
+}
+
+impl CanadianAccount {
+    pub fn is_registered(&self) -> bool {
+        self.account_type != CanadianAccountType::NonRegistered
+    }
+}
+
+/// A loss denied because identical property was bought by the taxpayer or an
+/// affiliated person within 30 days and still held at the end of that period.
+/// Transactions are identified by their position in the history.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SuperficialLoss {
+    pub sale: usize,
+    pub account: CanadianAccount,
+    /// The loss before the rule, as a positive amount.
+    pub loss: Money,
+    pub denied_loss: Money,
+    pub triggering_purchases: Vec<usize>,
+    /// The account whose ACB took the denied loss, or `None` when the
+    /// substituted property is in a registered account and the loss is gone.
+    pub added_to: Option<CanadianAccount>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SuperficialLossAnalysis {
+    pub accounts: HashMap<CanadianAccount, AdjustedCostBase>,
+    pub superficial_losses: Vec<SuperficialLoss>,
+}
+
+fn shares_acquired(transaction: &AcbTransaction) -> Decimal {
+    match transaction.kind {
+        AcbTransactionKind::Buy { shares, .. } | AcbTransactionKind::ReinvestedDistribution { shares, .. } => shares,
+        AcbTransactionKind::Sell { shares, .. } => -shares,
+        _ => dec!(0),
+    }
+}
+
+/// Runs one security's history across a couple's accounts, in date order,
+/// through an ACB per account and applies the superficial loss rule to
+/// losses in non-registered accounts. `exchange_on` gives the rates for a
+/// transaction date.
+///
+/// The denied share of a loss is the least of the shares sold, the shares
+/// bought in the window and the shares held at its end, over the shares
+/// sold. Each purchase only counts once, so shares matched to an earlier
+/// loss are not matched again. The denied loss is added to the ACB of the
+/// account that bought the substituted shares, preferring the account that
+/// sold.
+pub fn apply_superficial_loss_rule<'a, F>(
+    security: &str,
+    history: &[(CanadianAccount, AcbTransaction)],
+    exchange_on: F,
+) -> Result<SuperficialLossAnalysis, TaxError>
+where
+    F: Fn(NaiveDate) -> &'a Exchange,
+{
+    let mut accounts: HashMap<CanadianAccount, AdjustedCostBase> = HashMap::new();
+    let mut superficial_losses = vec![];
+    let mut used_shares: HashMap<usize, Decimal> = HashMap::new();
+
+    for (index, (account, transaction)) in history.iter().enumerate() {
+        let realized = accounts
+            .entry(*account)
+            .or_insert_with(|| AdjustedCostBase::new(security))
+            .record(transaction, exchange_on(transaction.date))?;
+        let realized = match realized {
+            Some(realized) if !account.is_registered() && realized.gain.amount < dec!(0) && realized.shares > dec!(0) => {
+                realized
+            }
+            _ => continue,
+        };
+
+        let window_start = transaction.date - Duration::days(WINDOW_DAYS);
+        let window_end = transaction.date + Duration::days(WINDOW_DAYS);
+        let candidates = history.iter().enumerate().filter(|(other, (_, purchase))| {
+            *other != index
+                && purchase.date >= window_start
+                && purchase.date <= window_end
+                && shares_acquired(purchase) > dec!(0)
+        });
+        let held_at_end: Decimal = history
+            .iter()
+            .filter(|(_, other)| other.date <= window_end)
+            .map(|(_, other)| shares_acquired(other))
+            .sum();
+
+        // Shares that already made an earlier loss superficial cannot make
+        // this one superficial too.
+        let limit = realized.shares.min(held_at_end);
+        let mut remaining = limit;
+        let mut triggering_purchases = vec![];
+        for (other, (_, purchase)) in candidates {
+            if remaining <= dec!(0) {
+                break;
+            }
+            let used = used_shares.entry(other).or_insert(dec!(0));
+            let matched = remaining.min(shares_acquired(purchase) - *used);
+            if matched <= dec!(0) {
+                continue;
+            }
+            *used += matched;
+            remaining -= matched;
+            triggering_purchases.push(other);
+        }
+
+        let affected = limit - remaining;
+        if affected <= dec!(0) {
+            continue;
+        }
+        let loss = Money { amount: -realized.gain.amount, currency: Currency::CAD };
+        let denied_loss = Money { amount: loss.amount * affected / realized.shares, currency: Currency::CAD };
+
+        let substituted_accounts: Vec<CanadianAccount> = triggering_purchases
+            .iter()
+            .map(|other| history[*other].0)
+            .filter(|other| !other.is_registered())
+            .collect();
+        let added_to = if substituted_accounts.contains(account) {
+            Some(*account)
+        }else{
+            substituted_accounts.first().copied()
+        };
+
+        accounts.get_mut(account).unwrap().deny_loss(denied_loss);
+        if let Some(added_to) = added_to {
+            accounts
+                .entry(added_to)
+                .or_insert_with(|| AdjustedCostBase::new(security))
+                .add_to_cost(denied_loss);
+        }
+        superficial_losses.push(SuperficialLoss {
+            sale: index,
+            account: *account,
+            loss,
+            denied_loss,
+            triggering_purchases,
+            added_to,
+        });
+    }
+
+    Ok(SuperficialLossAnalysis { accounts, superficial_losses })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TAXABLE: CanadianAccount = CanadianAccount {
+        owner: AccountOwner::Taxpayer,
+        account_type: CanadianAccountType::NonRegistered,
+    };
+    const SPOUSE_TAXABLE: CanadianAccount = CanadianAccount {
+        owner: AccountOwner::Spouse,
+        account_type: CanadianAccountType::NonRegistered,
+    };
+    const SPOUSE_TFSA: CanadianAccount = CanadianAccount {
+        owner: AccountOwner::Spouse,
+        account_type: CanadianAccountType::Tfsa,
+    };
+
+    fn trade(account: CanadianAccount, month: u32, day: u32, kind: AcbTransactionKind) -> (CanadianAccount, AcbTransaction) {
+        (account, AcbTransaction { date: NaiveDate::from_ymd_opt(2024, month, day).unwrap(), kind })
+    }
+
+    fn buy(account: CanadianAccount, month: u32, day: u32, shares: Decimal, cost: Money) -> (CanadianAccount, AcbTransaction) {
+        trade(account, month, day, AcbTransactionKind::Buy { shares, cost })
+    }
+
+    fn sell(month: u32, day: u32) -> (CanadianAccount, AcbTransaction) {
+        trade(TAXABLE, month, day, AcbTransactionKind::Sell { shares: dec!(100), proceeds: cad_money!(3_000) })
+    }
+
+    #[test]
+    fn repurchase_adds_the_loss_to_the_new_shares() {
+        let cad = Exchange::new();
+        let history = vec![
+            buy(TAXABLE, 1, 2, dec!(100), cad_money!(5_000)),
+            sell(3, 1),
+            buy(TAXABLE, 3, 15, dec!(100), cad_money!(3_200)),
+        ];
+
+        let analysis = apply_superficial_loss_rule("XIC", &history, |_| &cad).unwrap();
+        let report = &analysis.superficial_losses[0];
+        assert_eq!(report.denied_loss, cad_money!(2_000));
+        assert_eq!(report.triggering_purchases, vec![2]);
+        assert_eq!(report.added_to, Some(TAXABLE));
+
+        let acb = &analysis.accounts[&TAXABLE];
+        assert_eq!(acb.realized_gains()[0].gain, cad_money!(0));
+        assert_eq!(acb.total_cost(), cad_money!(5_200));
+    }
+
+    #[test]
+    fn spouse_tfsa_purchase_denies_the_loss_for_good() {
+        let cad = Exchange::new();
+        let history = vec![
+            buy(TAXABLE, 1, 2, dec!(100), cad_money!(5_000)),
+            sell(3, 1),
+            buy(SPOUSE_TFSA, 3, 20, dec!(40), cad_money!(1_300)),
+        ];
+
+        let analysis = apply_superficial_loss_rule("XIC", &history, |_| &cad).unwrap();
+        let report = &analysis.superficial_losses[0];
+        assert_eq!(report.loss, cad_money!(2_000));
+        assert_eq!(report.denied_loss, cad_money!(800));
+        assert_eq!(report.added_to, None);
+        assert_eq!(analysis.accounts[&TAXABLE].realized_gains()[0].gain, cad_money!(-1_200));
+    }
+
+    #[test]
+    fn one_purchase_only_makes_one_loss_superficial() {
+        let cad = Exchange::new();
+        let history = vec![
+            buy(TAXABLE, 1, 2, dec!(100), cad_money!(5_000)),
+            buy(SPOUSE_TAXABLE, 1, 2, dec!(100), cad_money!(5_000)),
+            sell(3, 1),
+            trade(SPOUSE_TAXABLE, 3, 4, AcbTransactionKind::Sell { shares: dec!(100), proceeds: cad_money!(3_000) }),
+            buy(TAXABLE, 3, 15, dec!(100), cad_money!(3_200)),
+        ];
+
+        let analysis = apply_superficial_loss_rule("XIC", &history, |_| &cad).unwrap();
+        assert_eq!(analysis.superficial_losses.len(), 1);
+        assert_eq!(analysis.superficial_losses[0].sale, 2);
+        assert_eq!(analysis.superficial_losses[0].triggering_purchases, vec![4]);
+        assert_eq!(analysis.accounts[&TAXABLE].total_cost(), cad_money!(5_200));
+        assert_eq!(analysis.accounts[&SPOUSE_TAXABLE].realized_gains()[0].gain, cad_money!(-2_000));
+    }
+
+    #[test]
+    fn purchases_outside_the_window_are_ignored() {
+        let cad = Exchange::new();
+        let history = vec![
+            buy(TAXABLE, 1, 2, dec!(100), cad_money!(5_000)),
+            sell(3, 1),
+            buy(TAXABLE, 4, 1, dec!(100), cad_money!(3_200)),
+        ];
+
+        let analysis = apply_superficial_loss_rule("XIC", &history, |_| &cad).unwrap();
+        assert!(analysis.superficial_losses.is_empty());
+        assert_eq!(analysis.accounts[&TAXABLE].net_gain_for_year(2024), cad_money!(-2_000));
+    }
+}
@@ -0,0 +1,212 @@
+use chrono::{Datelike, NaiveDate};
+use rust_decimal::prelude::*;
+use rust_decimal_macros::*;
+use simple_money::*;
+use crate::TaxError;
+
+/// Tax on the highest excess in a month, for each month it stays in the
+/// account. The same rate applies to TFSAs and FHSAs.
+pub const REGISTERED_PENALTY_RATE_PER_MONTH: Decimal = dec!(0.01);
+
+/// The first year TFSAs existed.
+const FIRST_TFSA_YEAR: u32 = 2009;
+
+/// Room is only earned from the year a resident turns this age.
+const TFSA_ELIGIBILITY_AGE: u32 = 18;
+
+/// The TFSA dollar limit added to everyone's room on January 1.
+pub fn tfsa_dollar_limit(year: u32) -> Result<Money, TaxError> {
+    match year {
+        2009..=2012 => Ok(cad_money!(5_000)),
+        2013 | 2014 => Ok(cad_money!(5_500)),
+        2015 => Ok(cad_money!(10_000)),
+        2016..=2018 => Ok(cad_money!(5_500)),
+        2019..=2022 => Ok(cad_money!(6_000)),
+        2023 => Ok(cad_money!(6_500)),
+        2024 | 2025 => Ok(cad_money!(7_000)),
+        _ => Err(TaxError::UnsupportedYear(year)),
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TfsaTransactionKind {
+    Contribution(Money),
+    Withdrawal(Money),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TfsaTransaction {
+    pub date: NaiveDate,
+    pub kind: TfsaTransactionKind,
+}
+
+/// One year of TFSA activity, as on Form RC243.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TfsaYear {
+    pub year: u32,
+    /// Room on January 1, including the year's limit and last year's
+    /// withdrawals. Negative while an excess is carried in.
+    pub room_at_start: Money,
+    pub contributions: Money,
+    pub withdrawals: Money,
+    /// Room left at the end of the year, before next year's limit and
+    /// withdrawals are added back. Negative while there is an excess.
+    pub unused_room: Money,
+    pub highest_excess: Money,
+    pub penalty_tax: Money,
+}
+
+/// One person's TFSA contribution room, tracked across years.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TfsaRoom {
+    birth_year: u32,
+    /// The last year whose dollar limit is already in `unused_room`.
+    accrued_through: u32,
+    unused_room: Money,
+    withdrawals_to_restore: Money,
+    ledger: Vec<TfsaYear>,
+}
+
+impl TfsaRoom {
+    /// Starts with no room used, accruing every limit since 2009.
+    pub fn new(birth_year: u32) -> TfsaRoom {
+        TfsaRoom {
+            birth_year,
+            accrued_through: FIRST_TFSA_YEAR - 1,
+            unused_room: cad_money!(0),
+            withdrawals_to_restore: cad_money!(0),
+            ledger: vec![],
+        }
+    }
+
+    /// Starts from the room on January 1 of `year` as reported by the CRA.
+    pub fn from_assessment(birth_year: u32, year: u32, room_at_start: Money) -> Result<TfsaRoom, TaxError> {
+        if room_at_start.currency != Currency::CAD {
+            return Err(TaxError::MismatchedCurrencies);
+        }
+        Ok(TfsaRoom { accrued_through: year, unused_room: room_at_start, ..TfsaRoom::new(birth_year) })
+    }
+
+    pub fn ledger(&self) -> &[TfsaYear] {
+        &self.ledger
+    }
+
+    /// Room on January 1 of `year`, adding the limits for the years since the
+    /// last one recorded in which the holder was at least 18.
+    pub fn room_at_start(&self, year: u32) -> Result<Money, TaxError> {
+        let eligible_from = (self.birth_year + TFSA_ELIGIBILITY_AGE).max(self.accrued_through + 1);
+        let mut room = self.unused_room + self.withdrawals_to_restore;
+        for accrual_year in eligible_from..=year {
+            room = room + tfsa_dollar_limit(accrual_year)?;
+        }
+        Ok(room)
+    }
+
+    /// Records a year's contributions and withdrawals. Withdrawals only come
+    /// back as room next January, except the part that cancels an excess,
+    /// which never does.
+    pub fn record_year(&mut self, year: u32, transactions: &[TfsaTransaction]) -> Result<TfsaYear, TaxError> {
+        let room_at_start = self.room_at_start(year)?;
+        let zero = cad_money!(0);
+        let mut transactions: Vec<&TfsaTransaction> =
+            transactions.iter().filter(|transaction| transaction.date.year() as u32 == year).collect();
+        transactions.sort_by_key(|transaction| transaction.date);
+
+        let mut available = room_at_start;
+        let mut contributions = zero;
+        let mut withdrawals = zero;
+        let mut restored = zero;
+        let mut highest_excess = zero;
+        let mut penalty_tax = zero;
+        for month in 1..=12 {
+            let mut month_excess = (zero - available).max(zero);
+            for transaction in transactions.iter().filter(|transaction| transaction.date.month() == month) {
+                match transaction.kind {
+                    TfsaTransactionKind::Contribution(amount) => {
+                        if amount.currency != Currency::CAD {
+                            return Err(TaxError::MismatchedCurrencies);
+                        }
+                        available = available - amount;
+                        contributions = contributions + amount;
+                    }
+                    TfsaTransactionKind::Withdrawal(amount) => {
+                        if amount.currency != Currency::CAD {
+                            return Err(TaxError::MismatchedCurrencies);
+                        }
+                        let cancelled = amount.min((zero - available).max(zero));
+                        available = available + cancelled;
+                        withdrawals = withdrawals + amount;
+                        restored = restored + amount - cancelled;
+                    }
+                }
+                month_excess = month_excess.max((zero - available).max(zero));
+            }
+            highest_excess = highest_excess.max(month_excess);
+            penalty_tax = penalty_tax + month_excess * REGISTERED_PENALTY_RATE_PER_MONTH;
+        }
+
+        self.accrued_through = self.accrued_through.max(year);
+        self.unused_room = available;
+        self.withdrawals_to_restore = restored;
+        let entry = TfsaYear {
+            year,
+            room_at_start,
+            contributions,
+            withdrawals,
+            unused_room: available,
+            highest_excess,
+            penalty_tax,
+        };
+        self.ledger.push(entry);
+        Ok(entry)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transaction(year: i32, month: u32, day: u32, kind: TfsaTransactionKind) -> TfsaTransaction {
+        TfsaTransaction { date: NaiveDate::from_ymd_opt(year, month, day).unwrap(), kind }
+    }
+
+    #[test]
+    fn room_accrues_from_the_year_the_holder_turns_eighteen() {
+        assert_eq!(TfsaRoom::new(1990).room_at_start(2024).unwrap(), cad_money!(95_000));
+        assert_eq!(TfsaRoom::new(2000).room_at_start(2024).unwrap(), cad_money!(43_000));
+        assert_eq!(TfsaRoom::new(2010).room_at_start(2024).unwrap(), cad_money!(0));
+    }
+
+    #[test]
+    fn withdrawals_come_back_the_next_january() {
+        let mut room = TfsaRoom::new(1990);
+        let year = room
+            .record_year(2024, &[
+                transaction(2024, 1, 2, TfsaTransactionKind::Contribution(cad_money!(95_000))),
+                transaction(2024, 6, 1, TfsaTransactionKind::Withdrawal(cad_money!(10_000))),
+                transaction(2024, 8, 1, TfsaTransactionKind::Contribution(cad_money!(1_000))),
+            ])
+            .unwrap();
+        assert_eq!(year.highest_excess, cad_money!(1_000));
+        assert_eq!(year.penalty_tax, cad_money!(50));
+        assert_eq!(year.unused_room, cad_money!(-1_000));
+        assert_eq!(room.room_at_start(2025).unwrap(), cad_money!(16_000));
+    }
+
+    #[test]
+    fn withdrawal_cancelling_an_excess_is_not_restored() {
+        let mut room = TfsaRoom::from_assessment(1990, 2024, cad_money!(5_000)).unwrap();
+        let year = room
+            .record_year(2024, &[
+                transaction(2024, 3, 5, TfsaTransactionKind::Contribution(cad_money!(10_000))),
+                transaction(2024, 9, 10, TfsaTransactionKind::Withdrawal(cad_money!(3_000))),
+            ])
+            .unwrap();
+        assert_eq!(year.highest_excess, cad_money!(5_000));
+        assert_eq!(year.penalty_tax, cad_money!(410));
+
+        let next = room.record_year(2025, &[]).unwrap();
+        assert_eq!(next.room_at_start, cad_money!(5_000));
+        assert_eq!(next.penalty_tax, cad_money!(0));
+    }
+}
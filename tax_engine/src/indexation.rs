@@ -0,0 +1,228 @@
+use rust_decimal::prelude::*;
+use rust_decimal::RoundingStrategy;
+use rust_decimal_macros::*;
+use simple_money::*;
+use std::collections::HashMap;
+use crate::{DeductionPhaseOut, TaxBracket, TaxError, TaxSchedule};
+
+/// How an indexation factor and the amounts it produces are rounded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IndexationRounding {
+    /// The CRA rounds the factor to three decimals and each indexed amount
+    /// to the nearest dollar.
+    Cra,
+    /// The IRS rounds indexed amounts down to a multiple, such as $25 for
+    /// most brackets or $50 for joint filers.
+    DownToMultiple(Decimal),
+}
+
+impl IndexationRounding {
+    pub fn round_factor(&self, factor: Decimal) -> Decimal {
+        match self {
+            IndexationRounding::Cra => factor.round_dp_with_strategy(3, RoundingStrategy::RoundHalfUp),
+            IndexationRounding::DownToMultiple(_) => factor,
+        }
+    }
+
+    pub fn round_amount(&self, money: Money) -> Money {
+        let amount = match self {
+            IndexationRounding::Cra => money.amount.round_dp_with_strategy(0, RoundingStrategy::RoundHalfUp),
+            IndexationRounding::DownToMultiple(multiple) => (money.amount / multiple).floor() * multiple,
+        };
+        Money { amount, currency: money.currency }
+    }
+
+    /// Applies an already rounded factor to one amount.
+    fn index(&self, money: Money, factor: Decimal) -> Money {
+        self.round_amount(money * factor)
+    }
+}
+
+impl TaxSchedule {
+    /// The schedule one year later: bracket thresholds, deduction limits and
+    /// phase-out thresholds grow by `factor`, while rates stay the same.
+    pub fn indexed(&self, factor: Decimal, rounding: IndexationRounding) -> TaxSchedule {
+        let factor = rounding.round_factor(factor);
+        let brackets = self
+            .brackets
+            .iter()
+            .map(|bracket| TaxBracket {
+                min_money: rounding.index(bracket.min_money, factor),
+                max_money: bracket.max_money.map(|max_money| rounding.index(max_money, factor)),
+                rate: bracket.rate,
+            })
+            .collect();
+        let deductions_map = self
+            .deductions_map
+            .iter()
+            .map(|(category, rule)| {
+                let mut rule = *rule;
+                rule.max_amount = rule.max_amount.map(|max_amount| rounding.index(max_amount, factor));
+                rule.phase_out = rule.phase_out.map(|phase_out| DeductionPhaseOut {
+                    threshold: rounding.index(phase_out.threshold, factor),
+                    reduction_rate: phase_out.reduction_rate,
+                });
+                (*category, rule)
+            })
+            .collect();
+
+        TaxSchedule {
+            brackets,
+            deductions_map,
+            credits_map: self.credits_map.clone(),
+            tax_currency: self.tax_currency,
+        }
+    }
+}
+
+/// Projects a schedule into future years. With CRA rounding each year is
+/// indexed from the rounded amounts of the year before, so rounding
+/// compounds as it does in the published amounts. The IRS instead indexes
+/// the base year's amounts by the cumulative change and rounds once.
+/// Published factors and legislated schedules take precedence over the
+/// inflation assumption.
+#[derive(Clone, Debug)]
+pub struct ScheduleProjection {
+    base_year: u32,
+    base_schedule: TaxSchedule,
+    inflation: Decimal,
+    rounding: IndexationRounding,
+    indexation_factors: HashMap<u32, Decimal>,
+    legislated_schedules: HashMap<u32, TaxSchedule>,
+}
+
+impl ScheduleProjection {
+    /// `inflation` is the assumed yearly CPI growth, such as 0.02.
+    pub fn new(
+        base_year: u32,
+        base_schedule: TaxSchedule,
+        inflation: Decimal,
+        rounding: IndexationRounding,
+    ) -> ScheduleProjection {
+        ScheduleProjection {
+            base_year,
+            base_schedule,
+            inflation,
+            rounding,
+            indexation_factors: HashMap::new(),
+            legislated_schedules: HashMap::new(),
+        }
+    }
+
+    /// Uses a published factor, such as 1.027, for `year` instead of the
+    /// inflation assumption.
+    pub fn set_indexation_factor(&mut self, year: u32, factor: Decimal) {
+        self.indexation_factors.insert(year, factor);
+    }
+
+    /// Replaces the projection for `year` with a legislated schedule. Later
+    /// years are indexed from it.
+    pub fn set_legislated_schedule(&mut self, year: u32, schedule: TaxSchedule) {
+        self.legislated_schedules.insert(year, schedule);
+    }
+
+    /// The rounded factor that takes amounts from `year - 1` to `year`.
+    pub fn indexation_factor(&self, year: u32) -> Decimal {
+        let factor = self
+            .indexation_factors
+            .get(&year)
+            .copied()
+            .unwrap_or(dec!(1) + self.inflation);
+        self.rounding.round_factor(factor)
+    }
+
+    pub fn schedule_for(&self, year: u32) -> Result<TaxSchedule, TaxError> {
+        if year < self.base_year {
+            return Err(TaxError::UnsupportedYear(year));
+        }
+        let (start_year, mut schedule) = match (self.base_year..=year)
+            .rev()
+            .find_map(|candidate| self.legislated_schedules.get(&candidate).map(|schedule| (candidate, schedule)))
+        {
+            Some((legislated_year, schedule)) => (legislated_year, schedule.clone()),
+            None => (self.base_year, self.base_schedule.clone()),
+        };
+        for factor in self.factors_between(start_year, year) {
+            schedule = schedule.indexed(factor, self.rounding);
+        }
+        Ok(schedule)
+    }
+
+    /// Indexes a dollar amount outside the schedule, such as a credit base or
+    /// a contribution limit, from `from_year` to `year` with the same factors.
+    pub fn index_amount(&self, money: Money, from_year: u32, year: u32) -> Money {
+        self.factors_between(from_year, year)
+            .into_iter()
+            .fold(money, |amount, factor| self.rounding.index(amount, factor))
+    }
+
+    /// The factors to apply in turn to take amounts from `from_year` to
+    /// `year`: one per year for the CRA, or their product for the IRS.
+    fn factors_between(&self, from_year: u32, year: u32) -> Vec<Decimal> {
+        let factors = (from_year + 1..=year).map(|indexed_year| self.indexation_factor(indexed_year));
+        match self.rounding {
+            IndexationRounding::Cra => factors.collect(),
+            IndexationRounding::DownToMultiple(_) if year > from_year => {
+                vec![factors.fold(dec!(1), |product, factor| product * factor)]
+            }
+            IndexationRounding::DownToMultiple(_) => vec![],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ca::federal_schedule;
+
+    #[test]
+    fn cra_rounding_compounds_from_published_factors() {
+        let mut projection = ScheduleProjection::new(2024, federal_schedule(2024).unwrap(), dec!(0.02), IndexationRounding::Cra);
+        projection.set_indexation_factor(2025, dec!(1.0271));
+
+        let schedule_2025 = projection.schedule_for(2025).unwrap();
+        assert_eq!(schedule_2025.calculate_tax(cad_money!(57_375)), cad_money!(8_606.25));
+        assert_eq!(schedule_2025.calculate_tax(cad_money!(114_750)), cad_money!(20_368.125));
+
+        // 57,375 × 1.02 = 58,522.5, which rounds up.
+        let schedule_2026 = projection.schedule_for(2026).unwrap();
+        assert_eq!(schedule_2026.calculate_tax(cad_money!(58_523)), cad_money!(8_778.45));
+        assert_eq!(schedule_2026.calculate_tax(cad_money!(58_524)), cad_money!(8_778.655));
+        assert_eq!(projection.index_amount(cad_money!(15_705), 2024, 2026), cad_money!(16_452));
+        assert_eq!(projection.schedule_for(2023).unwrap_err(), TaxError::UnsupportedYear(2023));
+    }
+
+    #[test]
+    fn legislated_schedules_override_the_projection() {
+        let mut projection = ScheduleProjection::new(2024, federal_schedule(2024).unwrap(), dec!(0.02), IndexationRounding::Cra);
+        let legislated = TaxSchedule::from_thresholds(Currency::CAD, &[dec!(60_000)], &[dec!(0.14), dec!(0.3)]).unwrap();
+        projection.set_legislated_schedule(2027, legislated);
+
+        let schedule_2027 = projection.schedule_for(2027).unwrap();
+        assert_eq!(schedule_2027.calculate_tax(cad_money!(60_000)), cad_money!(8_400));
+        let schedule_2028 = projection.schedule_for(2028).unwrap();
+        assert_eq!(schedule_2028.calculate_tax(cad_money!(61_200)), cad_money!(8_568));
+    }
+
+    #[test]
+    fn irs_rounding_goes_down_to_a_multiple() {
+        let rounding = IndexationRounding::DownToMultiple(dec!(50));
+        assert_eq!(rounding.round_factor(dec!(1.0284)), dec!(1.0284));
+        assert_eq!(rounding.index(usd_money!(11_600), dec!(1.028)), usd_money!(11_900));
+        assert_eq!(IndexationRounding::Cra.round_factor(dec!(1.0275)), dec!(1.028));
+    }
+
+    #[test]
+    fn irs_rounding_indexes_from_the_base_year() {
+        let base = TaxSchedule::from_thresholds(Currency::USD, &[dec!(11_600)], &[dec!(0.10), dec!(0.12)]).unwrap();
+        let projection = ScheduleProjection::new(2024, base, dec!(0.028), IndexationRounding::DownToMultiple(dec!(50)));
+
+        // 11,600 × 1.028² = 12,258.69, rounded down once. Rounding each year
+        // would give 11,900 and then 12,200.
+        let schedule_2026 = projection.schedule_for(2026).unwrap();
+        assert_eq!(schedule_2026.calculate_tax(usd_money!(12_250)), usd_money!(1_225));
+        assert_eq!(schedule_2026.calculate_tax(usd_money!(12_350)), usd_money!(1_237));
+        assert_eq!(projection.index_amount(usd_money!(11_600), 2024, 2026), usd_money!(12_250));
+        assert_eq!(projection.index_amount(usd_money!(11_610), 2024, 2024), usd_money!(11_610));
+    }
+}
@@ -1,16 +1,115 @@
 use rust_decimal::prelude::*;
+use rust_decimal::RoundingStrategy;
+use chrono::{Datelike, Duration, NaiveDate, Weekday};
 use std::collections::HashMap;
 use std::cmp::Ordering;
 use simple_money::*;
 use rust_decimal_macros::*;
 use thiserror::Error;
 
+mod indexation;
+mod tables;
+mod treaty;
+pub mod ca;
+pub mod us;
+
+pub use indexation::*;
+pub use tables::*;
+pub use treaty::*;
+
 #[derive(Debug, Error, PartialEq)]
 pub enum TaxError {
     #[error("Mismatched currencies")]
     MismatchedCurrencies,
     #[error("Could not find deduction")]
     CouldNotFindDeduction,
+    #[error("Could not find credit")]
+    CouldNotFindCredit,
+    #[error("No tax data for year {0}")]
+    UnsupportedYear(u32),
+    #[error("No tax data for this jurisdiction")]
+    UnsupportedJurisdiction,
+    #[error("Could not find exchange rate")]
+    CouldNotFindExchangeRate,
+    #[error("Cannot dispose of more shares than are held")]
+    InsufficientShares,
+    #[error("Share count must be positive, not {0}")]
+    NonPositiveShares(Decimal),
+    #[error("Gap between tax brackets from {1} to {2}, before bracket {0}")]
+    BracketGap(usize, Decimal, Decimal),
+    #[error("Tax bracket {0} starts at {1}, inside the bracket below, which ends at {2}")]
+    BracketOverlap(usize, Decimal, Decimal),
+    #[error("Tax bracket {0} ends at {2}, which is not above its minimum {1}")]
+    EmptyBracket(usize, Decimal, Decimal),
+    #[error("Tax bracket {0} is open-ended, but is not the top bracket")]
+    OpenEndedBracketNotLast(usize),
+    #[error("The top tax bracket must be open-ended")]
+    MissingOpenEndedBracket,
+    #[error("Negative tax rate {0}")]
+    NegativeRate(Decimal),
+    #[error("Tax bracket {0} has rate {2}, below the rate {1} of the bracket under it")]
+    DecreasingRate(usize, Decimal, Decimal),
+    #[error("{file}: {field}: {message}")]
+    InvalidTable { file: String, field: String, message: String },
+}
+
+impl From<MoneyError> for TaxError {
+    fn from(error: MoneyError) -> TaxError {
+        match error {
+            MoneyError::CouldNotFindExchangeRate => TaxError::CouldNotFindExchangeRate,
+            MoneyError::MismatchedCurrencies => TaxError::MismatchedCurrencies,
+        }
+    }
+}
+
+/// Whose account a transaction happened in. The loss-denial rules look at
+/// purchases by a spouse as well as by the taxpayer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum AccountOwner {
+    Taxpayer,
+    Spouse,
+}
+
+/// How often an employee is paid.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PayFrequency {
+    Weekly,
+    Biweekly,
+    SemiMonthly,
+    Monthly,
+}
+
+impl PayFrequency {
+    pub fn periods_per_year(&self) -> u32 {
+        match self {
+            PayFrequency::Weekly => 52,
+            PayFrequency::Biweekly => 26,
+            PayFrequency::SemiMonthly => 24,
+            PayFrequency::Monthly => 12,
+        }
+    }
+}
+
+/// Rounds an amount withheld from a payment to the cent.
+pub(crate) fn round_cents(money: Money) -> Money {
+    Money {
+        amount: money.amount.round_dp_with_strategy(2, RoundingStrategy::RoundHalfUp),
+        currency: money.currency,
+    }
+}
+
+/// Moves a due date that falls on a weekend to the following Monday.
+pub(crate) fn next_business_day(date: NaiveDate) -> NaiveDate {
+    match date.weekday() {
+        Weekday::Sat => date + Duration::days(2),
+        Weekday::Sun => date + Duration::days(1),
+        _ => date,
+    }
+}
+
+/// Number of days in the year containing `date`, for daily interest.
+pub(crate) fn days_in_year(date: NaiveDate) -> Decimal {
+    Decimal::from(NaiveDate::from_ymd_opt(date.year(), 12, 31).unwrap().ordinal())
 }
 
 #[derive(PartialEq, Eq, Clone, Copy, Debug)]
@@ -22,7 +121,7 @@ pub struct TaxBracket{
 
 impl PartialOrd for TaxBracket {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        self.min_money.partial_cmp(&other.min_money)
+        Some(self.cmp(other))
     }
 }
 
@@ -68,13 +167,13 @@ impl TaxBracket {
 
         if let Some(max_money) = self.max_money {
             if taxable_income >= max_money {
-                return max_money * self.rate;
+                return (max_money - self.min_money) * self.rate;
             }else{
                 return (taxable_income - self.min_money) * self.rate;
             }
         }
 
-        return (taxable_income - self.min_money) * self.rate;
+        (taxable_income - self.min_money) * self.rate
     }
 }
 
@@ -82,6 +181,15 @@ impl TaxBracket {
 pub enum TaxDeductionCategory {
     CapitalGains,
     EmployeeStockOptions,
+    RrspContributions,
+}
+
+/// Shrinks a deduction's limit by `reduction_rate` for every dollar of net
+/// income above `threshold`, down to zero.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DeductionPhaseOut {
+    pub threshold: Money,
+    pub reduction_rate: Decimal,
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -89,19 +197,40 @@ pub struct TaxDeductionRule {
     pub tax_deduction_type: TaxDeductionCategory,
     pub max_amount: Option<Money>,
     pub inclusion_rate: Decimal,
+    pub phase_out: Option<DeductionPhaseOut>,
 }
 
 impl TaxDeductionRule {
-    pub fn apply_deduction(&self, deduction: TaxDeduction) -> Money {
-        if let Some(max_amount) = self.max_amount {
-            if deduction.money_to_deduct <= max_amount {
-                return max_amount * self.inclusion_rate
-            }else{
-                return deduction.money_to_deduct * self.inclusion_rate
+    /// The most that can be claimed in a year by a taxpayer with `net_income`,
+    /// or `None` when claims are unlimited. Without a `max_amount`, a phase-out
+    /// reduces the amount claimed instead.
+    pub fn limit(&self, net_income: Money, amount_claimed: Money) -> Option<Money> {
+        let base_limit = match (self.max_amount, self.phase_out) {
+            (Some(max_amount), _) => max_amount,
+            (None, Some(_)) => amount_claimed,
+            (None, None) => return None,
+        };
+
+        match self.phase_out {
+            Some(phase_out) if net_income > phase_out.threshold => {
+                let reduction = (net_income - phase_out.threshold) * phase_out.reduction_rate;
+                if reduction >= base_limit {
+                    Some(Money { amount: dec!(0), currency: base_limit.currency })
+                }else{
+                    Some(base_limit - reduction)
+                }
             }
+            _ => Some(base_limit),
         }
+    }
 
-        return deduction.money_to_deduct * self.inclusion_rate;
+    /// The amount a single claim reduces income by, ignoring phase-outs and
+    /// other claims sharing the cap.
+    pub fn apply_deduction(&self, deduction: TaxDeduction) -> Money {
+        match self.max_amount {
+            Some(max_amount) => deduction.money_to_deduct.min(max_amount) * self.inclusion_rate,
+            None => deduction.money_to_deduct * self.inclusion_rate,
+        }
     }
 }
 
@@ -110,10 +239,53 @@ pub struct TaxDeduction {
     pub money_to_deduct: Money,
 }
 
+/// How a year's deduction claims were applied. Any part of a claim above the
+/// category's `max_amount` is left in `carry_forward` to be claimed in a later
+/// year; what a phase-out takes away is not.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DeductionSummary {
+    pub total: Money,
+    pub claimed: HashMap<TaxDeductionCategory, Money>,
+    pub carry_forward: HashMap<TaxDeductionCategory, Money>,
+}
+
+impl DeductionSummary {
+    /// Turns the unused amounts into claims for the following year.
+    pub fn carry_forward_deductions(&self) -> Vec<TaxDeduction> {
+        self.carry_forward
+            .iter()
+            .filter(|(_, money)| money.amount > dec!(0))
+            .map(|(category, money)| TaxDeduction {
+                tax_deduction_type: *category,
+                money_to_deduct: *money,
+            })
+            .collect()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TaxCreditCategory {
+    EligibleDividends,
+    NonEligibleDividends,
+}
+
+/// A non-refundable credit worth `rate` times the amount it is claimed on.
+#[derive(Clone, Copy, Debug)]
+pub struct TaxCreditRule {
+    pub tax_credit_type: TaxCreditCategory,
+    pub rate: Decimal,
+}
+
+pub struct TaxCredit {
+    pub tax_credit_type: TaxCreditCategory,
+    pub credit_base: Money,
+}
+
 #[derive(Debug, Clone)]
 pub struct TaxSchedule {
     brackets: Vec<TaxBracket>,
     deductions_map: HashMap<TaxDeductionCategory, TaxDeductionRule>,
+    credits_map: HashMap<TaxCreditCategory, TaxCreditRule>,
     tax_currency: Currency,
 }
 
@@ -130,6 +302,52 @@ impl TaxSchedule {
         brackets.iter().all(|bracket| Self::validate_currency_on_bracket(bracket, currency))
     }
 
+    /// Brackets must start at zero, follow on from each other without gaps
+    /// or overlaps and end with an open-ended bracket. Rates may not fall
+    /// from one bracket to the next; a tax whose marginal rate drops, like
+    /// New York's recapture, has to be worked out outside the schedule.
+    ///
+    /// Brackets are checked in order of their minimums, but errors give the
+    /// index of the bracket in `brackets` as passed in.
+    fn validate_structure(brackets: &[TaxBracket]) -> Result<(), TaxError> {
+        let mut order: Vec<usize> = (0..brackets.len()).collect();
+        order.sort_by_key(|index| brackets[*index].min_money);
+
+        let mut expected_min = dec!(0);
+        let mut previous_rate: Option<Decimal> = None;
+        for (position, index) in order.iter().copied().enumerate() {
+            let bracket = &brackets[index];
+            if bracket.rate < dec!(0) {
+                return Err(TaxError::NegativeRate(bracket.rate));
+            }
+            if let Some(previous_rate) = previous_rate {
+                if bracket.rate < previous_rate {
+                    return Err(TaxError::DecreasingRate(index, previous_rate, bracket.rate));
+                }
+            }
+            previous_rate = Some(bracket.rate);
+
+            let min_amount = bracket.min_money.amount;
+            if min_amount > expected_min {
+                return Err(TaxError::BracketGap(index, expected_min, min_amount));
+            }
+            if min_amount < expected_min {
+                return Err(TaxError::BracketOverlap(index, min_amount, expected_min));
+            }
+            match bracket.max_money {
+                Some(max_money) if max_money.amount <= min_amount => {
+                    return Err(TaxError::EmptyBracket(index, min_amount, max_money.amount));
+                }
+                Some(max_money) => expected_min = max_money.amount,
+                None if position + 1 < order.len() => {
+                    return Err(TaxError::OpenEndedBracketNotLast(index));
+                }
+                None => return Ok(()),
+            }
+        }
+        Err(TaxError::MissingOpenEndedBracket)
+    }
+
     pub fn new(
         brackets: Vec<TaxBracket>,
         currency: Currency,
@@ -137,16 +355,41 @@ impl TaxSchedule {
         if !Self::validate_currency_on_brackets(brackets.clone(), currency){
            Err(TaxError::MismatchedCurrencies) 
         }else{
+            Self::validate_structure(&brackets)?;
             let mut new_brackets = brackets.clone();
             new_brackets.sort();
-            return Ok(TaxSchedule {
+            Ok(TaxSchedule {
                 brackets: new_brackets,
                 deductions_map: HashMap::new(),
+                credits_map: HashMap::new(),
                 tax_currency: currency,
             })
         }
     }
 
+    /// Builds brackets that start at zero and change rate at each threshold,
+    /// so they are contiguous by construction. There must be one more rate
+    /// than thresholds, the last applying to everything above the top one.
+    pub fn from_thresholds(
+        currency: Currency,
+        thresholds: &[Decimal],
+        rates: &[Decimal],
+    ) -> Result<TaxSchedule, TaxError> {
+        let mut brackets = Vec::with_capacity(rates.len());
+        let mut min_amount = dec!(0);
+        for (index, rate) in rates.iter().enumerate() {
+            let max_money = thresholds
+                .get(index)
+                .map(|max_amount| Money { amount: *max_amount, currency });
+            brackets.push(TaxBracket::new(Money { amount: min_amount, currency }, max_money, *rate)?);
+            if let Some(max_money) = max_money {
+                min_amount = max_money.amount;
+            }
+        }
+
+        Self::new(brackets, currency)
+    }
+
     pub fn set_deduction(
         &mut self,
         tax_deduction_category: TaxDeductionCategory,
@@ -155,32 +398,78 @@ impl TaxSchedule {
         self.deductions_map.insert(tax_deduction_category, tax_deduction_rule);
     }
 
-    fn determine_deductions_amount(
+    pub fn set_credit(
+        &mut self,
+        tax_credit_category: TaxCreditCategory,
+        tax_credit_rule: TaxCreditRule,
+    ){
+        self.credits_map.insert(tax_credit_category, tax_credit_rule);
+    }
+
+    fn determine_credits_amount(
         &self,
-        deductions: Vec<TaxDeduction>,
+        credits: Vec<TaxCredit>,
     ) -> Result<Money, TaxError> {
-        deductions
+        credits
             .iter()
-            .try_fold( Money { amount: dec!(0), currency: self.tax_currency } , |acc, actual_tax_deduction| {
-                match self
-                    .deductions_map
-                    .get(&actual_tax_deduction.tax_deduction_type)
-                {
-                    Some(deduction_info) => {
-                        let money_result = actual_tax_deduction.money_to_deduct
-                            * deduction_info.inclusion_rate
-                            + acc;
-                        Ok(money_result)
-                    }
-                    None => Err(TaxError::CouldNotFindDeduction),
+            .try_fold(Money { amount: dec!(0), currency: self.tax_currency }, |acc, actual_tax_credit| {
+                match self.credits_map.get(&actual_tax_credit.tax_credit_type) {
+                    Some(credit_info) => Ok(actual_tax_credit.credit_base * credit_info.rate + acc),
+                    None => Err(TaxError::CouldNotFindCredit),
                 }
             })
     }
 
+    /// Applies each category's limit to the sum of its claims, so several
+    /// claims share one cap. `net_income` drives any phase-outs.
+    pub fn determine_deductions(
+        &self,
+        net_income: Money,
+        deductions: Vec<TaxDeduction>,
+    ) -> Result<DeductionSummary, TaxError> {
+        let mut claims: HashMap<TaxDeductionCategory, Money> = HashMap::new();
+        for deduction in deductions.iter() {
+            if deduction.money_to_deduct.currency != self.tax_currency {
+                return Err(TaxError::MismatchedCurrencies);
+            }
+            if !self.deductions_map.contains_key(&deduction.tax_deduction_type) {
+                return Err(TaxError::CouldNotFindDeduction);
+            }
+            let claim = claims
+                .entry(deduction.tax_deduction_type)
+                .or_insert(Money { amount: dec!(0), currency: self.tax_currency });
+            *claim = *claim + deduction.money_to_deduct;
+        }
+
+        let mut summary = DeductionSummary {
+            total: Money { amount: dec!(0), currency: self.tax_currency },
+            claimed: HashMap::new(),
+            carry_forward: HashMap::new(),
+        };
+        for (category, amount_claimed) in claims {
+            let rule = self.deductions_map[&category];
+            let allowed = match rule.limit(net_income, amount_claimed) {
+                Some(limit) => amount_claimed.min(limit),
+                None => amount_claimed,
+            };
+            summary.total = summary.total + allowed * rule.inclusion_rate;
+            summary.claimed.insert(category, allowed);
+            // Only the part above the cap is deferred; a phase-out reduction
+            // is lost.
+            let carry_forward = match rule.max_amount {
+                Some(max_amount) => amount_claimed - amount_claimed.min(max_amount),
+                None => Money { amount: dec!(0), currency: self.tax_currency },
+            };
+            summary.carry_forward.insert(category, carry_forward);
+        }
+
+        Ok(summary)
+    }
+
     pub fn calculate_tax(&self, taxable_income: Money) -> Money {
         self.brackets
             .iter()
-            .map(|bracket| bracket.calculate_tax(taxable_income.clone()))
+            .map(|bracket| bracket.calculate_tax(taxable_income))
             .fold(Money { amount: dec!(0), currency: taxable_income.currency }, |acc, bracket_tax| acc + bracket_tax)
     }
 
@@ -189,12 +478,28 @@ impl TaxSchedule {
         income: Money,
         deductions: Vec<TaxDeduction>,
     ) -> Result<Money, TaxError> {
-        let deductions_amount = self.determine_deductions_amount(deductions);
-        match deductions_amount {
-            Ok(deductions_total) => Ok(self.calculate_tax(income - deductions_total)),
+        let deductions_summary = self.determine_deductions(income, deductions);
+        match deductions_summary {
+            Ok(summary) => Ok(self.calculate_tax(income - summary.total)),
             Err(error_code) => Err(error_code),
         }
     }
+
+    /// Non-refundable credits reduce tax to zero at most.
+    pub fn calculate_tax_with_credits(
+        &self,
+        income: Money,
+        deductions: Vec<TaxDeduction>,
+        credits: Vec<TaxCredit>,
+    ) -> Result<Money, TaxError> {
+        let tax = self.calculate_tax_with_deductions(income, deductions)?;
+        let credits_total = self.determine_credits_amount(credits)?;
+        if credits_total >= tax {
+            Ok(Money { amount: dec!(0), currency: tax.currency })
+        }else{
+            Ok(tax - credits_total)
+        }
+    }
 }
 
 #[cfg(test)]
@@ -222,7 +527,7 @@ mod tests {
         let schedule = TaxSchedule::new(vec![lowest, middle, highest], Currency::CAD).unwrap();
 
         let over_highest_tax = schedule.calculate_tax(cad_money!(25_000));
-        assert_eq!(over_highest_tax, cad_money!(6_500));
+        assert_eq!(over_highest_tax, cad_money!(4_500));
 
         let middle_tax = schedule.calculate_tax(cad_money!(15_000));
         assert_eq!(middle_tax, cad_money!(2000));
@@ -235,7 +540,7 @@ mod tests {
     fn single_bracket_example() {
         let lowest = TaxBracket {
             min_money: cad_money!(0),
-            max_money: Some(cad_money!(10_000)),
+            max_money: None,
             rate: dec!(0.1),
         };
 
@@ -245,6 +550,50 @@ mod tests {
         assert_eq!(tax, cad_money!(1000));
     }
 
+    #[test]
+    fn bracket_structure_is_validated() {
+        let bracket = |min: Decimal, max: Option<Decimal>, rate: Decimal| TaxBracket {
+            min_money: Money { amount: min, currency: Currency::CAD },
+            max_money: max.map(|max| Money { amount: max, currency: Currency::CAD }),
+            rate,
+        };
+
+        let gap = vec![bracket(dec!(0), Some(dec!(10_000)), dec!(0.1)), bracket(dec!(12_000), None, dec!(0.2))];
+        assert_eq!(TaxSchedule::new(gap, Currency::CAD).unwrap_err(), TaxError::BracketGap(1, dec!(10_000), dec!(12_000)));
+
+        let overlap = vec![bracket(dec!(0), Some(dec!(10_000)), dec!(0.1)), bracket(dec!(9_000), None, dec!(0.2))];
+        assert_eq!(TaxSchedule::new(overlap, Currency::CAD).unwrap_err(), TaxError::BracketOverlap(1, dec!(9_000), dec!(10_000)));
+
+        // Errors name brackets by their position in the input, not once sorted.
+        let empty = vec![bracket(dec!(10_000), None, dec!(0.2)), bracket(dec!(0), Some(dec!(0)), dec!(0.1))];
+        assert_eq!(TaxSchedule::new(empty, Currency::CAD).unwrap_err(), TaxError::EmptyBracket(1, dec!(0), dec!(0)));
+
+        let open_below = vec![bracket(dec!(10_000), None, dec!(0.2)), bracket(dec!(0), None, dec!(0.1))];
+        assert_eq!(TaxSchedule::new(open_below, Currency::CAD).unwrap_err(), TaxError::OpenEndedBracketNotLast(1));
+
+        let falling = vec![bracket(dec!(10_000), None, dec!(0.1)), bracket(dec!(0), Some(dec!(10_000)), dec!(0.2))];
+        assert_eq!(TaxSchedule::new(falling, Currency::CAD).unwrap_err(), TaxError::DecreasingRate(0, dec!(0.2), dec!(0.1)));
+
+        let closed = vec![bracket(dec!(0), Some(dec!(10_000)), dec!(0.1))];
+        assert_eq!(TaxSchedule::new(closed, Currency::CAD).unwrap_err(), TaxError::MissingOpenEndedBracket);
+
+        let negative = vec![bracket(dec!(0), None, dec!(-0.1))];
+        assert_eq!(TaxSchedule::new(negative, Currency::CAD).unwrap_err(), TaxError::NegativeRate(dec!(-0.1)));
+    }
+
+    #[test]
+    fn schedule_from_thresholds() {
+        let schedule = TaxSchedule::from_thresholds(
+            Currency::CAD,
+            &[dec!(10_000), dec!(20_000)],
+            &[dec!(0.1), dec!(0.2), dec!(0.3)],
+        ).unwrap();
+        assert_eq!(schedule.calculate_tax(cad_money!(25_000)), cad_money!(4_500));
+
+        let missing_top_rate = TaxSchedule::from_thresholds(Currency::CAD, &[dec!(10_000)], &[dec!(0.1)]);
+        assert_eq!(missing_top_rate.unwrap_err(), TaxError::MissingOpenEndedBracket);
+    }
+
     #[test]
     fn invalid_bracket_and_regime(){
         let invalid = TaxBracket::new(
@@ -279,6 +628,7 @@ mod tests {
             tax_deduction_type: TaxDeductionCategory::CapitalGains,
             max_amount: None,
             inclusion_rate: dec!(0.5),
+            phase_out: None,
         };
 
         let mut schedule = TaxSchedule::new(
@@ -297,7 +647,105 @@ mod tests {
 
         match tax {
             Ok(result) => assert_eq!(result, cad_money!(750.00)),
-            Err(_) => assert!(false, "Tax should not be an Err"),
+            Err(_) => panic!("Tax should not be an Err"),
         }
     }
-}
\ No newline at end of file
+
+    fn flat_schedule_with_rule(rule: TaxDeductionRule) -> TaxSchedule {
+        let single = TaxBracket::new(cad_money!(0), None, dec!(0.1)).unwrap();
+        let mut schedule = TaxSchedule::new(vec![single], Currency::CAD).unwrap();
+        schedule.set_deduction(rule.tax_deduction_type, rule);
+        schedule
+    }
+
+    #[test]
+    fn capped_deduction() {
+        let rule = TaxDeductionRule {
+            tax_deduction_type: TaxDeductionCategory::EmployeeStockOptions,
+            max_amount: Some(cad_money!(1_000)),
+            inclusion_rate: dec!(1),
+            phase_out: None,
+        };
+
+        let below_cap = rule.apply_deduction(TaxDeduction {
+            tax_deduction_type: TaxDeductionCategory::EmployeeStockOptions,
+            money_to_deduct: cad_money!(400),
+        });
+        assert_eq!(below_cap, cad_money!(400));
+
+        let above_cap = rule.apply_deduction(TaxDeduction {
+            tax_deduction_type: TaxDeductionCategory::EmployeeStockOptions,
+            money_to_deduct: cad_money!(5_000),
+        });
+        assert_eq!(above_cap, cad_money!(1_000));
+    }
+
+    #[test]
+    fn claims_share_a_cap_and_carry_forward() {
+        let schedule = flat_schedule_with_rule(TaxDeductionRule {
+            tax_deduction_type: TaxDeductionCategory::EmployeeStockOptions,
+            max_amount: Some(cad_money!(1_000)),
+            inclusion_rate: dec!(0.5),
+            phase_out: None,
+        });
+        let claims = vec![
+            TaxDeduction {
+                tax_deduction_type: TaxDeductionCategory::EmployeeStockOptions,
+                money_to_deduct: cad_money!(700),
+            },
+            TaxDeduction {
+                tax_deduction_type: TaxDeductionCategory::EmployeeStockOptions,
+                money_to_deduct: cad_money!(800),
+            },
+        ];
+
+        let summary = schedule.determine_deductions(cad_money!(10_000), claims).unwrap();
+        assert_eq!(summary.total, cad_money!(500));
+        assert_eq!(summary.claimed[&TaxDeductionCategory::EmployeeStockOptions], cad_money!(1_000));
+        assert_eq!(summary.carry_forward[&TaxDeductionCategory::EmployeeStockOptions], cad_money!(500));
+
+        let next_year = schedule.determine_deductions(cad_money!(10_000), summary.carry_forward_deductions()).unwrap();
+        assert_eq!(next_year.total, cad_money!(250));
+        assert_eq!(next_year.carry_forward[&TaxDeductionCategory::EmployeeStockOptions], cad_money!(0));
+
+        let tax = schedule.calculate_tax_with_deductions(cad_money!(10_000), vec![TaxDeduction {
+            tax_deduction_type: TaxDeductionCategory::EmployeeStockOptions,
+            money_to_deduct: cad_money!(3_000),
+        }]).unwrap();
+        assert_eq!(tax, cad_money!(950));
+    }
+
+    #[test]
+    fn phase_out_on_net_income() {
+        let schedule = flat_schedule_with_rule(TaxDeductionRule {
+            tax_deduction_type: TaxDeductionCategory::CapitalGains,
+            max_amount: Some(cad_money!(2_000)),
+            inclusion_rate: dec!(1),
+            phase_out: Some(DeductionPhaseOut {
+                threshold: cad_money!(50_000),
+                reduction_rate: dec!(0.1),
+            }),
+        });
+        let claim = || vec![TaxDeduction {
+            tax_deduction_type: TaxDeductionCategory::CapitalGains,
+            money_to_deduct: cad_money!(2_000),
+        }];
+
+        let below = schedule.determine_deductions(cad_money!(40_000), claim()).unwrap();
+        assert_eq!(below.total, cad_money!(2_000));
+
+        let partial = schedule.determine_deductions(cad_money!(55_000), claim()).unwrap();
+        assert_eq!(partial.total, cad_money!(1_500));
+        assert_eq!(partial.carry_forward[&TaxDeductionCategory::CapitalGains], cad_money!(0));
+
+        let over_cap = schedule.determine_deductions(cad_money!(55_000), vec![TaxDeduction {
+            tax_deduction_type: TaxDeductionCategory::CapitalGains,
+            money_to_deduct: cad_money!(2_600),
+        }]).unwrap();
+        assert_eq!(over_cap.total, cad_money!(1_500));
+        assert_eq!(over_cap.carry_forward[&TaxDeductionCategory::CapitalGains], cad_money!(600));
+
+        let phased_out = schedule.determine_deductions(cad_money!(80_000), claim()).unwrap();
+        assert_eq!(phased_out.total, cad_money!(0));
+    }
+}
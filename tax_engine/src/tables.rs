@@ -0,0 +1,483 @@
+//! Tax tables loaded from TOML or JSON files, so a budget update is a data
+//! change rather than a recompile.
+//!
+//! # Schema, version 1
+//!
+//! ```toml
+//! schema_version = 1
+//! jurisdiction = "ca-federal"    # free-form label
+//! year = 2024
+//! currency = "CAD"               # CAD or USD
+//!
+//! # Brackets in increasing order. Each `max` must equal the next `min`, the
+//! # first starts at 0, only the last is open-ended and no rate is below the
+//! # one before it.
+//! [[brackets]]
+//! min = "0"
+//! max = "55867"
+//! rate = "0.15"
+//!
+//! [[brackets]]
+//! min = "55867"
+//! rate = "0.205"
+//!
+//! # Optional. `category` is a `TaxDeductionCategory` variant name.
+//! [[deductions]]
+//! category = "EmployeeStockOptions"
+//! inclusion_rate = "0.5"
+//! max_amount = "10000"           # optional
+//! phase_out = { threshold = "50000", reduction_rate = "0.1" }   # optional
+//!
+//! # Optional. `category` is a `TaxCreditCategory` variant name.
+//! [[credits]]
+//! category = "EligibleDividends"
+//! rate = "0.150198"
+//!
+//! # Optional named dollar amounts (credit bases, contribution limits,
+//! # payroll maximums) and named rates, in the table's currency.
+//! [limits]
+//! basic_personal_amount = "15705"
+//!
+//! [rates]
+//! cpp_contribution_rate = "0.0595"
+//! ```
+//!
+//! The JSON form has the same structure. Amounts and rates may be numbers
+//! or strings; strings keep every decimal exactly. A bracket, limit or
+//! deduction amount may also be a table `{ amount = "...", currency = "..." }`,
+//! whose currency must match the table's.
+
+use rust_decimal::prelude::*;
+use rust_decimal_macros::*;
+use serde_json::{Map, Value};
+use simple_money::*;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fs;
+use std::path::Path;
+use crate::{
+    DeductionPhaseOut, TaxBracket, TaxCreditCategory, TaxCreditRule, TaxDeductionCategory, TaxDeductionRule, TaxError,
+    TaxSchedule,
+};
+
+/// The only schema version this loader understands.
+pub const TAX_TABLE_SCHEMA_VERSION: u64 = 1;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TableFormat {
+    Toml,
+    Json,
+}
+
+impl TableFormat {
+    /// Picks the format from a file's extension.
+    pub fn from_path(path: &Path) -> Option<TableFormat> {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("toml") => Some(TableFormat::Toml),
+            Some("json") => Some(TableFormat::Json),
+            _ => None,
+        }
+    }
+}
+
+/// One year's tables for one jurisdiction.
+#[derive(Clone, Debug)]
+pub struct TaxTable {
+    pub source: String,
+    pub jurisdiction: String,
+    pub year: u32,
+    pub currency: Currency,
+    pub schedule: TaxSchedule,
+    limits: HashMap<String, Money>,
+    rates: HashMap<String, Decimal>,
+}
+
+impl TaxTable {
+    pub fn limit(&self, name: &str) -> Result<Money, TaxError> {
+        self.limits
+            .get(name)
+            .copied()
+            .ok_or_else(|| table_error(&self.source, &format!("limits.{}", name), "is missing"))
+    }
+
+    pub fn rate(&self, name: &str) -> Result<Decimal, TaxError> {
+        self.rates
+            .get(name)
+            .copied()
+            .ok_or_else(|| table_error(&self.source, &format!("rates.{}", name), "is missing"))
+    }
+}
+
+fn table_error(file: &str, field: &str, message: &str) -> TaxError {
+    TaxError::InvalidTable {
+        file: file.to_string(),
+        field: field.to_string(),
+        message: message.to_string(),
+    }
+}
+
+/// Reads and validates a table, choosing the format from the extension.
+pub fn load_tax_table(path: &Path) -> Result<TaxTable, TaxError> {
+    let source = path.display().to_string();
+    let format = TableFormat::from_path(path)
+        .ok_or_else(|| table_error(&source, "file", "must have a .toml or .json extension"))?;
+    let contents = fs::read_to_string(path).map_err(|error| table_error(&source, "file", &error.to_string()))?;
+    parse_tax_table(&source, &contents, format)
+}
+
+/// Validates a table already read into memory. `source` names it in errors.
+pub fn parse_tax_table(source: &str, contents: &str, format: TableFormat) -> Result<TaxTable, TaxError> {
+    let document = match format {
+        TableFormat::Toml => contents
+            .parse::<toml::Value>()
+            .map_err(|error| error.to_string())
+            .and_then(|value| serde_json::to_value(value).map_err(|error| error.to_string())),
+        TableFormat::Json => serde_json::from_str::<Value>(contents).map_err(|error| error.to_string()),
+    }
+    .map_err(|message| table_error(source, "file", &message))?;
+
+    TableReader { source }.read(&document)
+}
+
+/// Walks a parsed document, keeping the file name for errors.
+struct TableReader<'a> {
+    source: &'a str,
+}
+
+impl<'a> TableReader<'a> {
+    fn error(&self, field: &str, message: &str) -> TaxError {
+        table_error(self.source, field, message)
+    }
+
+    fn object<'v>(&self, value: &'v Value, field: &str) -> Result<&'v Map<String, Value>, TaxError> {
+        value.as_object().ok_or_else(|| self.error(field, "must be a table"))
+    }
+
+    fn required<'v>(&self, table: &'v Map<String, Value>, prefix: &str, key: &str) -> Result<&'v Value, TaxError> {
+        table.get(key).ok_or_else(|| self.error(&join(prefix, key), "is missing"))
+    }
+
+    fn string<'v>(&self, value: &'v Value, field: &str) -> Result<&'v str, TaxError> {
+        value.as_str().ok_or_else(|| self.error(field, "must be a string"))
+    }
+
+    fn unsigned(&self, value: &Value, field: &str) -> Result<u64, TaxError> {
+        value.as_u64().ok_or_else(|| self.error(field, "must be a whole number"))
+    }
+
+    fn decimal(&self, value: &Value, field: &str) -> Result<Decimal, TaxError> {
+        let text = match value {
+            Value::String(text) => text.clone(),
+            Value::Number(number) => number.to_string(),
+            _ => return Err(self.error(field, "must be a number or a decimal string")),
+        };
+        Decimal::from_str(&text).map_err(|_| self.error(field, &format!("`{}` is not a decimal", text)))
+    }
+
+    fn rate(&self, value: &Value, field: &str) -> Result<Decimal, TaxError> {
+        let rate = self.decimal(value, field)?;
+        if rate < dec!(0) {
+            return Err(self.error(field, "must not be negative"));
+        }
+        Ok(rate)
+    }
+
+    fn currency(&self, value: &Value, field: &str) -> Result<Currency, TaxError> {
+        match self.string(value, field)? {
+            "CAD" => Ok(Currency::CAD),
+            "USD" => Ok(Currency::USD),
+            other => Err(self.error(field, &format!("`{}` is not CAD or USD", other))),
+        }
+    }
+
+    fn money(&self, value: &Value, field: &str, currency: Currency) -> Result<Money, TaxError> {
+        let amount = match value {
+            Value::Object(table) => {
+                let amount_currency = self.currency(self.required(table, field, "currency")?, &join(field, "currency"))?;
+                if amount_currency != currency {
+                    return Err(self.error(&join(field, "currency"), "does not match the table's currency"));
+                }
+                self.decimal(self.required(table, field, "amount")?, &join(field, "amount"))?
+            }
+            _ => self.decimal(value, field)?,
+        };
+        Ok(Money { amount, currency })
+    }
+
+    fn array<'v>(&self, table: &'v Map<String, Value>, key: &str) -> Result<&'v [Value], TaxError> {
+        match table.get(key) {
+            Some(value) => value.as_array().map(|values| values.as_slice()).ok_or_else(|| self.error(key, "must be an array")),
+            None => Ok(&[]),
+        }
+    }
+
+    fn read(&self, document: &Value) -> Result<TaxTable, TaxError> {
+        let root = self.object(document, "file")?;
+        let version = self.unsigned(self.required(root, "", "schema_version")?, "schema_version")?;
+        if version != TAX_TABLE_SCHEMA_VERSION {
+            return Err(self.error("schema_version", &format!("version {} is not supported", version)));
+        }
+        let jurisdiction = self.string(self.required(root, "", "jurisdiction")?, "jurisdiction")?.to_string();
+        let year = self.unsigned(self.required(root, "", "year")?, "year")?;
+        let year = u32::try_from(year).map_err(|_| self.error("year", "is out of range"))?;
+        let currency = self.currency(self.required(root, "", "currency")?, "currency")?;
+
+        let brackets = self.brackets(root, currency)?;
+        let mut schedule =
+            TaxSchedule::new(brackets.clone(), currency).map_err(|error| self.bracket_error(&brackets, error))?;
+        for (index, value) in self.array(root, "deductions")?.iter().enumerate() {
+            let rule = self.deduction(value, &format!("deductions[{}]", index), currency)?;
+            schedule.set_deduction(rule.tax_deduction_type, rule);
+        }
+        for (index, value) in self.array(root, "credits")?.iter().enumerate() {
+            let rule = self.credit(value, &format!("credits[{}]", index))?;
+            schedule.set_credit(rule.tax_credit_type, rule);
+        }
+
+        let mut limits = HashMap::new();
+        if let Some(value) = root.get("limits") {
+            for (name, amount) in self.object(value, "limits")? {
+                limits.insert(name.clone(), self.money(amount, &join("limits", name), currency)?);
+            }
+        }
+        let mut rates = HashMap::new();
+        if let Some(value) = root.get("rates") {
+            for (name, rate) in self.object(value, "rates")? {
+                rates.insert(name.clone(), self.rate(rate, &join("rates", name))?);
+            }
+        }
+
+        Ok(TaxTable {
+            source: self.source.to_string(),
+            jurisdiction,
+            year,
+            currency,
+            schedule,
+            limits,
+            rates,
+        })
+    }
+
+    /// Reads the brackets. Their structure is checked by `TaxSchedule::new`,
+    /// and `bracket_error` points any problem at the field that caused it.
+    fn brackets(&self, root: &Map<String, Value>, currency: Currency) -> Result<Vec<TaxBracket>, TaxError> {
+        let values = self.array(root, "brackets")?;
+        if values.is_empty() {
+            return Err(self.error("brackets", "must have at least one bracket"));
+        }
+
+        let mut brackets = Vec::with_capacity(values.len());
+        for (index, value) in values.iter().enumerate() {
+            let field = format!("brackets[{}]", index);
+            let table = self.object(value, &field)?;
+            let min_money = self.money(self.required(table, &field, "min")?, &join(&field, "min"), currency)?;
+            let max_money = match table.get("max") {
+                Some(max) => Some(self.money(max, &join(&field, "max"), currency)?),
+                None => None,
+            };
+            let rate = self.rate(self.required(table, &field, "rate")?, &join(&field, "rate"))?;
+            brackets.push(TaxBracket::new(min_money, max_money, rate)?);
+        }
+        Ok(brackets)
+    }
+
+    /// Turns a bracket structure error from `TaxSchedule::new` into one
+    /// naming the bracket field, by the bracket index in the error.
+    fn bracket_error(&self, brackets: &[TaxBracket], error: TaxError) -> TaxError {
+        let field = |index: usize, name: &str| join(&format!("brackets[{}]", index), name);
+
+        match error {
+            TaxError::BracketGap(index, end, _) => self.error(&field(index, "min"), &format!("leaves a gap after {}", end)),
+            TaxError::BracketOverlap(index, _, end) => {
+                self.error(&field(index, "min"), &format!("overlaps the previous bracket, which ends at {}", end))
+            }
+            TaxError::EmptyBracket(index, _, _) => self.error(&field(index, "max"), "must be above min"),
+            TaxError::OpenEndedBracketNotLast(index) => {
+                self.error(&field(index, "max"), "is missing, but only the last bracket can be open-ended")
+            }
+            TaxError::DecreasingRate(index, previous_rate, _) => {
+                self.error(&field(index, "rate"), &format!("is below the rate of the previous bracket, {}", previous_rate))
+            }
+            // Nothing is out of order, so the top bracket is the one with the
+            // highest minimum.
+            TaxError::MissingOpenEndedBracket => match brackets.iter().enumerate().max_by_key(|(_, bracket)| bracket.min_money) {
+                Some((index, _)) => self.error(&field(index, "max"), "must be left out so the top bracket is open-ended"),
+                None => error,
+            },
+            other => other,
+        }
+    }
+
+    fn deduction(&self, value: &Value, field: &str, currency: Currency) -> Result<TaxDeductionRule, TaxError> {
+        let table = self.object(value, field)?;
+        let category_field = join(field, "category");
+        let category = match self.string(self.required(table, field, "category")?, &category_field)? {
+            "CapitalGains" => TaxDeductionCategory::CapitalGains,
+            "EmployeeStockOptions" => TaxDeductionCategory::EmployeeStockOptions,
+            "RrspContributions" => TaxDeductionCategory::RrspContributions,
+            other => return Err(self.error(&category_field, &format!("`{}` is not a deduction category", other))),
+        };
+        let max_amount = match table.get("max_amount") {
+            Some(max_amount) => Some(self.money(max_amount, &join(field, "max_amount"), currency)?),
+            None => None,
+        };
+        let phase_out = match table.get("phase_out") {
+            Some(value) => {
+                let phase_out_field = join(field, "phase_out");
+                let phase_out = self.object(value, &phase_out_field)?;
+                Some(DeductionPhaseOut {
+                    threshold: self.money(
+                        self.required(phase_out, &phase_out_field, "threshold")?,
+                        &join(&phase_out_field, "threshold"),
+                        currency,
+                    )?,
+                    reduction_rate: self.rate(
+                        self.required(phase_out, &phase_out_field, "reduction_rate")?,
+                        &join(&phase_out_field, "reduction_rate"),
+                    )?,
+                })
+            }
+            None => None,
+        };
+
+        Ok(TaxDeductionRule {
+            tax_deduction_type: category,
+            max_amount,
+            inclusion_rate: self.rate(self.required(table, field, "inclusion_rate")?, &join(field, "inclusion_rate"))?,
+            phase_out,
+        })
+    }
+
+    fn credit(&self, value: &Value, field: &str) -> Result<TaxCreditRule, TaxError> {
+        let table = self.object(value, field)?;
+        let category_field = join(field, "category");
+        let category = match self.string(self.required(table, field, "category")?, &category_field)? {
+            "EligibleDividends" => TaxCreditCategory::EligibleDividends,
+            "NonEligibleDividends" => TaxCreditCategory::NonEligibleDividends,
+            other => return Err(self.error(&category_field, &format!("`{}` is not a credit category", other))),
+        };
+
+        Ok(TaxCreditRule {
+            tax_credit_type: category,
+            rate: self.rate(self.required(table, field, "rate")?, &join(field, "rate"))?,
+        })
+    }
+}
+
+fn join(prefix: &str, key: &str) -> String {
+    if prefix.is_empty() {
+        key.to_string()
+    }else{
+        format!("{}.{}", prefix, key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use crate::ca::{
+        basic_personal_amount, federal_schedule, rrsp_dollar_limit, tfsa_dollar_limit, DividendType, LOWEST_FEDERAL_RATE,
+    };
+
+    fn data_file(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("data").join(name)
+    }
+
+    /// The bundled table duplicates values the engine still builds in Rust,
+    /// so every entry is checked against them to keep the two from drifting.
+    #[test]
+    fn bundled_federal_table_matches_the_built_in_values() {
+        let table = load_tax_table(&data_file("ca/federal-2024.toml")).unwrap();
+        let built_in = federal_schedule(2024).unwrap();
+        assert_eq!(table.year, 2024);
+        assert_eq!(table.schedule.brackets, built_in.brackets);
+
+        assert_eq!(table.schedule.deductions_map.len(), built_in.deductions_map.len());
+        for (category, rule) in table.schedule.deductions_map.iter() {
+            let expected = built_in.deductions_map[category];
+            assert_eq!(rule.max_amount, expected.max_amount, "{:?}", category);
+            assert_eq!(rule.inclusion_rate, expected.inclusion_rate, "{:?}", category);
+            assert_eq!(rule.phase_out, expected.phase_out, "{:?}", category);
+        }
+        assert_eq!(table.schedule.credits_map.len(), built_in.credits_map.len());
+        for (category, rule) in table.schedule.credits_map.iter() {
+            assert_eq!(rule.rate, built_in.credits_map[category].rate, "{:?}", category);
+        }
+
+        for (name, limit) in table.limits.iter() {
+            let expected = match name.as_str() {
+                "basic_personal_amount" => basic_personal_amount(2024, cad_money!(0)).unwrap(),
+                "basic_personal_amount_minimum" => basic_personal_amount(2024, cad_money!(1_000_000)).unwrap(),
+                "rrsp_dollar_limit" => rrsp_dollar_limit(2024).unwrap(),
+                "tfsa_dollar_limit" => tfsa_dollar_limit(2024).unwrap(),
+                _ => panic!("limits.{} has no built-in value to check", name),
+            };
+            assert_eq!(*limit, expected, "limits.{}", name);
+        }
+        for (name, rate) in table.rates.iter() {
+            let expected = match name.as_str() {
+                "lowest_rate" => LOWEST_FEDERAL_RATE,
+                "eligible_dividend_gross_up" => DividendType::Eligible.gross_up_rate(),
+                "non_eligible_dividend_gross_up" => DividendType::NonEligible.gross_up_rate(),
+                _ => panic!("rates.{} has no built-in value to check", name),
+            };
+            assert_eq!(*rate, expected, "rates.{}", name);
+        }
+    }
+
+    #[test]
+    fn json_tables_with_exact_decimals() {
+        let contents = r#"{
+            "schema_version": 1,
+            "jurisdiction": "us-federal-single",
+            "year": 2024,
+            "currency": "USD",
+            "brackets": [
+                { "min": 0, "max": "11600", "rate": "0.10" },
+                { "min": { "amount": "11600", "currency": "USD" }, "rate": 0.12 }
+            ],
+            "deductions": [{ "category": "CapitalGains", "inclusion_rate": "0.5" }],
+            "limits": { "elective_deferral": "23000" }
+        }"#;
+        let table = parse_tax_table("inline.json", contents, TableFormat::Json).unwrap();
+        assert_eq!(table.schedule.calculate_tax(usd_money!(21_600)), usd_money!(2_360));
+        assert_eq!(table.limit("elective_deferral").unwrap(), usd_money!(23_000));
+        assert_eq!(
+            table.rate("missing").unwrap_err().to_string(),
+            "inline.json: rates.missing: is missing"
+        );
+    }
+
+    #[test]
+    fn errors_name_the_file_and_field() {
+        let parse = |brackets: &str| {
+            let contents = format!("schema_version = 1\njurisdiction = \"test\"\nyear = 2024\ncurrency = \"CAD\"\n{}", brackets);
+            parse_tax_table("test.toml", &contents, TableFormat::Toml).unwrap_err().to_string()
+        };
+
+        let gap = "[[brackets]]\nmin = 0\nmax = 10000\nrate = 0.1\n[[brackets]]\nmin = 12000\nrate = 0.2\n";
+        assert_eq!(parse(gap), "test.toml: brackets[1].min: leaves a gap after 10000");
+        let overlap = "[[brackets]]\nmin = 0\nmax = 10000\nrate = 0.1\n[[brackets]]\nmin = 9000\nrate = 0.2\n";
+        assert_eq!(parse(overlap), "test.toml: brackets[1].min: overlaps the previous bracket, which ends at 10000");
+        let empty = "[[brackets]]\nmin = 0\nmax = 0\nrate = 0.1\n[[brackets]]\nmin = 0\nrate = 0.2\n";
+        assert_eq!(parse(empty), "test.toml: brackets[0].max: must be above min");
+        let open = "[[brackets]]\nmin = 0\nrate = 0.1\n[[brackets]]\nmin = 10000\nrate = 0.2\n";
+        assert_eq!(parse(open), "test.toml: brackets[0].max: is missing, but only the last bracket can be open-ended");
+        let open_listed_last = "[[brackets]]\nmin = 10000\nrate = 0.2\n[[brackets]]\nmin = 0\nrate = 0.1\n";
+        assert_eq!(parse(open_listed_last), "test.toml: brackets[1].max: is missing, but only the last bracket can be open-ended");
+        let falling = "[[brackets]]\nmin = 10000\nrate = 0.2\n[[brackets]]\nmin = 0\nmax = 10000\nrate = 0.3\n";
+        assert_eq!(parse(falling), "test.toml: brackets[0].rate: is below the rate of the previous bracket, 0.3");
+        let closed = "[[brackets]]\nmin = 0\nmax = 10000\nrate = 0.1\n";
+        assert_eq!(parse(closed), "test.toml: brackets[0].max: must be left out so the top bracket is open-ended");
+        let usd = "[[brackets]]\nmin = { amount = 0, currency = \"USD\" }\nrate = 0.1\n";
+        assert_eq!(parse(usd), "test.toml: brackets[0].min.currency: does not match the table's currency");
+        let negative = "[[brackets]]\nmin = 0\nrate = \"-0.1\"\n";
+        assert_eq!(parse(negative), "test.toml: brackets[0].rate: must not be negative");
+        let gross_up = "[[brackets]]\nmin = 0\nrate = 0.1\n[[deductions]]\ncategory = \"CapitalGains\"\ninclusion_rate = \"-0.38\"\n";
+        assert_eq!(parse(gross_up), "test.toml: deductions[0].inclusion_rate: must not be negative");
+
+        let missing = load_tax_table(Path::new("no-such-table.toml")).unwrap_err();
+        assert!(missing.to_string().starts_with("no-such-table.toml: file: "));
+    }
+}
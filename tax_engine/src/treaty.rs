@@ -0,0 +1,397 @@
+//! The Canada–US tax treaty: the rates each country may charge residents of
+//! the other, the employment income exemption, and the foreign tax credit
+//! ordering for US citizens living in Canada.
+//!
+//! Canada taxes its residents on worldwide income and the US taxes its
+//! citizens wherever they live, so a US citizen resident in Canada is taxed
+//! twice on everything. Article XXIV sorts this out in three steps:
+//!
+//! 1. Canada credits US tax on US-source income, but for dividends, interest
+//!    and pensions only up to the treaty rate the US could charge someone
+//!    who is not a citizen.
+//! 2. The US credits Canadian tax on Canadian-source income as usual.
+//! 3. For the US-source income in step 1, the US credits the Canadian tax
+//!    left after Canada's credit, down to the treaty-rate tax. To make room
+//!    under the limitation, just enough of that income is re-sourced as
+//!    foreign and goes in its own Form 1116 category.
+
+use rust_decimal::prelude::*;
+use rust_decimal_macros::*;
+use simple_money::*;
+use crate::ca::{
+    basic_federal_tax, provincial_tax, CanadianForeignTaxCredit, CanadianForeignTaxCreditYear, CanadianJurisdiction,
+    ForeignIncome, ForeignIncomeKind,
+};
+use crate::us::{
+    FederalIncomeTax, FilingStatus, ForeignSourceIncome, ForeignTaxCategory, IncomeTaxJurisdiction, UsForeignTaxCredit,
+    UsForeignTaxCreditYear, UsIncome, UsTaxComponent,
+};
+use crate::TaxError;
+
+/// Share of US social security benefits Canada taxes, under Article
+/// XVIII(5)(a).
+pub const CANADIAN_SOCIAL_SECURITY_INCLUSION_RATE: Decimal = dec!(0.85);
+
+/// Most of a Canadian social security benefit the US includes in income.
+const US_SOCIAL_SECURITY_INCLUSION_RATE: Decimal = dec!(0.85);
+
+/// Employment income at or below this amount, in the source country's
+/// currency, is only taxed where the employee lives.
+const EMPLOYMENT_EXEMPTION_AMOUNT: Decimal = dec!(10_000);
+
+/// Days an employee can work in the other country in any twelve-month
+/// period before their pay is taxed there.
+const EMPLOYMENT_EXEMPTION_DAYS: u32 = 183;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TreatyCountry {
+    Canada,
+    UnitedStates,
+}
+
+impl TreatyCountry {
+    pub fn currency(&self) -> Currency {
+        match self {
+            TreatyCountry::Canada => Currency::CAD,
+            TreatyCountry::UnitedStates => Currency::USD,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TreatyIncomeKind {
+    Employment,
+    /// Portfolio dividends, from companies the holder owns less than 10% of.
+    Dividends,
+    Interest,
+    /// Periodic pension payments.
+    Pension,
+    SocialSecurity,
+}
+
+impl TreatyIncomeKind {
+    /// Most the source country may tax a resident of the other country on
+    /// this income, under Articles X, XI and XVIII. Employment income has no
+    /// cap and is taxed at source unless `employment_taxable_at_source`
+    /// says otherwise.
+    pub fn treaty_rate(&self) -> Option<Decimal> {
+        match self {
+            TreatyIncomeKind::Employment => None,
+            TreatyIncomeKind::Dividends => Some(dec!(0.15)),
+            TreatyIncomeKind::Interest => Some(dec!(0)),
+            TreatyIncomeKind::Pension => Some(dec!(0.15)),
+            TreatyIncomeKind::SocialSecurity => Some(dec!(0)),
+        }
+    }
+
+    fn canadian_inclusion_rate(&self, source: TreatyCountry) -> Decimal {
+        match (self, source) {
+            (TreatyIncomeKind::SocialSecurity, TreatyCountry::UnitedStates) => CANADIAN_SOCIAL_SECURITY_INCLUSION_RATE,
+            _ => dec!(1),
+        }
+    }
+
+    /// US social security paid to a Canadian resident is only taxed in
+    /// Canada under Article XVIII(5)(a), even for US citizens.
+    fn taxed_by_the_us(&self, source: TreatyCountry) -> bool {
+        !matches!((self, source), (TreatyIncomeKind::SocialSecurity, TreatyCountry::UnitedStates))
+    }
+
+    fn canadian_kind(&self) -> ForeignIncomeKind {
+        match self {
+            TreatyIncomeKind::Dividends | TreatyIncomeKind::Interest => ForeignIncomeKind::Property,
+            _ => ForeignIncomeKind::NonBusiness,
+        }
+    }
+
+    fn us_category(&self) -> ForeignTaxCategory {
+        match self {
+            TreatyIncomeKind::Dividends | TreatyIncomeKind::Interest => ForeignTaxCategory::Passive,
+            _ => ForeignTaxCategory::General,
+        }
+    }
+}
+
+/// Whether pay for work done in the other country can be taxed there, under
+/// Article XV. It cannot when it is $10,000 or less in that country's
+/// currency, or when the employee was there no more than 183 days and the
+/// pay was not borne by an employer resident or established there.
+pub fn employment_taxable_at_source(remuneration: Money, days_present: u32, borne_by_source_employer: bool) -> bool {
+    remuneration.amount > EMPLOYMENT_EXEMPTION_AMOUNT
+        && (days_present > EMPLOYMENT_EXEMPTION_DAYS || borne_by_source_employer)
+}
+
+/// An amount of income, in the currency of the country it comes from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CrossBorderIncome {
+    pub kind: TreatyIncomeKind,
+    pub source: TreatyCountry,
+    pub amount: Money,
+}
+
+/// Both countries' tax for one year, each in its own currency.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CrossBorderReturn {
+    pub canadian_tax_before_credits: Money,
+    pub canadian_credit: CanadianForeignTaxCreditYear,
+    pub canadian_tax: Money,
+    /// Regular income tax, which the foreign tax credit can offset.
+    pub us_tax_before_credits: Money,
+    pub us_credit: UsForeignTaxCreditYear,
+    /// After credits, including the Net Investment Income Tax.
+    pub us_tax: Money,
+    /// US-source income treated as foreign under Article XXIV.
+    pub resourced_income: Money,
+}
+
+/// A US citizen resident in a Canadian province, filing in both countries
+/// year after year so foreign tax credit carryovers follow them.
+#[derive(Clone, Debug)]
+pub struct UsCitizenResidentInCanada {
+    province: CanadianJurisdiction,
+    filing_status: FilingStatus,
+    canadian_credit: CanadianForeignTaxCredit,
+    us_credit: UsForeignTaxCredit,
+}
+
+impl UsCitizenResidentInCanada {
+    pub fn new(province: CanadianJurisdiction, filing_status: FilingStatus) -> UsCitizenResidentInCanada {
+        UsCitizenResidentInCanada {
+            province,
+            filing_status,
+            canadian_credit: CanadianForeignTaxCredit::new(),
+            us_credit: UsForeignTaxCredit::new(),
+        }
+    }
+
+    /// Works out both returns for `year`. Amounts are converted with
+    /// `exchange`, and tax is attributed to each item of income by its share
+    /// of total income. Canadian-source dividends are taxed as ordinary
+    /// income here, without the gross-up and dividend tax credit.
+    pub fn file(&mut self, year: u32, income: &[CrossBorderIncome], exchange: &Exchange) -> Result<CrossBorderReturn, TaxError> {
+        if income.iter().any(|item| item.amount.currency != item.source.currency()) {
+            return Err(TaxError::MismatchedCurrencies);
+        }
+        let zero_cad = cad_money!(0);
+        let zero_usd = usd_money!(0);
+
+        let mut canadian_amounts = vec![];
+        let mut us_amounts = vec![];
+        for item in income.iter() {
+            canadian_amounts.push(exchange.convert(item.amount, Currency::CAD)? * item.kind.canadian_inclusion_rate(item.source));
+            us_amounts.push(exchange.convert(item.amount, Currency::USD)?);
+        }
+
+        // Canada: worldwide income, before any foreign tax credit.
+        let canadian_income = canadian_amounts.iter().fold(zero_cad, |sum, amount| sum + *amount);
+        let federal_tax = basic_federal_tax(year, canadian_income)?;
+        let provincial_tax = provincial_tax(self.province, year, canadian_income, zero_cad)?;
+        let canadian_tax_before_credits = federal_tax + provincial_tax;
+        let canadian_share = |amount: Money| {
+            if canadian_income > zero_cad {
+                canadian_tax_before_credits * (amount.amount / canadian_income.amount)
+            }else{
+                zero_cad
+            }
+        };
+
+        // US: worldwide income, before any foreign tax credit.
+        let mut us_income = UsIncome::from_wages(zero_usd);
+        for (item, amount) in income.iter().zip(us_amounts.iter()) {
+            if !item.kind.taxed_by_the_us(item.source) {
+                continue;
+            }
+            match item.kind {
+                TreatyIncomeKind::Employment => us_income.wages = us_income.wages + *amount,
+                TreatyIncomeKind::Dividends => us_income.qualified_dividends = us_income.qualified_dividends + *amount,
+                TreatyIncomeKind::Interest | TreatyIncomeKind::Pension => {
+                    us_income.other_ordinary_income = us_income.other_ordinary_income + *amount
+                }
+                TreatyIncomeKind::SocialSecurity => {
+                    us_income.other_ordinary_income =
+                        us_income.other_ordinary_income + *amount * US_SOCIAL_SECURITY_INCLUSION_RATE
+                }
+            }
+        }
+        let us_total_income = us_income.adjusted_gross_income();
+        let us_computation = FederalIncomeTax::new(year, self.filing_status)?.calculate(&us_income)?;
+        let us_tax_before_credits = us_computation.component(UsTaxComponent::IncomeTax);
+        let us_share = |amount: Money| {
+            if us_total_income > zero_usd {
+                us_tax_before_credits * (amount.amount / us_total_income.amount)
+            }else{
+                zero_usd
+            }
+        };
+
+        // Step 1: Canada credits US tax on US-source income, capped at the
+        // treaty rate for dividends, interest and pensions.
+        let mut canadian_foreign_income = vec![];
+        for ((item, canadian_amount), us_amount) in income.iter().zip(canadian_amounts.iter()).zip(us_amounts.iter()) {
+            if item.source != TreatyCountry::UnitedStates {
+                continue;
+            }
+            let us_tax = match item.kind.treaty_rate() {
+                Some(rate) => *us_amount * rate,
+                None => us_share(*us_amount),
+            };
+            canadian_foreign_income.push(ForeignIncome {
+                kind: item.kind.canadian_kind(),
+                income: *canadian_amount,
+                tax_paid: exchange.convert(us_tax, Currency::CAD)?,
+            });
+        }
+        let canadian_credit =
+            self.canadian_credit
+                .record_year(year, canadian_income, federal_tax, provincial_tax, &canadian_foreign_income)?;
+        let canadian_tax = canadian_tax_before_credits - canadian_credit.total();
+
+        // Steps 2 and 3: the US credits Canadian tax on Canadian-source
+        // income, and the Canadian tax left on US-source income once enough
+        // of it is re-sourced.
+        let mut us_foreign_income = vec![];
+        let mut resourced_income = zero_usd;
+        for ((item, canadian_amount), us_amount) in income.iter().zip(canadian_amounts.iter()).zip(us_amounts.iter()) {
+            if !item.kind.taxed_by_the_us(item.source) {
+                continue;
+            }
+            let canadian_tax_on_item = exchange.convert(canadian_share(*canadian_amount), Currency::USD)?;
+            match (item.source, item.kind.treaty_rate()) {
+                (TreatyCountry::Canada, _) => us_foreign_income.push(ForeignSourceIncome {
+                    category: item.kind.us_category(),
+                    income: *us_amount,
+                    tax_paid: canadian_tax_on_item,
+                }),
+                (TreatyCountry::UnitedStates, Some(rate)) => {
+                    let us_tax_on_item = us_share(*us_amount);
+                    let treaty_tax = *us_amount * rate;
+                    if us_tax_on_item <= treaty_tax {
+                        continue;
+                    }
+                    let resourced = *us_amount * ((us_tax_on_item - treaty_tax).amount / us_tax_on_item.amount);
+                    resourced_income = resourced_income + resourced;
+                    us_foreign_income.push(ForeignSourceIncome {
+                        category: ForeignTaxCategory::TreatyResourced,
+                        income: resourced,
+                        tax_paid: (canadian_tax_on_item - treaty_tax).max(zero_usd),
+                    });
+                }
+                (TreatyCountry::UnitedStates, None) => {}
+            }
+        }
+        let us_credit = self.us_credit.record_year(year, us_tax_before_credits, us_total_income, &us_foreign_income)?;
+        let us_tax = us_computation.total() - us_credit.total();
+
+        Ok(CrossBorderReturn {
+            canadian_tax_before_credits,
+            canadian_credit,
+            canadian_tax,
+            us_tax_before_credits,
+            us_credit,
+            us_tax,
+            resourced_income,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::round_cents;
+
+    #[test]
+    fn treaty_rates_and_the_employment_exemption() {
+        assert_eq!(TreatyIncomeKind::Dividends.treaty_rate(), Some(dec!(0.15)));
+        assert_eq!(TreatyIncomeKind::Interest.treaty_rate(), Some(dec!(0)));
+        assert_eq!(TreatyIncomeKind::Employment.treaty_rate(), None);
+
+        assert!(!employment_taxable_at_source(usd_money!(9_000), 250, true));
+        assert!(!employment_taxable_at_source(usd_money!(50_000), 120, false));
+        assert!(employment_taxable_at_source(usd_money!(50_000), 120, true));
+        assert!(employment_taxable_at_source(usd_money!(50_000), 200, false));
+    }
+
+    #[test]
+    fn us_citizen_in_ontario_with_us_dividends() {
+        let mut exchange = Exchange::new();
+        exchange.set_rate(Currency::USD, Currency::CAD, dec!(1.25));
+        let income = [
+            CrossBorderIncome { kind: TreatyIncomeKind::Employment, source: TreatyCountry::Canada, amount: cad_money!(100_000) },
+            CrossBorderIncome { kind: TreatyIncomeKind::Dividends, source: TreatyCountry::UnitedStates, amount: usd_money!(10_000) },
+        ];
+        let mut taxpayer = UsCitizenResidentInCanada::new(CanadianJurisdiction::Ontario, FilingStatus::Single);
+        let result = taxpayer.file(2024, &income, &exchange).unwrap();
+
+        // Canada only credits the 15% the treaty lets the US charge.
+        assert_eq!(round_cents(result.canadian_tax_before_credits), cad_money!(26_848.75));
+        assert_eq!(result.canadian_credit.federal_non_business_credit, cad_money!(1_875));
+        assert_eq!(round_cents(result.canadian_tax), cad_money!(24_973.75));
+
+        // The US credit on the Canadian wages is limited to the US tax on
+        // them, and the US tax on the dividends is already below the treaty
+        // rate, so nothing needs re-sourcing.
+        assert_eq!(result.us_tax_before_credits, usd_money!(10_941));
+        assert_eq!(round_cents(result.us_credit.categories[0].credit), usd_money!(9_725.33));
+        assert_eq!(result.resourced_income, usd_money!(0));
+        assert_eq!(round_cents(result.us_tax), usd_money!(1_215.67));
+
+        assert_eq!(
+            taxpayer.file(2024, &[CrossBorderIncome { source: TreatyCountry::Canada, ..income[1] }], &exchange).unwrap_err(),
+            TaxError::MismatchedCurrencies
+        );
+    }
+
+    #[test]
+    fn us_dividends_are_resourced_to_credit_canadian_tax() {
+        let mut exchange = Exchange::new();
+        exchange.set_rate(Currency::USD, Currency::CAD, dec!(1.25));
+        let income = [
+            CrossBorderIncome { kind: TreatyIncomeKind::Employment, source: TreatyCountry::Canada, amount: cad_money!(250_000) },
+            CrossBorderIncome { kind: TreatyIncomeKind::Dividends, source: TreatyCountry::UnitedStates, amount: usd_money!(50_000) },
+        ];
+        let mut taxpayer = UsCitizenResidentInCanada::new(CanadianJurisdiction::Ontario, FilingStatus::Single);
+        let result = taxpayer.file(2024, &income, &exchange).unwrap();
+        assert_eq!(result.canadian_credit.federal_non_business_credit, cad_money!(9_375));
+
+        // US tax on the dividends is 45,038.50 / 5 = 9,007.70, above the
+        // 7,500 the treaty allows, so enough of them is re-sourced for the
+        // Canadian tax to cover the difference.
+        assert_eq!(round_cents(result.resourced_income), usd_money!(8_368.95));
+        let resourced = result.us_credit.categories[1];
+        assert_eq!(resourced.category, ForeignTaxCategory::TreatyResourced);
+        assert_eq!(round_cents(resourced.credit), usd_money!(1_507.70));
+        // What is left is the treaty-rate tax plus the 3.8% Net Investment
+        // Income Tax, which the credit cannot offset.
+        assert_eq!(round_cents(result.us_tax), usd_money!(9_400));
+        assert_eq!(taxpayer.us_credit.carryover(ForeignTaxCategory::General, 2025), result.us_credit.categories[0].unused);
+    }
+
+    #[test]
+    fn us_social_security_is_only_taxed_in_canada() {
+        let mut exchange = Exchange::new();
+        exchange.set_rate(Currency::USD, Currency::CAD, dec!(1.25));
+        let income = [
+            CrossBorderIncome { kind: TreatyIncomeKind::Employment, source: TreatyCountry::Canada, amount: cad_money!(60_000) },
+            CrossBorderIncome { kind: TreatyIncomeKind::SocialSecurity, source: TreatyCountry::UnitedStates, amount: usd_money!(20_000) },
+        ];
+        let mut with_benefits = UsCitizenResidentInCanada::new(CanadianJurisdiction::Ontario, FilingStatus::Single);
+        let result = with_benefits.file(2024, &income, &exchange).unwrap();
+        let mut wages_only = UsCitizenResidentInCanada::new(CanadianJurisdiction::Ontario, FilingStatus::Single);
+        let without = wages_only.file(2024, &income[..1], &exchange).unwrap();
+
+        // The US return only has the wages on it.
+        assert_eq!(result.us_tax_before_credits, usd_money!(3_776));
+        assert_eq!(result.us_tax_before_credits, without.us_tax_before_credits);
+        assert_eq!(result.resourced_income, usd_money!(0));
+        assert_eq!(result.us_credit.categories.len(), 1);
+        assert_eq!(result.us_credit.categories[0].category, ForeignTaxCategory::General);
+
+        // Canada taxes 85% of the benefit and has no US tax to credit.
+        assert_eq!(
+            result.canadian_tax_before_credits,
+            basic_federal_tax(2024, cad_money!(81_250)).unwrap()
+                + provincial_tax(CanadianJurisdiction::Ontario, 2024, cad_money!(81_250), cad_money!(0)).unwrap()
+        );
+        assert_eq!(result.canadian_credit.total(), cad_money!(0));
+    }
+}
@@ -0,0 +1,210 @@
+use rust_decimal_macros::*;
+use simple_money::*;
+use crate::TaxError;
+use super::{amount_over, FederalIncomeTax, FilingStatus, UsIncome};
+
+/// AMT exemption, the income at which it starts phasing out, and where the
+/// 28% rate begins.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AmtParameters {
+    pub exemption: Money,
+    pub phase_out_threshold: Money,
+    pub twenty_eight_percent_threshold: Money,
+}
+
+impl AmtParameters {
+    pub fn for_year(year: u32, filing_status: FilingStatus) -> Result<AmtParameters, TaxError> {
+        use FilingStatus::*;
+
+        let (exemption, phase_out_threshold, twenty_eight_percent_threshold) = match (year, filing_status) {
+            (2022, Single) | (2022, HeadOfHousehold) => (usd_money!(75_900), usd_money!(539_900), usd_money!(206_100)),
+            (2022, MarriedFilingJointly) => (usd_money!(118_100), usd_money!(1_079_800), usd_money!(206_100)),
+            (2022, MarriedFilingSeparately) => (usd_money!(59_050), usd_money!(539_900), usd_money!(103_050)),
+            (2023, Single) | (2023, HeadOfHousehold) => (usd_money!(81_300), usd_money!(578_150), usd_money!(220_700)),
+            (2023, MarriedFilingJointly) => (usd_money!(126_500), usd_money!(1_156_300), usd_money!(220_700)),
+            (2023, MarriedFilingSeparately) => (usd_money!(63_250), usd_money!(578_150), usd_money!(110_350)),
+            (2024, Single) | (2024, HeadOfHousehold) => (usd_money!(85_700), usd_money!(609_350), usd_money!(232_600)),
+            (2024, MarriedFilingJointly) => (usd_money!(133_300), usd_money!(1_218_700), usd_money!(232_600)),
+            (2024, MarriedFilingSeparately) => (usd_money!(66_650), usd_money!(609_350), usd_money!(116_300)),
+            _ => return Err(TaxError::UnsupportedYear(year)),
+        };
+
+        Ok(AmtParameters { exemption, phase_out_threshold, twenty_eight_percent_threshold })
+    }
+
+    /// The exemption shrinks by 25 cents for each dollar of AMTI over the
+    /// phase-out threshold.
+    pub fn exemption_for(&self, alternative_minimum_taxable_income: Money) -> Money {
+        let reduction = amount_over(alternative_minimum_taxable_income, self.phase_out_threshold) * dec!(0.25);
+        amount_over(self.exemption, reduction)
+    }
+
+    fn rate_tax(&self, income: Money) -> Money {
+        let at_twenty_six = income.min(self.twenty_eight_percent_threshold);
+        at_twenty_six * dec!(0.26) + amount_over(income, self.twenty_eight_percent_threshold) * dec!(0.28)
+    }
+}
+
+/// Items added back to regular taxable income to get AMTI. Exclusion items
+/// are permanent differences; deferral items, like the ISO bargain element,
+/// reverse later and so earn a minimum tax credit.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AmtAdjustments {
+    pub incentive_stock_options: Money,
+    pub other_deferral_items: Money,
+    /// State and local taxes claimed as an itemized deduction.
+    pub state_and_local_taxes: Money,
+    pub other_exclusion_items: Money,
+}
+
+impl AmtAdjustments {
+    pub fn none() -> AmtAdjustments {
+        AmtAdjustments {
+            incentive_stock_options: usd_money!(0),
+            other_deferral_items: usd_money!(0),
+            state_and_local_taxes: usd_money!(0),
+            other_exclusion_items: usd_money!(0),
+        }
+    }
+
+    fn deferral_items(&self) -> Money {
+        self.incentive_stock_options + self.other_deferral_items
+    }
+
+    fn exclusion_items(&self) -> Money {
+        self.state_and_local_taxes + self.other_exclusion_items
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AmtResult {
+    pub alternative_minimum_taxable_income: Money,
+    pub exemption: Money,
+    pub tentative_minimum_tax: Money,
+    pub regular_tax: Money,
+    pub alternative_minimum_tax: Money,
+    /// Minimum tax credit created this year by deferral items.
+    pub credit_generated: Money,
+    /// Prior years' credit used against this year's regular tax.
+    pub credit_used: Money,
+    /// Credit left to carry into the next year.
+    pub credit_carryforward: Money,
+}
+
+impl AmtResult {
+    /// Regular tax plus AMT, less any minimum tax credit used.
+    pub fn total_tax(&self) -> Money {
+        self.regular_tax + self.alternative_minimum_tax - self.credit_used
+    }
+}
+
+impl FederalIncomeTax {
+    fn tentative_minimum_tax(
+        &self,
+        parameters: &AmtParameters,
+        alternative_minimum_taxable_income: Money,
+        preferential_income: Money,
+    ) -> (Money, Money) {
+        let exemption = parameters.exemption_for(alternative_minimum_taxable_income);
+        let taxable_excess = amount_over(alternative_minimum_taxable_income, exemption);
+        let worksheet = self.stack_preferential_income(
+            taxable_excess,
+            preferential_income,
+            |income| parameters.rate_tax(income),
+        );
+
+        (exemption, worksheet.tax)
+    }
+
+    /// Calculates Form 6251 for the year alongside the regular tax, and applies
+    /// `credit_carryforward` from earlier AMT years against any excess of
+    /// regular tax over tentative minimum tax (Form 8801).
+    pub fn alternative_minimum_tax(
+        &self,
+        income: &UsIncome,
+        adjustments: &AmtAdjustments,
+        credit_carryforward: Money,
+    ) -> Result<AmtResult, TaxError> {
+        income.validate()?;
+        let parameters = AmtParameters::for_year(self.year, self.filing_status)?;
+
+        let taxable_income = self.taxable_income(income.adjusted_gross_income(), income.itemized_deductions);
+        let preferential_income = income.preferential_income();
+        let regular_tax = self.capital_gains_worksheet(taxable_income, preferential_income).tax;
+
+        // The standard deduction is not allowed for the AMT.
+        let standard_deduction_addback = match income.itemized_deductions {
+            Some(itemized) if itemized >= self.standard_deduction() => usd_money!(0),
+            _ => self.standard_deduction().min(income.adjusted_gross_income()),
+        };
+        let exclusion_only_income = taxable_income + standard_deduction_addback + adjustments.exclusion_items();
+        let alternative_minimum_taxable_income = exclusion_only_income + adjustments.deferral_items();
+
+        let (exemption, tentative_minimum_tax) =
+            self.tentative_minimum_tax(&parameters, alternative_minimum_taxable_income, preferential_income);
+        let alternative_minimum_tax = amount_over(tentative_minimum_tax, regular_tax);
+
+        let (_, exclusion_only_tentative_tax) =
+            self.tentative_minimum_tax(&parameters, exclusion_only_income, preferential_income);
+        let credit_generated = amount_over(
+            alternative_minimum_tax,
+            amount_over(exclusion_only_tentative_tax, regular_tax),
+        );
+        let credit_used = credit_carryforward.min(amount_over(regular_tax, tentative_minimum_tax));
+
+        Ok(AmtResult {
+            alternative_minimum_taxable_income,
+            exemption,
+            tentative_minimum_tax,
+            regular_tax,
+            alternative_minimum_tax,
+            credit_generated,
+            credit_used,
+            credit_carryforward: credit_carryforward - credit_used + credit_generated,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn iso_exercise_triggers_amt_and_credit() {
+        let single = FederalIncomeTax::new(2024, FilingStatus::Single).unwrap();
+        let income = UsIncome::from_wages(usd_money!(200_000));
+        let adjustments = AmtAdjustments {
+            incentive_stock_options: usd_money!(300_000),
+            ..AmtAdjustments::none()
+        };
+
+        let result = single.alternative_minimum_tax(&income, &adjustments, usd_money!(0)).unwrap();
+        assert_eq!(result.alternative_minimum_taxable_income, usd_money!(500_000));
+        assert_eq!(result.exemption, usd_money!(85_700));
+        assert_eq!(result.tentative_minimum_tax, usd_money!(111_352));
+        assert_eq!(result.regular_tax, usd_money!(37_538.5));
+        assert_eq!(result.alternative_minimum_tax, usd_money!(73_813.5));
+        assert_eq!(result.credit_generated, usd_money!(73_813.5));
+        assert_eq!(result.credit_carryforward, usd_money!(73_813.5));
+    }
+
+    #[test]
+    fn exemption_phase_out() {
+        let parameters = AmtParameters::for_year(2024, FilingStatus::MarriedFilingJointly).unwrap();
+        assert_eq!(parameters.exemption_for(usd_money!(1_000_000)), usd_money!(133_300));
+        assert_eq!(parameters.exemption_for(usd_money!(1_318_700)), usd_money!(108_300));
+        assert_eq!(parameters.exemption_for(usd_money!(2_000_000)), usd_money!(0));
+    }
+
+    #[test]
+    fn credit_used_in_a_later_year() {
+        let single = FederalIncomeTax::new(2024, FilingStatus::Single).unwrap();
+        let income = UsIncome::from_wages(usd_money!(400_000));
+
+        let result = single.alternative_minimum_tax(&income, &AmtAdjustments::none(), usd_money!(73_813.5)).unwrap();
+        assert_eq!(result.alternative_minimum_tax, usd_money!(0));
+        assert_eq!(result.credit_generated, usd_money!(0));
+        assert_eq!(result.credit_used, usd_money!(21_912.75));
+        assert_eq!(result.credit_carryforward, usd_money!(73_813.5) - result.credit_used);
+    }
+}
@@ -0,0 +1,156 @@
+use rust_decimal_macros::*;
+use simple_money::*;
+use crate::TaxError;
+use super::{amount_over, FilingStatus, UsIncome};
+
+/// Net capital loss deductible against other income each year.
+fn annual_loss_limit(filing_status: FilingStatus) -> Money {
+    match filing_status {
+        FilingStatus::MarriedFilingSeparately => usd_money!(1_500),
+        _ => usd_money!(3_000),
+    }
+}
+
+/// One year's line in the carryover ledger.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CapitalLossYear {
+    pub year: u32,
+    /// Net capital loss deducted against ordinary income.
+    pub loss_deduction: Money,
+    pub short_term_carryover: Money,
+    pub long_term_carryover: Money,
+}
+
+/// Capital losses carried forward from year to year. They never expire and
+/// keep their short or long-term character.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CapitalLossCarryover {
+    pub short_term: Money,
+    pub long_term: Money,
+    ledger: Vec<CapitalLossYear>,
+}
+
+impl Default for CapitalLossCarryover {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CapitalLossCarryover {
+    pub fn new() -> CapitalLossCarryover {
+        CapitalLossCarryover {
+            short_term: usd_money!(0),
+            long_term: usd_money!(0),
+            ledger: vec![],
+        }
+    }
+
+    pub fn ledger(&self) -> &[CapitalLossYear] {
+        &self.ledger
+    }
+
+    /// Nets a year's gains against the carryover and rewrites them in
+    /// `income`, leaving at most the annual loss deduction as a negative
+    /// short-term gain. The rest carries to next year.
+    ///
+    /// The order is the one Schedule D sets: each carryover first meets gains
+    /// of its own term, then the other term's gains, and short-term losses
+    /// are used first against ordinary income.
+    pub fn apply(
+        &mut self,
+        year: u32,
+        income: &mut UsIncome,
+        filing_status: FilingStatus,
+    ) -> Result<CapitalLossYear, TaxError> {
+        income.validate()?;
+        let zero = usd_money!(0);
+        let net_short_term = income.short_term_capital_gains - self.short_term;
+        let net_long_term = income.long_term_capital_gains - self.long_term;
+        let net = net_short_term + net_long_term;
+
+        let loss_deduction = if net >= zero {
+            income.short_term_capital_gains = net_short_term;
+            income.long_term_capital_gains = net_long_term;
+            zero
+        }else{
+            let loss_deduction = (zero - net).min(annual_loss_limit(filing_status));
+            income.short_term_capital_gains = zero - loss_deduction;
+            income.long_term_capital_gains = zero;
+            loss_deduction
+        };
+
+        // Capital Loss Carryover Worksheet.
+        let short_term_loss = amount_over(zero - net_short_term, zero);
+        let long_term_loss = amount_over(zero - net_long_term, zero);
+        self.short_term = amount_over(short_term_loss, loss_deduction + amount_over(net_long_term, zero));
+        self.long_term = amount_over(
+            long_term_loss,
+            amount_over(net_short_term, zero) + amount_over(loss_deduction, short_term_loss),
+        );
+
+        let entry = CapitalLossYear {
+            year,
+            loss_deduction,
+            short_term_carryover: self.short_term,
+            long_term_carryover: self.long_term,
+        };
+        self.ledger.push(entry);
+        Ok(entry)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn three_thousand_a_year_then_carry_forward() {
+        let mut carryover = CapitalLossCarryover::new();
+        let mut income = UsIncome {
+            short_term_capital_gains: usd_money!(-2_000),
+            long_term_capital_gains: usd_money!(-8_000),
+            ..UsIncome::from_wages(usd_money!(100_000))
+        };
+
+        let first = carryover.apply(2023, &mut income, FilingStatus::Single).unwrap();
+        assert_eq!(first.loss_deduction, usd_money!(3_000));
+        assert_eq!(first.short_term_carryover, usd_money!(0));
+        assert_eq!(first.long_term_carryover, usd_money!(7_000));
+        assert_eq!(income.adjusted_gross_income(), usd_money!(97_000));
+
+        let mut next_year = UsIncome {
+            short_term_capital_gains: usd_money!(1_000),
+            long_term_capital_gains: usd_money!(2_500),
+            ..UsIncome::from_wages(usd_money!(100_000))
+        };
+        let second = carryover.apply(2024, &mut next_year, FilingStatus::Single).unwrap();
+        assert_eq!(second.loss_deduction, usd_money!(3_000));
+        assert_eq!(second.long_term_carryover, usd_money!(500));
+        assert_eq!(next_year.adjusted_gross_income(), usd_money!(97_000));
+        assert_eq!(carryover.ledger().len(), 2);
+    }
+
+    #[test]
+    fn carryover_absorbed_by_gains() {
+        let mut carryover = CapitalLossCarryover { short_term: usd_money!(4_000), ..CapitalLossCarryover::new() };
+        let mut income = UsIncome {
+            long_term_capital_gains: usd_money!(10_000),
+            ..UsIncome::from_wages(usd_money!(50_000))
+        };
+
+        let result = carryover.apply(2024, &mut income, FilingStatus::MarriedFilingJointly).unwrap();
+        assert_eq!(result.loss_deduction, usd_money!(0));
+        assert_eq!(carryover.short_term, usd_money!(0));
+        assert_eq!(income.net_capital_gain(), usd_money!(6_000));
+    }
+
+    #[test]
+    fn married_filing_separately_limit() {
+        let mut carryover = CapitalLossCarryover { short_term: usd_money!(5_000), ..CapitalLossCarryover::new() };
+        let mut income = UsIncome::from_wages(usd_money!(50_000));
+
+        let result = carryover.apply(2024, &mut income, FilingStatus::MarriedFilingSeparately).unwrap();
+        assert_eq!(result.loss_deduction, usd_money!(1_500));
+        assert_eq!(result.short_term_carryover, usd_money!(3_500));
+    }
+}
@@ -0,0 +1,278 @@
+use chrono::{Months, NaiveDate};
+use rust_decimal::prelude::*;
+use rust_decimal_macros::*;
+use simple_money::*;
+use crate::TaxError;
+use super::{amount_over, is_long_term, UsIncome};
+
+/// How stock compensation shows up on a US return.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum EquityIncomeCategory {
+    /// Reported on the W-2 and taxed as ordinary income.
+    CompensationIncome,
+    ShortTermCapitalGain,
+    LongTermCapitalGain,
+    /// Income counted only for the Alternative Minimum Tax. Negative when a
+    /// sale reverses the adjustment made at exercise.
+    AmtAdjustment,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EquityAward {
+    NonQualifiedStockOption,
+    IncentiveStockOption {
+        grant_date: NaiveDate,
+    },
+    /// A purchase under a §423 plan. `discount_rate` is the plan's discount
+    /// off the offering-date price, usually 15%.
+    EmployeeStockPurchase {
+        offering_date: NaiveDate,
+        fair_market_value_at_offering: Money,
+        discount_rate: Decimal,
+    },
+}
+
+/// Shares acquired by exercising an option or buying through an ESPP. Prices
+/// are per share.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EquityAcquisition {
+    pub award: EquityAward,
+    pub acquisition_date: NaiveDate,
+    pub shares: Decimal,
+    pub price_paid: Money,
+    pub fair_market_value_at_acquisition: Money,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EquitySale {
+    pub sale_date: NaiveDate,
+    pub shares: Decimal,
+    pub sale_price: Money,
+}
+
+/// The itemized income a single grant event creates.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EquityTaxEffect {
+    pub items: Vec<(EquityIncomeCategory, Money)>,
+}
+
+impl EquityTaxEffect {
+    pub fn amount(&self, category: EquityIncomeCategory) -> Money {
+        self.items
+            .iter()
+            .filter(|(candidate, _)| *candidate == category)
+            .fold(usd_money!(0), |acc, (_, money)| acc + *money)
+    }
+
+    /// Adds the regular-tax income to a year's `UsIncome`. The AMT adjustment
+    /// is not part of regular income and has to be tracked separately.
+    pub fn apply_to(&self, income: &mut UsIncome) {
+        income.wages = income.wages + self.amount(EquityIncomeCategory::CompensationIncome);
+        income.short_term_capital_gains =
+            income.short_term_capital_gains + self.amount(EquityIncomeCategory::ShortTermCapitalGain);
+        income.long_term_capital_gains =
+            income.long_term_capital_gains + self.amount(EquityIncomeCategory::LongTermCapitalGain);
+    }
+}
+
+fn capital_gain_category(acquisition_date: NaiveDate, sale_date: NaiveDate) -> EquityIncomeCategory {
+    if is_long_term(acquisition_date, sale_date) {
+        EquityIncomeCategory::LongTermCapitalGain
+    }else{
+        EquityIncomeCategory::ShortTermCapitalGain
+    }
+}
+
+impl EquityAcquisition {
+    fn validate(&self) -> Result<(), TaxError> {
+        let offering_currency = match self.award {
+            EquityAward::EmployeeStockPurchase { fair_market_value_at_offering, .. } => {
+                fair_market_value_at_offering.currency
+            }
+            _ => Currency::USD,
+        };
+        if self.price_paid.currency != Currency::USD
+            || self.fair_market_value_at_acquisition.currency != Currency::USD
+            || offering_currency != Currency::USD
+        {
+            Err(TaxError::MismatchedCurrencies)
+        }else{
+            Ok(())
+        }
+    }
+
+    fn spread(&self) -> Money {
+        amount_over(self.fair_market_value_at_acquisition, self.price_paid)
+    }
+
+    /// Tax effect of exercising the options or purchasing the ESPP shares.
+    /// NSO spread is wages; the ISO spread is an AMT adjustment; an ESPP
+    /// purchase has no immediate effect.
+    pub fn acquisition_effect(&self) -> Result<EquityTaxEffect, TaxError> {
+        self.validate()?;
+        let spread = self.spread() * self.shares;
+        let items = match self.award {
+            EquityAward::NonQualifiedStockOption => vec![(EquityIncomeCategory::CompensationIncome, spread)],
+            EquityAward::IncentiveStockOption { .. } => vec![(EquityIncomeCategory::AmtAdjustment, spread)],
+            EquityAward::EmployeeStockPurchase { .. } => vec![],
+        };
+
+        Ok(EquityTaxEffect { items })
+    }
+
+    /// ISO shares held more than two years from grant and one year from
+    /// exercise, or ESPP shares held two years from the offering date and one
+    /// year from purchase.
+    pub fn is_qualifying_disposition(&self, sale_date: NaiveDate) -> bool {
+        let held_one_year = is_long_term(self.acquisition_date, sale_date);
+        match self.award {
+            EquityAward::NonQualifiedStockOption => false,
+            EquityAward::IncentiveStockOption { grant_date } => {
+                held_one_year && sale_date > grant_date + Months::new(24)
+            }
+            EquityAward::EmployeeStockPurchase { offering_date, .. } => {
+                held_one_year && sale_date > offering_date + Months::new(24)
+            }
+        }
+    }
+
+    /// Tax effect of selling `sale.shares` of these shares.
+    pub fn sale_effect(&self, sale: &EquitySale) -> Result<EquityTaxEffect, TaxError> {
+        self.validate()?;
+        if sale.sale_price.currency != Currency::USD {
+            return Err(TaxError::MismatchedCurrencies);
+        }
+
+        let shares = sale.shares;
+        let proceeds = sale.sale_price * shares;
+        let capital = capital_gain_category(self.acquisition_date, sale.sale_date);
+        let qualifying = self.is_qualifying_disposition(sale.sale_date);
+
+        let items = match self.award {
+            EquityAward::NonQualifiedStockOption => {
+                vec![(capital, proceeds - self.fair_market_value_at_acquisition * shares)]
+            }
+            EquityAward::IncentiveStockOption { .. } => {
+                // AMT basis includes the spread already taxed for AMT at
+                // exercise, so AMT income on the sale is lower by that spread.
+                let amt_reversal = usd_money!(0) - self.spread() * shares;
+                if qualifying {
+                    vec![
+                        (EquityIncomeCategory::LongTermCapitalGain, proceeds - self.price_paid * shares),
+                        (EquityIncomeCategory::AmtAdjustment, amt_reversal),
+                    ]
+                }else{
+                    let compensation = amount_over(sale.sale_price, self.price_paid)
+                        .min(self.spread()) * shares;
+                    vec![
+                        (EquityIncomeCategory::CompensationIncome, compensation),
+                        (capital, proceeds - self.price_paid * shares - compensation),
+                        (EquityIncomeCategory::AmtAdjustment, amt_reversal),
+                    ]
+                }
+            }
+            EquityAward::EmployeeStockPurchase { fair_market_value_at_offering, discount_rate, .. } => {
+                if qualifying {
+                    let compensation = amount_over(sale.sale_price, self.price_paid)
+                        .min(fair_market_value_at_offering * discount_rate) * shares;
+                    vec![
+                        (EquityIncomeCategory::CompensationIncome, compensation),
+                        (
+                            EquityIncomeCategory::LongTermCapitalGain,
+                            proceeds - self.price_paid * shares - compensation,
+                        ),
+                    ]
+                }else{
+                    let compensation = self.spread() * shares;
+                    vec![
+                        (EquityIncomeCategory::CompensationIncome, compensation),
+                        (capital, proceeds - self.price_paid * shares - compensation),
+                    ]
+                }
+            }
+        };
+
+        Ok(EquityTaxEffect { items })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    fn iso_exercise() -> EquityAcquisition {
+        EquityAcquisition {
+            award: EquityAward::IncentiveStockOption { grant_date: date(2021, 3, 1) },
+            acquisition_date: date(2023, 5, 1),
+            shares: dec!(1_000),
+            price_paid: usd_money!(5),
+            fair_market_value_at_acquisition: usd_money!(40),
+        }
+    }
+
+    #[test]
+    fn nso_spread_is_wages() {
+        let exercise = EquityAcquisition {
+            award: EquityAward::NonQualifiedStockOption,
+            ..iso_exercise()
+        };
+        let effect = exercise.acquisition_effect().unwrap();
+        assert_eq!(effect.amount(EquityIncomeCategory::CompensationIncome), usd_money!(35_000));
+
+        let sale = EquitySale { sale_date: date(2023, 9, 1), shares: dec!(1_000), sale_price: usd_money!(45) };
+        let mut income = UsIncome::from_wages(usd_money!(100_000));
+        effect.apply_to(&mut income);
+        exercise.sale_effect(&sale).unwrap().apply_to(&mut income);
+        assert_eq!(income.wages, usd_money!(135_000));
+        assert_eq!(income.short_term_capital_gains, usd_money!(5_000));
+    }
+
+    #[test]
+    fn iso_qualifying_and_disqualifying_dispositions() {
+        let exercise = iso_exercise();
+        let effect = exercise.acquisition_effect().unwrap();
+        assert_eq!(effect.amount(EquityIncomeCategory::AmtAdjustment), usd_money!(35_000));
+        assert_eq!(effect.amount(EquityIncomeCategory::CompensationIncome), usd_money!(0));
+
+        let qualifying = EquitySale { sale_date: date(2024, 6, 1), shares: dec!(1_000), sale_price: usd_money!(60) };
+        let effect = exercise.sale_effect(&qualifying).unwrap();
+        assert_eq!(effect.amount(EquityIncomeCategory::LongTermCapitalGain), usd_money!(55_000));
+        assert_eq!(effect.amount(EquityIncomeCategory::AmtAdjustment), usd_money!(-35_000));
+
+        let disqualifying = EquitySale { sale_date: date(2023, 11, 1), shares: dec!(1_000), sale_price: usd_money!(30) };
+        let effect = exercise.sale_effect(&disqualifying).unwrap();
+        assert_eq!(effect.amount(EquityIncomeCategory::CompensationIncome), usd_money!(25_000));
+        assert_eq!(effect.amount(EquityIncomeCategory::ShortTermCapitalGain), usd_money!(0));
+        assert_eq!(effect.amount(EquityIncomeCategory::AmtAdjustment), usd_money!(-35_000));
+    }
+
+    #[test]
+    fn espp_discount_on_qualifying_disposition() {
+        let purchase = EquityAcquisition {
+            award: EquityAward::EmployeeStockPurchase {
+                offering_date: date(2022, 1, 1),
+                fair_market_value_at_offering: usd_money!(20),
+                discount_rate: dec!(0.15),
+            },
+            acquisition_date: date(2022, 6, 30),
+            shares: dec!(100),
+            price_paid: usd_money!(17),
+            fair_market_value_at_acquisition: usd_money!(30),
+        };
+        assert_eq!(purchase.acquisition_effect().unwrap().items, vec![]);
+
+        let qualifying = EquitySale { sale_date: date(2024, 3, 1), shares: dec!(100), sale_price: usd_money!(50) };
+        let effect = purchase.sale_effect(&qualifying).unwrap();
+        assert_eq!(effect.amount(EquityIncomeCategory::CompensationIncome), usd_money!(300));
+        assert_eq!(effect.amount(EquityIncomeCategory::LongTermCapitalGain), usd_money!(3_000));
+
+        let disqualifying = EquitySale { sale_date: date(2023, 3, 1), shares: dec!(100), sale_price: usd_money!(50) };
+        let effect = purchase.sale_effect(&disqualifying).unwrap();
+        assert_eq!(effect.amount(EquityIncomeCategory::CompensationIncome), usd_money!(1_300));
+        assert_eq!(effect.amount(EquityIncomeCategory::ShortTermCapitalGain), usd_money!(2_000));
+    }
+}
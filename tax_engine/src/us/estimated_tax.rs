@@ -0,0 +1,335 @@
+use chrono::{Datelike, Duration, NaiveDate};
+use rust_decimal::prelude::*;
+use rust_decimal_macros::*;
+use simple_money::*;
+use crate::{days_in_year, next_business_day, round_cents, TaxError};
+use super::{FederalIncomeTax, FilingStatus, IncomeTaxJurisdiction, UsIncome};
+
+/// No underpayment penalty applies when the tax left after withholding is
+/// below this amount.
+const PENALTY_THRESHOLD: Money = Money { amount: dec!(1_000), currency: Currency::USD };
+
+/// Share of the current year's tax the safe harbor asks for.
+const CURRENT_YEAR_SAFE_HARBOR: Decimal = dec!(0.90);
+
+/// Above this prior-year AGI the prior-year safe harbor is 110% of last
+/// year's tax instead of 100%.
+fn high_income_threshold(filing_status: FilingStatus) -> Money {
+    match filing_status {
+        FilingStatus::MarriedFilingSeparately => usd_money!(75_000),
+        _ => usd_money!(150_000),
+    }
+}
+
+/// The IRS underpayment rate for individuals, set each quarter. Form 2210
+/// applies it as simple interest.
+pub fn underpayment_interest_rate(date: NaiveDate) -> Result<Decimal, TaxError> {
+    let quarter = (date.month() - 1) / 3 + 1;
+    let rate = match (date.year(), quarter) {
+        (2023, 4) => dec!(0.08),
+        (2023, _) => dec!(0.07),
+        (2024, _) => dec!(0.08),
+        (2025, _) => dec!(0.07),
+        (year, _) => return Err(TaxError::UnsupportedYear(year as u32)),
+    };
+
+    Ok(rate)
+}
+
+/// How each installment's required amount is worked out.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum EstimatedTaxMethod {
+    /// A quarter of the required annual payment each time.
+    Regular,
+    /// Form 2210 Schedule AI: each installment only covers income received
+    /// by the end of its period, annualized. Helps when income such as a
+    /// stock sale arrives late in the year.
+    Annualized,
+}
+
+/// The previous year's return, for the prior-year safe harbor.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PriorYearTax {
+    pub tax: Money,
+    pub adjusted_gross_income: Money,
+    /// The safe harbor is only available when the prior year was a full
+    /// 12-month tax year.
+    pub full_year: bool,
+}
+
+/// Income received during the year and any federal tax withheld from it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EstimatedTaxEvent {
+    pub date: NaiveDate,
+    pub income: UsIncome,
+    pub withheld: Money,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EstimatedTaxPayment {
+    pub due_date: NaiveDate,
+    pub amount: Money,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UsEstimatedTaxPlan {
+    pub current_year_tax: Money,
+    pub withholding: Money,
+    /// The smaller of the two safe harbors.
+    pub required_annual_payment: Money,
+    /// Estimated payments on top of withholding. Empty when no penalty can
+    /// apply.
+    pub payments: Vec<EstimatedTaxPayment>,
+}
+
+/// Plans federal estimated tax payments from the year's income events and
+/// works out the Form 2210 penalty on the payments actually made.
+/// Withholding is treated as paid evenly on the four due dates.
+#[derive(Clone, Debug)]
+pub struct UsEstimatedTaxPlanner {
+    year: u32,
+    income_tax: FederalIncomeTax,
+    prior_year: Option<PriorYearTax>,
+    events: Vec<EstimatedTaxEvent>,
+}
+
+impl UsEstimatedTaxPlanner {
+    pub fn new(year: u32, filing_status: FilingStatus, prior_year: Option<PriorYearTax>) -> Result<UsEstimatedTaxPlanner, TaxError> {
+        if prior_year.is_some_and(|prior| prior.tax.currency != Currency::USD || prior.adjusted_gross_income.currency != Currency::USD) {
+            return Err(TaxError::MismatchedCurrencies);
+        }
+        Ok(UsEstimatedTaxPlanner {
+            year,
+            income_tax: FederalIncomeTax::new(year, filing_status)?,
+            prior_year,
+            events: vec![],
+        })
+    }
+
+    pub fn record(&mut self, event: EstimatedTaxEvent) -> Result<(), TaxError> {
+        event.income.validate()?;
+        if event.withheld.currency != Currency::USD {
+            return Err(TaxError::MismatchedCurrencies);
+        }
+        self.events.push(event);
+        Ok(())
+    }
+
+    /// April 15, June 15 and September 15, then January 15 of the next year,
+    /// moved off weekends.
+    pub fn due_dates(&self) -> Vec<NaiveDate> {
+        let year = self.year as i32;
+        [(year, 4), (year, 6), (year, 9), (year + 1, 1)]
+            .iter()
+            .map(|(year, month)| next_business_day(NaiveDate::from_ymd_opt(*year, *month, 15).unwrap()))
+            .collect()
+    }
+
+    /// Income tax and Net Investment Income Tax on the income recorded so far.
+    pub fn current_year_tax(&self) -> Result<Money, TaxError> {
+        Ok(self.income_tax.calculate(&self.income_through(None, dec!(1)))?.total())
+    }
+
+    pub fn withholding(&self) -> Money {
+        self.events.iter().fold(usd_money!(0), |sum, event| sum + event.withheld)
+    }
+
+    /// The smaller of 90% of this year's tax and 100% of last year's, or
+    /// 110% when last year's AGI was above the high-income threshold.
+    pub fn required_annual_payment(&self) -> Result<Money, TaxError> {
+        let current_year = self.current_year_tax()? * CURRENT_YEAR_SAFE_HARBOR;
+        let prior_year = match self.prior_year {
+            Some(prior) if prior.full_year => {
+                let rate = if prior.adjusted_gross_income > high_income_threshold(self.income_tax.filing_status) {
+                    dec!(1.10)
+                }else{
+                    dec!(1)
+                };
+                prior.tax * rate
+            }
+            _ => return Ok(current_year),
+        };
+
+        Ok(current_year.min(prior_year))
+    }
+
+    /// The amount each installment must bring the year's payments up to,
+    /// counting withholding.
+    pub fn required_installments(&self, method: EstimatedTaxMethod) -> Result<Vec<Money>, TaxError> {
+        let zero = usd_money!(0);
+        let regular = self.required_annual_payment()? * dec!(0.25);
+        let year = self.year as i32;
+        let periods = [
+            (NaiveDate::from_ymd_opt(year, 3, 31).unwrap(), dec!(4), dec!(0.225)),
+            (NaiveDate::from_ymd_opt(year, 5, 31).unwrap(), dec!(2.4), dec!(0.45)),
+            (NaiveDate::from_ymd_opt(year, 8, 31).unwrap(), dec!(1.5), dec!(0.675)),
+            (NaiveDate::from_ymd_opt(year, 12, 31).unwrap(), dec!(1), dec!(0.90)),
+        ];
+
+        let mut required = vec![];
+        let mut required_so_far = zero;
+        for (index, (period_end, factor, applicable_percentage)) in periods.iter().enumerate() {
+            let cumulative_regular = regular * Decimal::from(index as u32 + 1);
+            let cumulative = match method {
+                EstimatedTaxMethod::Regular => cumulative_regular,
+                EstimatedTaxMethod::Annualized => {
+                    let annualized = self.income_through(Some(*period_end), *factor);
+                    let annualized_tax = self.income_tax.calculate(&annualized)?.total();
+                    (annualized_tax * *applicable_percentage).min(cumulative_regular)
+                }
+            };
+            let installment = (cumulative - required_so_far).max(zero);
+            required_so_far = required_so_far + installment;
+            required.push(installment);
+        }
+
+        Ok(required)
+    }
+
+    /// Payments to make on each due date so that, with withholding, every
+    /// installment is covered.
+    pub fn plan(&self, method: EstimatedTaxMethod) -> Result<UsEstimatedTaxPlan, TaxError> {
+        let zero = usd_money!(0);
+        let current_year_tax = self.current_year_tax()?;
+        let withholding = self.withholding();
+        let required_annual_payment = self.required_annual_payment()?;
+        let mut payments = vec![];
+        if current_year_tax - withholding >= PENALTY_THRESHOLD {
+            let withholding_share = withholding * dec!(0.25);
+            let mut required_so_far = zero;
+            let mut paid_so_far = zero;
+            for (due_date, installment) in self.due_dates().into_iter().zip(self.required_installments(method)?) {
+                required_so_far = required_so_far + installment;
+                paid_so_far = paid_so_far + withholding_share;
+                let amount = (required_so_far - paid_so_far).max(zero);
+                paid_so_far = paid_so_far + amount;
+                payments.push(EstimatedTaxPayment { due_date, amount: round_cents(amount) });
+            }
+        }
+
+        Ok(UsEstimatedTaxPlan { current_year_tax, withholding, required_annual_payment, payments })
+    }
+
+    /// The Form 2210 penalty on `payments_made`. Each day an installment is
+    /// underpaid, up to April 15 of the next year, costs the underpayment
+    /// rate for that day. Payments go to the earliest underpayment first.
+    pub fn penalty(&self, payments_made: &[(NaiveDate, Money)], method: EstimatedTaxMethod) -> Result<Money, TaxError> {
+        let zero = usd_money!(0);
+        if payments_made.iter().any(|(_, amount)| amount.currency != Currency::USD) {
+            return Err(TaxError::MismatchedCurrencies);
+        }
+        let withholding = self.withholding();
+        if self.current_year_tax()? - withholding < PENALTY_THRESHOLD {
+            return Ok(zero);
+        }
+
+        let due_dates = self.due_dates();
+        let required: Vec<(NaiveDate, Money)> = due_dates
+            .iter()
+            .copied()
+            .zip(self.required_installments(method)?.into_iter().map(|installment| installment - withholding * dec!(0.25)))
+            .collect();
+        let end = NaiveDate::from_ymd_opt(self.year as i32 + 1, 4, 15).unwrap();
+        let mut date = due_dates[0];
+        let mut balance = payments_made
+            .iter()
+            .filter(|(paid, _)| *paid < date)
+            .fold(zero, |sum, (_, amount)| sum + *amount);
+        let mut penalty = zero;
+        while date < end {
+            balance = balance
+                + payments_made.iter().filter(|(paid, _)| *paid == date).fold(zero, |sum, (_, amount)| sum + *amount)
+                - required.iter().filter(|(due, _)| *due == date).fold(zero, |sum, (_, amount)| sum + *amount);
+            if balance < zero {
+                penalty = penalty + (zero - balance) * (underpayment_interest_rate(date)? / days_in_year(date));
+            }
+            date += Duration::days(1);
+        }
+
+        Ok(round_cents(penalty))
+    }
+
+    /// Income from events up to `through`, multiplied by `factor`.
+    fn income_through(&self, through: Option<NaiveDate>, factor: Decimal) -> UsIncome {
+        let mut total = UsIncome::from_wages(usd_money!(0));
+        for event in self.events.iter().filter(|event| through.is_none_or(|through| event.date <= through)) {
+            let income = event.income;
+            total.wages = total.wages + income.wages * factor;
+            total.other_ordinary_income = total.other_ordinary_income + income.other_ordinary_income * factor;
+            total.qualified_dividends = total.qualified_dividends + income.qualified_dividends * factor;
+            total.short_term_capital_gains = total.short_term_capital_gains + income.short_term_capital_gains * factor;
+            total.long_term_capital_gains = total.long_term_capital_gains + income.long_term_capital_gains * factor;
+            total.itemized_deductions = match (total.itemized_deductions, income.itemized_deductions) {
+                (Some(sum), Some(itemized)) => Some(sum + itemized * factor),
+                (sum, itemized) => sum.or(itemized.map(|itemized| itemized * factor)),
+            };
+        }
+        total
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    fn stock_sale(date: NaiveDate) -> EstimatedTaxEvent {
+        let income = UsIncome { short_term_capital_gains: usd_money!(150_000), ..UsIncome::from_wages(usd_money!(0)) };
+        EstimatedTaxEvent { date, income, withheld: usd_money!(0) }
+    }
+
+    fn prior_year(adjusted_gross_income: Money) -> Option<PriorYearTax> {
+        Some(PriorYearTax { tax: usd_money!(20_000), adjusted_gross_income, full_year: true })
+    }
+
+    #[test]
+    fn prior_year_safe_harbors() {
+        let mut planner = UsEstimatedTaxPlanner::new(2024, FilingStatus::Single, prior_year(usd_money!(160_000))).unwrap();
+        planner.record(stock_sale(date(2024, 2, 1))).unwrap();
+        let plan = planner.plan(EstimatedTaxMethod::Regular).unwrap();
+        assert_eq!(plan.current_year_tax, usd_money!(25_538.50));
+        assert_eq!(plan.required_annual_payment, usd_money!(22_000));
+        assert_eq!(
+            plan.payments,
+            vec![
+                EstimatedTaxPayment { due_date: date(2024, 4, 15), amount: usd_money!(5_500) },
+                EstimatedTaxPayment { due_date: date(2024, 6, 17), amount: usd_money!(5_500) },
+                EstimatedTaxPayment { due_date: date(2024, 9, 16), amount: usd_money!(5_500) },
+                EstimatedTaxPayment { due_date: date(2025, 1, 15), amount: usd_money!(5_500) },
+            ]
+        );
+
+        let mut planner = UsEstimatedTaxPlanner::new(2024, FilingStatus::Single, prior_year(usd_money!(140_000))).unwrap();
+        planner.record(stock_sale(date(2024, 2, 1))).unwrap();
+        assert_eq!(planner.required_annual_payment().unwrap(), usd_money!(20_000));
+
+        let mut planner = UsEstimatedTaxPlanner::new(2024, FilingStatus::Single, None).unwrap();
+        planner.record(stock_sale(date(2024, 2, 1))).unwrap();
+        assert_eq!(planner.required_annual_payment().unwrap(), usd_money!(22_984.65));
+    }
+
+    #[test]
+    fn withholding_within_a_thousand_needs_no_payments() {
+        let mut planner = UsEstimatedTaxPlanner::new(2024, FilingStatus::Single, prior_year(usd_money!(160_000))).unwrap();
+        planner
+            .record(EstimatedTaxEvent { withheld: usd_money!(25_000), ..stock_sale(date(2024, 2, 1)) })
+            .unwrap();
+        assert!(planner.plan(EstimatedTaxMethod::Regular).unwrap().payments.is_empty());
+        assert_eq!(planner.penalty(&[], EstimatedTaxMethod::Regular).unwrap(), usd_money!(0));
+    }
+
+    #[test]
+    fn annualized_method_for_late_income() {
+        let mut planner = UsEstimatedTaxPlanner::new(2024, FilingStatus::Single, prior_year(usd_money!(160_000))).unwrap();
+        planner.record(stock_sale(date(2024, 11, 1))).unwrap();
+        let annualized = planner.required_installments(EstimatedTaxMethod::Annualized).unwrap();
+        assert_eq!(annualized, vec![usd_money!(0), usd_money!(0), usd_money!(0), usd_money!(22_000)]);
+
+        let paid_in_january = [(date(2025, 1, 15), usd_money!(22_000))];
+        assert_eq!(planner.penalty(&paid_in_january, EstimatedTaxMethod::Annualized).unwrap(), usd_money!(0));
+        assert_eq!(planner.penalty(&paid_in_january, EstimatedTaxMethod::Regular).unwrap(), usd_money!(724.74));
+    }
+}
@@ -0,0 +1,337 @@
+use rust_decimal::prelude::*;
+use rust_decimal::RoundingStrategy;
+use rust_decimal_macros::*;
+use simple_money::*;
+use crate::{TaxError, TaxSchedule};
+use super::{amount_over, FilingStatus, IncomeTaxJurisdiction, JurisdictionTax, UsIncome, UsTaxComponent};
+
+const FEDERAL_RATES: [Decimal; 7] = [
+    dec!(0.10),
+    dec!(0.12),
+    dec!(0.22),
+    dec!(0.24),
+    dec!(0.32),
+    dec!(0.35),
+    dec!(0.37),
+];
+
+/// Lower thresholds of the 12% through 37% brackets, from the IRS revenue
+/// procedure for each year.
+fn federal_thresholds(year: u32, filing_status: FilingStatus) -> Result<[Decimal; 6], TaxError> {
+    use FilingStatus::*;
+
+    let thresholds = match (year, filing_status) {
+        (2022, Single) => [dec!(10_275), dec!(41_775), dec!(89_075), dec!(170_050), dec!(215_950), dec!(539_900)],
+        (2022, MarriedFilingJointly) => [dec!(20_550), dec!(83_550), dec!(178_150), dec!(340_100), dec!(431_900), dec!(647_850)],
+        (2022, MarriedFilingSeparately) => [dec!(10_275), dec!(41_775), dec!(89_075), dec!(170_050), dec!(215_950), dec!(323_925)],
+        (2022, HeadOfHousehold) => [dec!(14_650), dec!(55_900), dec!(89_050), dec!(170_050), dec!(215_950), dec!(539_900)],
+        (2023, Single) => [dec!(11_000), dec!(44_725), dec!(95_375), dec!(182_100), dec!(231_250), dec!(578_125)],
+        (2023, MarriedFilingJointly) => [dec!(22_000), dec!(89_450), dec!(190_750), dec!(364_200), dec!(462_500), dec!(693_750)],
+        (2023, MarriedFilingSeparately) => [dec!(11_000), dec!(44_725), dec!(95_375), dec!(182_100), dec!(231_250), dec!(346_875)],
+        (2023, HeadOfHousehold) => [dec!(15_700), dec!(59_850), dec!(95_350), dec!(182_100), dec!(231_250), dec!(578_100)],
+        (2024, Single) => [dec!(11_600), dec!(47_150), dec!(100_525), dec!(191_950), dec!(243_725), dec!(609_350)],
+        (2024, MarriedFilingJointly) => [dec!(23_200), dec!(94_300), dec!(201_050), dec!(383_900), dec!(487_450), dec!(731_200)],
+        (2024, MarriedFilingSeparately) => [dec!(11_600), dec!(47_150), dec!(100_525), dec!(191_950), dec!(243_725), dec!(365_600)],
+        (2024, HeadOfHousehold) => [dec!(16_550), dec!(63_100), dec!(100_500), dec!(191_950), dec!(243_700), dec!(609_350)],
+        _ => return Err(TaxError::UnsupportedYear(year)),
+    };
+
+    Ok(thresholds)
+}
+
+/// Top of the 0% and 15% brackets for qualified dividends and long-term
+/// capital gains.
+fn preferential_thresholds(year: u32, filing_status: FilingStatus) -> Result<(Money, Money), TaxError> {
+    use FilingStatus::*;
+
+    let thresholds = match (year, filing_status) {
+        (2022, Single) => (usd_money!(41_675), usd_money!(459_750)),
+        (2022, MarriedFilingJointly) => (usd_money!(83_350), usd_money!(517_200)),
+        (2022, MarriedFilingSeparately) => (usd_money!(41_675), usd_money!(258_600)),
+        (2022, HeadOfHousehold) => (usd_money!(55_800), usd_money!(488_500)),
+        (2023, Single) => (usd_money!(44_625), usd_money!(492_300)),
+        (2023, MarriedFilingJointly) => (usd_money!(89_250), usd_money!(553_850)),
+        (2023, MarriedFilingSeparately) => (usd_money!(44_625), usd_money!(276_900)),
+        (2023, HeadOfHousehold) => (usd_money!(59_750), usd_money!(523_050)),
+        (2024, Single) => (usd_money!(47_025), usd_money!(518_900)),
+        (2024, MarriedFilingJointly) => (usd_money!(94_050), usd_money!(583_750)),
+        (2024, MarriedFilingSeparately) => (usd_money!(47_025), usd_money!(291_850)),
+        (2024, HeadOfHousehold) => (usd_money!(63_000), usd_money!(551_350)),
+        _ => return Err(TaxError::UnsupportedYear(year)),
+    };
+
+    Ok(thresholds)
+}
+
+fn standard_deduction(year: u32, filing_status: FilingStatus) -> Result<Money, TaxError> {
+    use FilingStatus::*;
+
+    let deduction = match (year, filing_status) {
+        (2022, Single) | (2022, MarriedFilingSeparately) => usd_money!(12_950),
+        (2022, MarriedFilingJointly) => usd_money!(25_900),
+        (2022, HeadOfHousehold) => usd_money!(19_400),
+        (2023, Single) | (2023, MarriedFilingSeparately) => usd_money!(13_850),
+        (2023, MarriedFilingJointly) => usd_money!(27_700),
+        (2023, HeadOfHousehold) => usd_money!(20_800),
+        (2024, Single) | (2024, MarriedFilingSeparately) => usd_money!(14_600),
+        (2024, MarriedFilingJointly) => usd_money!(29_200),
+        (2024, HeadOfHousehold) => usd_money!(21_900),
+        _ => return Err(TaxError::UnsupportedYear(year)),
+    };
+
+    Ok(deduction)
+}
+
+/// The Net Investment Income Tax thresholds are set by statute and are not
+/// indexed.
+fn net_investment_income_threshold(filing_status: FilingStatus) -> Money {
+    match filing_status {
+        FilingStatus::MarriedFilingJointly => usd_money!(250_000),
+        FilingStatus::MarriedFilingSeparately => usd_money!(125_000),
+        FilingStatus::Single | FilingStatus::HeadOfHousehold => usd_money!(200_000),
+    }
+}
+
+/// The lines of the IRS Qualified Dividends and Capital Gain Tax Worksheet.
+/// Preferential income is stacked on top of ordinary income, so it fills the
+/// 0% band only to the extent ordinary income has not already used it up.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CapitalGainsWorksheet {
+    pub ordinary_income: Money,
+    pub taxed_at_zero: Money,
+    pub taxed_at_fifteen: Money,
+    pub taxed_at_twenty: Money,
+    pub tax: Money,
+}
+
+/// The US federal ordinary income tax for one tax year and filing status.
+#[derive(Debug, Clone)]
+pub struct FederalIncomeTax {
+    pub year: u32,
+    pub filing_status: FilingStatus,
+    schedule: TaxSchedule,
+    standard_deduction: Money,
+    zero_rate_threshold: Money,
+    fifteen_rate_threshold: Money,
+}
+
+impl FederalIncomeTax {
+    pub fn new(year: u32, filing_status: FilingStatus) -> Result<FederalIncomeTax, TaxError> {
+        let thresholds = federal_thresholds(year, filing_status)?;
+        let (zero_rate_threshold, fifteen_rate_threshold) = preferential_thresholds(year, filing_status)?;
+
+        Ok(FederalIncomeTax {
+            year,
+            filing_status,
+            schedule: TaxSchedule::from_thresholds(Currency::USD, &thresholds, &FEDERAL_RATES)?,
+            standard_deduction: standard_deduction(year, filing_status)?,
+            zero_rate_threshold,
+            fifteen_rate_threshold,
+        })
+    }
+
+    pub fn schedule(&self) -> &TaxSchedule {
+        &self.schedule
+    }
+
+    pub fn standard_deduction(&self) -> Money {
+        self.standard_deduction
+    }
+
+    /// Subtracts the larger of the standard deduction and the itemized
+    /// deductions from adjusted gross income, stopping at zero.
+    pub fn taxable_income(&self, adjusted_gross_income: Money, itemized_deductions: Option<Money>) -> Money {
+        let deduction = match itemized_deductions {
+            Some(itemized) => itemized.max(self.standard_deduction),
+            None => self.standard_deduction,
+        };
+
+        if adjusted_gross_income > deduction {
+            adjusted_gross_income - deduction
+        }else{
+            usd_money!(0)
+        }
+    }
+
+    pub fn calculate_tax(&self, taxable_income: Money) -> Money {
+        self.schedule.calculate_tax(taxable_income)
+    }
+
+    /// Taxes `preferential_income` (qualified dividends plus net capital gain)
+    /// at 0/15/20% and the rest of taxable income at ordinary rates, never
+    /// producing more tax than taxing everything as ordinary income.
+    pub fn capital_gains_worksheet(&self, taxable_income: Money, preferential_income: Money) -> CapitalGainsWorksheet {
+        self.stack_preferential_income(taxable_income, preferential_income, |income| self.calculate_tax(income))
+    }
+
+    /// The worksheet with `ordinary_tax` standing in for the regular brackets,
+    /// which lets the AMT reuse the same 0/15/20% stacking.
+    pub(crate) fn stack_preferential_income<F: Fn(Money) -> Money>(
+        &self,
+        taxable_income: Money,
+        preferential_income: Money,
+        ordinary_tax: F,
+    ) -> CapitalGainsWorksheet {
+        let preferential_income = preferential_income.min(taxable_income).max(usd_money!(0));
+        let ordinary_income = taxable_income - preferential_income;
+
+        let taxed_at_zero = amount_over(taxable_income.min(self.zero_rate_threshold), ordinary_income)
+            .min(preferential_income);
+        let taxed_below_fifteen_threshold = amount_over(
+            taxable_income.min(self.fifteen_rate_threshold),
+            ordinary_income + taxed_at_zero,
+        );
+        let taxed_at_fifteen = taxed_below_fifteen_threshold.min(preferential_income - taxed_at_zero);
+        let taxed_at_twenty = preferential_income - taxed_at_zero - taxed_at_fifteen;
+
+        let worksheet_tax = ordinary_tax(ordinary_income)
+            + taxed_at_fifteen * dec!(0.15)
+            + taxed_at_twenty * dec!(0.20);
+
+        CapitalGainsWorksheet {
+            ordinary_income,
+            taxed_at_zero,
+            taxed_at_fifteen,
+            taxed_at_twenty,
+            tax: worksheet_tax.min(ordinary_tax(taxable_income)),
+        }
+    }
+
+    /// The 3.8% Net Investment Income Tax on the smaller of net investment
+    /// income and the amount modified AGI exceeds the filing-status threshold.
+    pub fn net_investment_income_tax(&self, income: &UsIncome) -> Money {
+        let excess_income = amount_over(
+            income.adjusted_gross_income(),
+            net_investment_income_threshold(self.filing_status),
+        );
+
+        income.net_investment_income().min(excess_income) * dec!(0.038)
+    }
+
+    /// Tax as printed in the IRS Tax Table, which must be used for taxable
+    /// income below $100,000. The table taxes the midpoint of each $50 row
+    /// ($25 below $3,000) and rounds to whole dollars.
+    pub fn tax_table_amount(&self, taxable_income: Money) -> Money {
+        if taxable_income >= usd_money!(100_000) {
+            return self.calculate_tax(taxable_income);
+        }
+        if taxable_income < usd_money!(5) {
+            return usd_money!(0);
+        }
+
+        let (start, width) = if taxable_income < usd_money!(15) {
+            (dec!(5), dec!(10))
+        }else if taxable_income < usd_money!(25) {
+            (dec!(15), dec!(10))
+        }else if taxable_income < usd_money!(3_000) {
+            (dec!(25), dec!(25))
+        }else{
+            (dec!(3_000), dec!(50))
+        };
+        let row = ((taxable_income.amount - start) / width).floor();
+        let midpoint = Money {
+            amount: start + row * width + width / dec!(2),
+            currency: Currency::USD,
+        };
+        let tax = self.calculate_tax(midpoint);
+
+        Money {
+            amount: tax.amount.round_dp_with_strategy(0, RoundingStrategy::RoundHalfUp),
+            currency: Currency::USD,
+        }
+    }
+}
+
+impl IncomeTaxJurisdiction for FederalIncomeTax {
+    fn name(&self) -> &'static str {
+        "United States"
+    }
+
+    fn calculate(&self, income: &UsIncome) -> Result<JurisdictionTax, TaxError> {
+        income.validate()?;
+        let taxable_income = self.taxable_income(income.adjusted_gross_income(), income.itemized_deductions);
+        let worksheet = self.capital_gains_worksheet(taxable_income, income.preferential_income());
+
+        Ok(JurisdictionTax {
+            components: vec![
+                (UsTaxComponent::IncomeTax, worksheet.tax),
+                (UsTaxComponent::NetInvestmentIncomeTax, self.net_investment_income_tax(income)),
+            ],
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tax_table_rows() {
+        let single = FederalIncomeTax::new(2023, FilingStatus::Single).unwrap();
+        assert_eq!(single.tax_table_amount(usd_money!(50_010)), usd_money!(6_313));
+
+        let joint = FederalIncomeTax::new(2023, FilingStatus::MarriedFilingJointly).unwrap();
+        assert_eq!(joint.tax_table_amount(usd_money!(75_020)), usd_money!(8_563));
+
+        let head = FederalIncomeTax::new(2024, FilingStatus::HeadOfHousehold).unwrap();
+        assert_eq!(head.tax_table_amount(usd_money!(30_000)), usd_money!(3_272));
+
+        let separate = FederalIncomeTax::new(2022, FilingStatus::MarriedFilingSeparately).unwrap();
+        assert_eq!(separate.tax_table_amount(usd_money!(1_010)), usd_money!(101));
+    }
+
+    #[test]
+    fn tax_computation_worksheet() {
+        let single = FederalIncomeTax::new(2024, FilingStatus::Single).unwrap();
+        assert_eq!(single.calculate_tax(usd_money!(150_000)), usd_money!(29_042.50));
+
+        let joint = FederalIncomeTax::new(2024, FilingStatus::MarriedFilingJointly).unwrap();
+        assert_eq!(joint.calculate_tax(usd_money!(800_000)), usd_money!(222_125.50));
+    }
+
+    #[test]
+    fn gains_stack_on_top_of_ordinary_income() {
+        let single = FederalIncomeTax::new(2024, FilingStatus::Single).unwrap();
+        let worksheet = single.capital_gains_worksheet(usd_money!(80_000), usd_money!(50_000));
+
+        assert_eq!(worksheet.ordinary_income, usd_money!(30_000));
+        assert_eq!(worksheet.taxed_at_zero, usd_money!(17_025));
+        assert_eq!(worksheet.taxed_at_fifteen, usd_money!(32_975));
+        assert_eq!(worksheet.taxed_at_twenty, usd_money!(0));
+        assert_eq!(worksheet.tax, usd_money!(8_314.25));
+
+        let large = single.capital_gains_worksheet(usd_money!(700_000), usd_money!(600_000));
+        assert_eq!(large.taxed_at_zero, usd_money!(0));
+        assert_eq!(large.taxed_at_fifteen, usd_money!(418_900));
+        assert_eq!(large.taxed_at_twenty, usd_money!(181_100));
+    }
+
+    #[test]
+    fn federal_return_with_investment_income() {
+        let joint = FederalIncomeTax::new(2024, FilingStatus::MarriedFilingJointly).unwrap();
+        let income = UsIncome {
+            qualified_dividends: usd_money!(20_000),
+            short_term_capital_gains: usd_money!(-10_000),
+            long_term_capital_gains: usd_money!(100_000),
+            ..UsIncome::from_wages(usd_money!(219_200))
+        };
+        let tax = joint.calculate(&income).unwrap();
+
+        assert_eq!(income.preferential_income(), usd_money!(110_000));
+        assert_eq!(tax.component(UsTaxComponent::NetInvestmentIncomeTax), usd_money!(3_009.60));
+        assert_eq!(tax.component(UsTaxComponent::IncomeTax), usd_money!(48_406));
+    }
+
+    #[test]
+    fn standard_deduction_by_filing_status() {
+        let joint = FederalIncomeTax::new(2024, FilingStatus::MarriedFilingJointly).unwrap();
+        assert_eq!(joint.taxable_income(usd_money!(100_000), None), usd_money!(70_800));
+        assert_eq!(joint.taxable_income(usd_money!(100_000), Some(usd_money!(35_000))), usd_money!(65_000));
+        assert_eq!(joint.taxable_income(usd_money!(20_000), None), usd_money!(0));
+
+        assert_eq!(
+            FederalIncomeTax::new(2019, FilingStatus::Single).unwrap_err(),
+            TaxError::UnsupportedYear(2019)
+        );
+    }
+}
@@ -0,0 +1,172 @@
+use rust_decimal::prelude::*;
+use rust_decimal_macros::*;
+use simple_money::*;
+use crate::TaxError;
+use super::{amount_over, FilingStatus};
+
+/// Wages above this amount paid by a single employer are subject to Additional
+/// Medicare withholding regardless of the employee's filing status.
+pub(crate) const ADDITIONAL_MEDICARE_WITHHOLDING_THRESHOLD: Decimal = dec!(200_000);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FicaParameters {
+    pub social_security_rate: Decimal,
+    pub social_security_wage_base: Money,
+    pub medicare_rate: Decimal,
+    pub additional_medicare_rate: Decimal,
+}
+
+impl FicaParameters {
+    pub fn for_year(year: u32) -> Result<FicaParameters, TaxError> {
+        let wage_base = match year {
+            2020 => usd_money!(137_700),
+            2021 => usd_money!(142_800),
+            2022 => usd_money!(147_000),
+            2023 => usd_money!(160_200),
+            2024 => usd_money!(168_600),
+            2025 => usd_money!(176_100),
+            _ => return Err(TaxError::UnsupportedYear(year)),
+        };
+
+        Ok(FicaParameters {
+            social_security_rate: dec!(0.062),
+            social_security_wage_base: wage_base,
+            medicare_rate: dec!(0.0145),
+            additional_medicare_rate: dec!(0.009),
+        })
+    }
+
+    /// The Additional Medicare Tax threshold is set by statute and is not indexed.
+    pub fn additional_medicare_threshold(filing_status: FilingStatus) -> Money {
+        match filing_status {
+            FilingStatus::MarriedFilingJointly => usd_money!(250_000),
+            FilingStatus::MarriedFilingSeparately => usd_money!(125_000),
+            FilingStatus::Single | FilingStatus::HeadOfHousehold => usd_money!(200_000),
+        }
+    }
+
+    fn social_security_on(&self, wages: Money) -> Money {
+        wages.min(self.social_security_wage_base) * self.social_security_rate
+    }
+
+    /// Calculates FICA for a year given the wages paid by each employer.
+    ///
+    /// Every employer withholds Social Security up to the full wage base, so an
+    /// employee with more than one employer can be over-withheld; the difference
+    /// is reported as `excess_social_security_withholding` and is refundable as a
+    /// credit on the employee's return. Employers cannot recover their share.
+    pub fn calculate(
+        &self,
+        wages_by_employer: Vec<Money>,
+        filing_status: FilingStatus,
+    ) -> Result<FicaResult, TaxError> {
+        if wages_by_employer.iter().any(|wages| wages.currency != Currency::USD) {
+            return Err(TaxError::MismatchedCurrencies);
+        }
+
+        let additional_withholding_threshold = Money {
+            amount: ADDITIONAL_MEDICARE_WITHHOLDING_THRESHOLD,
+            currency: Currency::USD,
+        };
+        let mut total_wages = usd_money!(0);
+        let mut social_security_withheld = usd_money!(0);
+        let mut additional_medicare_withheld = usd_money!(0);
+        for wages in wages_by_employer.iter() {
+            total_wages = total_wages + *wages;
+            social_security_withheld = social_security_withheld + self.social_security_on(*wages);
+            additional_medicare_withheld = additional_medicare_withheld
+                + amount_over(*wages, additional_withholding_threshold) * self.additional_medicare_rate;
+        }
+
+        let social_security = self.social_security_on(total_wages);
+        let medicare = total_wages * self.medicare_rate;
+        let additional_medicare = amount_over(
+            total_wages,
+            Self::additional_medicare_threshold(filing_status),
+        ) * self.additional_medicare_rate;
+
+        Ok(FicaResult {
+            social_security,
+            medicare,
+            additional_medicare,
+            additional_medicare_withheld,
+            excess_social_security_withholding: social_security_withheld - social_security,
+            employer_social_security: social_security_withheld,
+            employer_medicare: medicare,
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FicaResult {
+    pub social_security: Money,
+    pub medicare: Money,
+    pub additional_medicare: Money,
+    pub additional_medicare_withheld: Money,
+    pub excess_social_security_withholding: Money,
+    pub employer_social_security: Money,
+    pub employer_medicare: Money,
+}
+
+impl FicaResult {
+    pub fn employee_total(&self) -> Money {
+        self.social_security + self.medicare + self.additional_medicare
+    }
+
+    pub fn employer_total(&self) -> Money {
+        self.employer_social_security + self.employer_medicare
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn single_employer_below_wage_base() {
+        let parameters = FicaParameters::for_year(2024).unwrap();
+        let result = parameters.calculate(vec![usd_money!(100_000)], FilingStatus::Single).unwrap();
+
+        assert_eq!(result.social_security, usd_money!(6_200));
+        assert_eq!(result.medicare, usd_money!(1_450));
+        assert_eq!(result.additional_medicare, usd_money!(0));
+        assert_eq!(result.excess_social_security_withholding, usd_money!(0));
+        assert_eq!(result.employer_total(), usd_money!(7_650));
+    }
+
+    #[test]
+    fn wage_base_and_additional_medicare() {
+        let parameters = FicaParameters::for_year(2024).unwrap();
+        let result = parameters.calculate(vec![usd_money!(300_000)], FilingStatus::MarriedFilingJointly).unwrap();
+
+        assert_eq!(result.social_security, usd_money!(10_453.20));
+        assert_eq!(result.medicare, usd_money!(4_350));
+        assert_eq!(result.additional_medicare, usd_money!(450));
+        assert_eq!(result.additional_medicare_withheld, usd_money!(900));
+        assert_eq!(result.employer_medicare, usd_money!(4_350));
+    }
+
+    #[test]
+    fn excess_social_security_with_two_employers() {
+        let parameters = FicaParameters::for_year(2023).unwrap();
+        let result = parameters.calculate(
+            vec![usd_money!(120_000), usd_money!(90_000)],
+            FilingStatus::Single,
+        ).unwrap();
+
+        assert_eq!(result.social_security, usd_money!(9_932.40));
+        assert_eq!(result.employer_social_security, usd_money!(13_020));
+        assert_eq!(result.excess_social_security_withholding, usd_money!(3_087.60));
+        assert_eq!(result.additional_medicare, usd_money!(90));
+        assert_eq!(result.additional_medicare_withheld, usd_money!(0));
+    }
+
+    #[test]
+    fn rejects_non_usd_wages_and_unknown_years() {
+        let parameters = FicaParameters::for_year(2024).unwrap();
+        let error = parameters.calculate(vec![cad_money!(1)], FilingStatus::Single).unwrap_err();
+        assert_eq!(error, TaxError::MismatchedCurrencies);
+
+        assert_eq!(FicaParameters::for_year(1999).unwrap_err(), TaxError::UnsupportedYear(1999));
+    }
+}
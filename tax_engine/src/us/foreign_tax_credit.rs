@@ -0,0 +1,195 @@
+use rust_decimal_macros::*;
+use simple_money::*;
+use std::collections::HashMap;
+use crate::TaxError;
+
+/// Years unused foreign tax can be carried forward.
+const CARRYFORWARD_YEARS: u32 = 10;
+
+/// The Form 1116 separate categories individuals commonly use. The
+/// limitation is worked out for each category on its own, so high-taxed
+/// income in one cannot shelter low-taxed income in another.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum ForeignTaxCategory {
+    General,
+    Passive,
+    /// US-source income treated as foreign source under a treaty, such as
+    /// the Canada–US treaty's rule for citizens living in Canada.
+    TreatyResourced,
+}
+
+/// Foreign-source income in one category and the foreign tax paid on it, in
+/// US dollars.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ForeignSourceIncome {
+    pub category: ForeignTaxCategory,
+    pub income: Money,
+    pub tax_paid: Money,
+}
+
+/// One Form 1116 for one year.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ForeignTaxCategoryCredit {
+    pub category: ForeignTaxCategory,
+    pub limitation: Money,
+    pub tax_paid: Money,
+    pub carryover_used: Money,
+    pub credit: Money,
+    /// This year's tax that could not be credited.
+    pub unused: Money,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UsForeignTaxCreditYear {
+    pub year: u32,
+    pub categories: Vec<ForeignTaxCategoryCredit>,
+}
+
+impl UsForeignTaxCreditYear {
+    pub fn total(&self) -> Money {
+        self.categories.iter().fold(usd_money!(0), |sum, category| sum + category.credit)
+    }
+}
+
+/// The foreign tax credit, tracked across years so unused foreign tax in
+/// each category can be carried forward ten years. The one-year carryback
+/// needs an amended return and is left to the caller.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct UsForeignTaxCredit {
+    carryovers: HashMap<ForeignTaxCategory, Vec<(u32, Money)>>,
+    ledger: Vec<UsForeignTaxCreditYear>,
+}
+
+impl UsForeignTaxCredit {
+    pub fn new() -> UsForeignTaxCredit {
+        UsForeignTaxCredit { carryovers: HashMap::new(), ledger: vec![] }
+    }
+
+    pub fn ledger(&self) -> &[UsForeignTaxCreditYear] {
+        &self.ledger
+    }
+
+    /// Unused tax in `category` that can still be claimed in `year`.
+    pub fn carryover(&self, category: ForeignTaxCategory, year: u32) -> Money {
+        self.carryovers
+            .get(&category)
+            .map(|carryovers| {
+                carryovers
+                    .iter()
+                    .filter(|(from, _)| *from < year && year <= from + CARRYFORWARD_YEARS)
+                    .fold(usd_money!(0), |sum, (_, amount)| sum + *amount)
+            })
+            .unwrap_or(usd_money!(0))
+    }
+
+    /// Credits for one year. Each category may credit up to `us_tax` times
+    /// its share of `total_income`, which assumes deductions are apportioned
+    /// ratably. This year's tax is used before carryovers, and carryovers
+    /// oldest first.
+    pub fn record_year(
+        &mut self,
+        year: u32,
+        us_tax: Money,
+        total_income: Money,
+        foreign_income: &[ForeignSourceIncome],
+    ) -> Result<UsForeignTaxCreditYear, TaxError> {
+        let mut amounts = vec![us_tax, total_income];
+        amounts.extend(foreign_income.iter().flat_map(|income| [income.income, income.tax_paid]));
+        if amounts.iter().any(|money| money.currency != Currency::USD) {
+            return Err(TaxError::MismatchedCurrencies);
+        }
+
+        let zero = usd_money!(0);
+        let mut categories: Vec<ForeignTaxCategory> = foreign_income.iter().map(|income| income.category).collect();
+        categories.extend(self.carryovers.keys().copied());
+        categories.sort();
+        categories.dedup();
+
+        let mut remaining_tax = us_tax;
+        let mut credits = vec![];
+        for category in categories {
+            let income = foreign_income
+                .iter()
+                .filter(|income| income.category == category)
+                .fold(zero, |sum, income| sum + income.income);
+            let tax_paid = foreign_income
+                .iter()
+                .filter(|income| income.category == category)
+                .fold(zero, |sum, income| sum + income.tax_paid);
+            let share = if total_income > zero {
+                (income.amount / total_income.amount).max(dec!(0)).min(dec!(1))
+            }else{
+                dec!(0)
+            };
+            let limitation = (us_tax * share).min(remaining_tax);
+
+            let current_credit = tax_paid.min(limitation);
+            let mut room = limitation - current_credit;
+            let mut carryover_used = zero;
+            let carryovers = self.carryovers.entry(category).or_default();
+            carryovers.retain(|(from, _)| year <= from + CARRYFORWARD_YEARS);
+            carryovers.sort_by_key(|(from, _)| *from);
+            for (_, amount) in carryovers.iter_mut().filter(|(from, _)| *from < year) {
+                let used = (*amount).min(room);
+                *amount = *amount - used;
+                room = room - used;
+                carryover_used = carryover_used + used;
+            }
+            carryovers.retain(|(_, amount)| *amount > zero);
+            let unused = tax_paid - current_credit;
+            if unused > zero {
+                carryovers.push((year, unused));
+            }
+
+            let credit = current_credit + carryover_used;
+            remaining_tax = remaining_tax - credit;
+            if income > zero || tax_paid > zero || carryover_used > zero {
+                credits.push(ForeignTaxCategoryCredit { category, limitation, tax_paid, carryover_used, credit, unused });
+            }
+        }
+        self.carryovers.retain(|_, carryovers| !carryovers.is_empty());
+
+        let entry = UsForeignTaxCreditYear { year, categories: credits };
+        self.ledger.push(entry.clone());
+        Ok(entry)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn categories_are_limited_separately() {
+        let mut credit = UsForeignTaxCredit::new();
+        let year = credit
+            .record_year(2024, usd_money!(20_000), usd_money!(100_000), &[
+                ForeignSourceIncome { category: ForeignTaxCategory::General, income: usd_money!(40_000), tax_paid: usd_money!(12_000) },
+                ForeignSourceIncome { category: ForeignTaxCategory::Passive, income: usd_money!(10_000), tax_paid: usd_money!(500) },
+            ])
+            .unwrap();
+        assert_eq!(year.categories[0].limitation, usd_money!(8_000));
+        assert_eq!(year.categories[0].unused, usd_money!(4_000));
+        // Excess general tax cannot use the passive category's spare limit.
+        assert_eq!(year.categories[1].credit, usd_money!(500));
+        assert_eq!(year.total(), usd_money!(8_500));
+        assert_eq!(credit.carryover(ForeignTaxCategory::General, 2025), usd_money!(4_000));
+        assert_eq!(credit.carryover(ForeignTaxCategory::Passive, 2025), usd_money!(0));
+    }
+
+    #[test]
+    fn carryovers_are_used_oldest_first_and_expire() {
+        let mut credit = UsForeignTaxCredit::new();
+        let general = |tax_paid: Money| ForeignSourceIncome { category: ForeignTaxCategory::General, income: usd_money!(40_000), tax_paid };
+        credit.record_year(2023, usd_money!(20_000), usd_money!(100_000), &[general(usd_money!(10_000))]).unwrap();
+        credit.record_year(2024, usd_money!(20_000), usd_money!(100_000), &[general(usd_money!(9_000))]).unwrap();
+        assert_eq!(credit.carryover(ForeignTaxCategory::General, 2025), usd_money!(3_000));
+
+        let year = credit.record_year(2025, usd_money!(20_000), usd_money!(100_000), &[general(usd_money!(6_000))]).unwrap();
+        assert_eq!(year.categories[0].carryover_used, usd_money!(2_000));
+        assert_eq!(credit.carryover(ForeignTaxCategory::General, 2026), usd_money!(1_000));
+        // What is left came from 2024, so it can be used through 2034.
+        assert_eq!(credit.carryover(ForeignTaxCategory::General, 2034), usd_money!(1_000));
+        assert_eq!(credit.carryover(ForeignTaxCategory::General, 2035), usd_money!(0));
+    }
+}
@@ -0,0 +1,143 @@
+use simple_money::*;
+use rust_decimal_macros::*;
+use crate::TaxError;
+
+/// How an amount of income is taxed. Short-term gains are taxed federally as
+/// ordinary income but some states treat them differently.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum IncomeCharacter {
+    Ordinary,
+    QualifiedDividends,
+    ShortTermCapitalGains,
+    LongTermCapitalGains,
+}
+
+/// A year's income as seen by a US taxing jurisdiction.
+///
+/// `other_ordinary_income` covers everything that is neither wages nor one of
+/// the investment characters, such as interest and non-qualified dividends.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct UsIncome {
+    pub wages: Money,
+    pub other_ordinary_income: Money,
+    pub qualified_dividends: Money,
+    pub short_term_capital_gains: Money,
+    pub long_term_capital_gains: Money,
+    pub itemized_deductions: Option<Money>,
+}
+
+impl UsIncome {
+    pub fn from_wages(wages: Money) -> UsIncome {
+        UsIncome {
+            wages,
+            other_ordinary_income: usd_money!(0),
+            qualified_dividends: usd_money!(0),
+            short_term_capital_gains: usd_money!(0),
+            long_term_capital_gains: usd_money!(0),
+            itemized_deductions: None,
+        }
+    }
+
+    pub fn amount(&self, character: IncomeCharacter) -> Money {
+        match character {
+            IncomeCharacter::Ordinary => self.wages + self.other_ordinary_income,
+            IncomeCharacter::QualifiedDividends => self.qualified_dividends,
+            IncomeCharacter::ShortTermCapitalGains => self.short_term_capital_gains,
+            IncomeCharacter::LongTermCapitalGains => self.long_term_capital_gains,
+        }
+    }
+
+    pub fn adjusted_gross_income(&self) -> Money {
+        self.wages
+            + self.other_ordinary_income
+            + self.qualified_dividends
+            + self.short_term_capital_gains
+            + self.long_term_capital_gains
+    }
+
+    /// Net capital gain: long-term gains after any short-term losses, never
+    /// below zero.
+    pub fn net_capital_gain(&self) -> Money {
+        let net = self.long_term_capital_gains.min(self.long_term_capital_gains + self.short_term_capital_gains);
+        amount_over(net, usd_money!(0))
+    }
+
+    /// Income eligible for the 0/15/20% rates.
+    pub fn preferential_income(&self) -> Money {
+        self.qualified_dividends + self.net_capital_gain()
+    }
+
+    /// Investment income subject to the Net Investment Income Tax. Interest
+    /// and other investment income reported in `other_ordinary_income` is not
+    /// included.
+    pub fn net_investment_income(&self) -> Money {
+        amount_over(
+            self.qualified_dividends + self.short_term_capital_gains + self.long_term_capital_gains,
+            usd_money!(0),
+        )
+    }
+
+    pub(crate) fn validate(&self) -> Result<(), TaxError> {
+        let amounts = [
+            Some(self.wages),
+            Some(self.other_ordinary_income),
+            Some(self.qualified_dividends),
+            Some(self.short_term_capital_gains),
+            Some(self.long_term_capital_gains),
+            self.itemized_deductions,
+        ];
+        if amounts.iter().flatten().all(|money| money.currency == Currency::USD) {
+            Ok(())
+        }else{
+            Err(TaxError::MismatchedCurrencies)
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum UsTaxComponent {
+    IncomeTax,
+    MentalHealthServicesTax,
+    StateDisabilityInsurance,
+    NewYorkCityTax,
+    Surtax,
+    CapitalGainsExcise,
+    NetInvestmentIncomeTax,
+}
+
+/// The itemized taxes a jurisdiction levies on a `UsIncome`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct JurisdictionTax {
+    pub components: Vec<(UsTaxComponent, Money)>,
+}
+
+impl JurisdictionTax {
+    pub fn component(&self, component: UsTaxComponent) -> Money {
+        self.components
+            .iter()
+            .filter(|(candidate, _)| *candidate == component)
+            .fold(usd_money!(0), |acc, (_, money)| acc + *money)
+    }
+
+    pub fn total(&self) -> Money {
+        self.components
+            .iter()
+            .fold(usd_money!(0), |acc, (_, money)| acc + *money)
+    }
+}
+
+/// Anything that taxes a US resident's yearly income: the federal schedule
+/// and each state module implement this so they can be summed for a return.
+pub trait IncomeTaxJurisdiction {
+    fn name(&self) -> &'static str;
+
+    fn calculate(&self, income: &UsIncome) -> Result<JurisdictionTax, TaxError>;
+}
+
+pub(crate) fn amount_over(income: Money, threshold: Money) -> Money {
+    if income > threshold {
+        income - threshold
+    }else{
+        Money { amount: dec!(0), currency: income.currency }
+    }
+}
@@ -0,0 +1,456 @@
+use chrono::{Months, NaiveDate};
+use rust_decimal::prelude::*;
+use rust_decimal_macros::*;
+use simple_money::*;
+use crate::TaxError;
+use crate::ca::{AcbTransaction, AcbTransactionKind};
+use super::UsIncome;
+
+/// Property held more than one year gives long-term gain or loss.
+pub fn is_long_term(acquisition_date: NaiveDate, sale_date: NaiveDate) -> bool {
+    sale_date > acquisition_date + Months::new(12)
+}
+
+/// Shares bought on one date at one cost. Ids are assigned in the order
+/// lots are opened.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TaxLot {
+    pub id: usize,
+    pub acquisition_date: NaiveDate,
+    pub shares: Decimal,
+    pub basis: Money,
+}
+
+impl TaxLot {
+    pub fn basis_per_share(&self) -> Decimal {
+        if self.shares.is_zero() {
+            dec!(0)
+        }else{
+            self.basis.amount / self.shares
+        }
+    }
+}
+
+/// Which lots a sale draws shares from.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LotSelection {
+    FirstInFirstOut,
+    HighestInFirstOut,
+    /// Shares to take from each lot, by lot id.
+    SpecificIdentification(Vec<(usize, Decimal)>),
+}
+
+/// The part of a sale matched to one lot.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LotDisposition {
+    pub lot_id: usize,
+    pub acquisition_date: NaiveDate,
+    pub sale_date: NaiveDate,
+    pub shares: Decimal,
+    pub proceeds: Money,
+    pub basis: Money,
+    /// Part of a loss disallowed by the wash sale rule.
+    pub disallowed_loss: Money,
+    /// Negative for a loss, after any disallowed loss.
+    pub gain: Money,
+    pub long_term: bool,
+}
+
+impl LotDisposition {
+    pub fn apply_to(&self, income: &mut UsIncome) {
+        if self.long_term {
+            income.long_term_capital_gains = income.long_term_capital_gains + self.gain;
+        }else{
+            income.short_term_capital_gains = income.short_term_capital_gains + self.gain;
+        }
+    }
+}
+
+/// The open lots of one security in a US account, with the dispositions
+/// realized so far. Amounts are in USD.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TaxLots {
+    pub security: String,
+    lots: Vec<TaxLot>,
+    next_id: usize,
+    dispositions: Vec<LotDisposition>,
+}
+
+fn require_usd(money: Money) -> Result<Money, TaxError> {
+    if money.currency == Currency::USD {
+        Ok(money)
+    }else{
+        Err(TaxError::MismatchedCurrencies)
+    }
+}
+
+impl TaxLots {
+    pub fn new(security: &str) -> TaxLots {
+        TaxLots {
+            security: security.to_string(),
+            lots: vec![],
+            next_id: 0,
+            dispositions: vec![],
+        }
+    }
+
+    /// Open lots, by acquisition date.
+    pub fn lots(&self) -> &[TaxLot] {
+        &self.lots
+    }
+
+    pub fn shares(&self) -> Decimal {
+        self.lots.iter().map(|lot| lot.shares).sum()
+    }
+
+    pub fn dispositions(&self) -> &[LotDisposition] {
+        &self.dispositions
+    }
+
+    /// Opens a lot and returns its id. `cost` includes commissions. Lots
+    /// may be recorded out of date order.
+    pub fn buy(&mut self, acquisition_date: NaiveDate, shares: Decimal, cost: Money) -> Result<usize, TaxError> {
+        if shares <= dec!(0) {
+            return Err(TaxError::NonPositiveShares(shares));
+        }
+        let basis = require_usd(cost)?;
+        let id = self.next_id;
+        self.next_id += 1;
+        self.lots.push(TaxLot { id, acquisition_date, shares, basis });
+        self.sort_lots();
+        Ok(id)
+    }
+
+    /// Keeps lots in acquisition order, which FIFO relies on.
+    fn sort_lots(&mut self) {
+        self.lots.sort_by_key(|lot| (lot.acquisition_date, lot.id));
+    }
+
+    fn shares_to_take(&self, shares: Decimal, selection: &LotSelection) -> Result<Vec<(usize, Decimal)>, TaxError> {
+        if shares <= dec!(0) {
+            return Err(TaxError::NonPositiveShares(shares));
+        }
+        let in_order = |order: Vec<&TaxLot>| {
+            let mut remaining = shares;
+            let mut taken = vec![];
+            for lot in order {
+                if remaining.is_zero() {
+                    break;
+                }
+                let from_lot = remaining.min(lot.shares);
+                taken.push((lot.id, from_lot));
+                remaining -= from_lot;
+            }
+            taken
+        };
+
+        let taken = match selection {
+            LotSelection::FirstInFirstOut => in_order(self.lots.iter().collect()),
+            LotSelection::HighestInFirstOut => {
+                let mut order: Vec<&TaxLot> = self.lots.iter().collect();
+                order.sort_by_key(|lot| std::cmp::Reverse(lot.basis_per_share()));
+                in_order(order)
+            }
+            LotSelection::SpecificIdentification(chosen) => {
+                // A lot may be named more than once, so check the total taken
+                // from each.
+                let mut totals: Vec<(usize, Decimal)> = vec![];
+                for (id, from_lot) in chosen.iter() {
+                    if *from_lot <= dec!(0) {
+                        return Err(TaxError::NonPositiveShares(*from_lot));
+                    }
+                    match totals.iter_mut().find(|(total_id, _)| total_id == id) {
+                        Some((_, total)) => *total += *from_lot,
+                        None => totals.push((*id, *from_lot)),
+                    }
+                }
+                for (id, total) in totals.iter() {
+                    match self.lots.iter().find(|lot| lot.id == *id) {
+                        Some(lot) if *total <= lot.shares => {}
+                        _ => return Err(TaxError::InsufficientShares),
+                    }
+                }
+                totals
+            }
+        };
+
+        if taken.iter().map(|(_, from_lot)| *from_lot).sum::<Decimal>() != shares {
+            return Err(TaxError::InsufficientShares);
+        }
+        Ok(taken)
+    }
+
+    /// Sells shares from the lots chosen by `selection`, splitting proceeds
+    /// across lots by share count. `proceeds` are net of commissions.
+    pub fn sell(
+        &mut self,
+        sale_date: NaiveDate,
+        shares: Decimal,
+        proceeds: Money,
+        selection: &LotSelection,
+    ) -> Result<Vec<LotDisposition>, TaxError> {
+        let proceeds = require_usd(proceeds)?;
+        let taken = self.shares_to_take(shares, selection)?;
+
+        let mut dispositions = Vec::with_capacity(taken.len());
+        for (id, from_lot) in taken {
+            let lot = self.lots.iter_mut().find(|lot| lot.id == id).unwrap();
+            let basis = Money { amount: lot.basis.amount * from_lot / lot.shares, currency: Currency::USD };
+            let lot_proceeds = Money { amount: proceeds.amount * from_lot / shares, currency: Currency::USD };
+            lot.shares -= from_lot;
+            lot.basis = lot.basis - basis;
+            dispositions.push(LotDisposition {
+                lot_id: id,
+                acquisition_date: lot.acquisition_date,
+                sale_date,
+                shares: from_lot,
+                proceeds: lot_proceeds,
+                basis,
+                disallowed_loss: usd_money!(0),
+                gain: lot_proceeds - basis,
+                long_term: is_long_term(lot.acquisition_date, sale_date),
+            });
+        }
+        self.lots.retain(|lot| !lot.shares.is_zero());
+        self.dispositions.extend(dispositions.iter().copied());
+
+        Ok(dispositions)
+    }
+
+    /// Moves `shares` of a replacement lot into a new lot that carries the
+    /// disallowed loss in its basis and starts its holding period
+    /// `holding_days` earlier, taking on the sold shares' holding period.
+    pub(crate) fn apply_wash_sale(
+        &mut self,
+        lot_id: usize,
+        shares: Decimal,
+        disallowed_loss: Money,
+        holding_days: i64,
+    ) -> Result<(), TaxError> {
+        let next_id = self.next_id;
+        let lot = match self.lots.iter_mut().find(|lot| lot.id == lot_id) {
+            Some(lot) if shares <= lot.shares => lot,
+            _ => return Err(TaxError::InsufficientShares),
+        };
+        let moved_basis = Money { amount: lot.basis.amount * shares / lot.shares, currency: Currency::USD };
+        let replacement = TaxLot {
+            id: next_id,
+            acquisition_date: lot.acquisition_date - chrono::Duration::days(holding_days),
+            shares,
+            basis: moved_basis + disallowed_loss,
+        };
+        lot.shares -= shares;
+        lot.basis = lot.basis - moved_basis;
+
+        self.next_id += 1;
+        self.lots.push(replacement);
+        self.lots.retain(|lot| !lot.shares.is_zero());
+        self.sort_lots();
+        Ok(())
+    }
+
+    /// Disallows part of the loss on a disposition, by its position in
+    /// `dispositions()`.
+    pub(crate) fn disallow_loss(&mut self, index: usize, amount: Money) {
+        if let Some(disposition) = self.dispositions.get_mut(index) {
+            disposition.disallowed_loss = disposition.disallowed_loss + amount;
+            disposition.gain = disposition.gain + amount;
+        }
+    }
+
+    /// Spreads a basis change over every open share.
+    fn adjust_basis_per_share(&mut self, amount: Money) {
+        let shares = self.shares();
+        if shares.is_zero() {
+            return;
+        }
+        for lot in self.lots.iter_mut() {
+            lot.basis = lot.basis + Money { amount: amount.amount * lot.shares / shares, currency: Currency::USD };
+        }
+    }
+
+    /// Applies a transaction from the stream that also drives a Canadian
+    /// `AdjustedCostBase`, so a dual filer's account is tracked both ways.
+    /// `exchange` must hold the rates for the transaction date.
+    ///
+    /// A return of capital reduces each lot's basis, with any excess over a
+    /// lot's basis reported as gain on that lot. A phantom distribution is an
+    /// undistributed capital gain and raises basis.
+    pub fn record(
+        &mut self,
+        transaction: &AcbTransaction,
+        selection: &LotSelection,
+        exchange: &Exchange,
+    ) -> Result<Vec<LotDisposition>, TaxError> {
+        let to_usd = |money: Money| exchange.convert(money, Currency::USD);
+        let date = transaction.date;
+
+        match transaction.kind {
+            AcbTransactionKind::Buy { shares, cost } => {
+                self.buy(date, shares, to_usd(cost)?)?;
+                Ok(vec![])
+            }
+            AcbTransactionKind::ReinvestedDistribution { shares, amount } => {
+                self.buy(date, shares, to_usd(amount)?)?;
+                Ok(vec![])
+            }
+            AcbTransactionKind::Sell { shares, proceeds } => self.sell(date, shares, to_usd(proceeds)?, selection),
+            AcbTransactionKind::PhantomDistribution { amount } => {
+                self.adjust_basis_per_share(to_usd(amount)?);
+                Ok(vec![])
+            }
+            AcbTransactionKind::ReturnOfCapital { amount } => {
+                let amount = to_usd(amount)?;
+                let shares = self.shares();
+                let mut dispositions = vec![];
+                for lot in self.lots.iter_mut() {
+                    let reduction = Money { amount: amount.amount * lot.shares / shares, currency: Currency::USD };
+                    let excess = reduction - reduction.min(lot.basis);
+                    lot.basis = lot.basis - reduction.min(lot.basis);
+                    if excess.amount > dec!(0) {
+                        dispositions.push(LotDisposition {
+                            lot_id: lot.id,
+                            acquisition_date: lot.acquisition_date,
+                            sale_date: date,
+                            shares: dec!(0),
+                            proceeds: excess,
+                            basis: usd_money!(0),
+                            disallowed_loss: usd_money!(0),
+                            gain: excess,
+                            long_term: is_long_term(lot.acquisition_date, date),
+                        });
+                    }
+                }
+                self.dispositions.extend(dispositions.iter().copied());
+                Ok(dispositions)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ca::AdjustedCostBase;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    fn three_lots() -> TaxLots {
+        let mut lots = TaxLots::new("AAPL");
+        lots.buy(date(2022, 1, 10), dec!(100), usd_money!(15_000)).unwrap();
+        lots.buy(date(2023, 8, 1), dec!(100), usd_money!(19_000)).unwrap();
+        lots.buy(date(2024, 2, 1), dec!(100), usd_money!(18_000)).unwrap();
+        lots
+    }
+
+    #[test]
+    fn fifo_hifo_and_specific_identification() {
+        let sale_date = date(2024, 6, 3);
+
+        let mut fifo = three_lots();
+        let sold = fifo.sell(sale_date, dec!(150), usd_money!(30_000), &LotSelection::FirstInFirstOut).unwrap();
+        assert_eq!(sold.iter().map(|lot| lot.lot_id).collect::<Vec<_>>(), vec![0, 1]);
+        assert_eq!(sold[0].gain, usd_money!(5_000));
+        assert!(sold[0].long_term);
+        assert_eq!(sold[1].gain, usd_money!(500));
+        assert!(!sold[1].long_term);
+
+        let mut hifo = three_lots();
+        let sold = hifo.sell(sale_date, dec!(150), usd_money!(30_000), &LotSelection::HighestInFirstOut).unwrap();
+        assert_eq!(sold.iter().map(|lot| lot.lot_id).collect::<Vec<_>>(), vec![1, 2]);
+        assert_eq!(sold[0].gain, usd_money!(1_000));
+        assert_eq!(sold[1].gain, usd_money!(1_000));
+
+        let mut specific = three_lots();
+        let chosen = LotSelection::SpecificIdentification(vec![(2, dec!(100)), (0, dec!(50))]);
+        let sold = specific.sell(sale_date, dec!(150), usd_money!(30_000), &chosen).unwrap();
+        let mut income = UsIncome::from_wages(usd_money!(0));
+        sold.iter().for_each(|lot| lot.apply_to(&mut income));
+        assert_eq!(income.short_term_capital_gains, usd_money!(2_000));
+        assert_eq!(income.long_term_capital_gains, usd_money!(2_500));
+        assert_eq!(specific.shares(), dec!(150));
+    }
+
+    #[test]
+    fn cannot_sell_shares_that_are_not_held() {
+        let mut lots = three_lots();
+        let too_many = lots.sell(date(2024, 6, 3), dec!(301), usd_money!(1), &LotSelection::FirstInFirstOut);
+        assert_eq!(too_many.unwrap_err(), TaxError::InsufficientShares);
+
+        let wrong_lot = LotSelection::SpecificIdentification(vec![(7, dec!(1))]);
+        let missing = lots.sell(date(2024, 6, 3), dec!(1), usd_money!(1), &wrong_lot);
+        assert_eq!(missing.unwrap_err(), TaxError::InsufficientShares);
+    }
+
+    #[test]
+    fn specific_identification_checks_each_lot_in_total() {
+        let mut lots = three_lots();
+        let twice = LotSelection::SpecificIdentification(vec![(0, dec!(100)), (0, dec!(100))]);
+        let sold = lots.sell(date(2024, 6, 3), dec!(200), usd_money!(40_000), &twice);
+        assert_eq!(sold.unwrap_err(), TaxError::InsufficientShares);
+
+        let negative = LotSelection::SpecificIdentification(vec![(0, dec!(150)), (1, dec!(-50))]);
+        let sold = lots.sell(date(2024, 6, 3), dec!(100), usd_money!(20_000), &negative);
+        assert_eq!(sold.unwrap_err(), TaxError::NonPositiveShares(dec!(-50)));
+        assert_eq!(lots.shares(), dec!(300));
+
+        // Naming a lot twice is fine while the total fits.
+        let split = LotSelection::SpecificIdentification(vec![(0, dec!(40)), (1, dec!(10)), (0, dec!(60))]);
+        let sold = lots.sell(date(2024, 6, 3), dec!(110), usd_money!(22_000), &split).unwrap();
+        assert_eq!(sold.iter().map(|lot| (lot.lot_id, lot.shares)).collect::<Vec<_>>(), vec![(0, dec!(100)), (1, dec!(10))]);
+        assert_eq!(sold[0].basis, usd_money!(15_000));
+    }
+
+    #[test]
+    fn fifo_follows_acquisition_date_not_recording_order() {
+        let mut lots = TaxLots::new("MSFT");
+        let recent = lots.buy(date(2024, 6, 1), dec!(10), usd_money!(4_000)).unwrap();
+        let back_dated = lots.buy(date(2020, 1, 1), dec!(10), usd_money!(1_500)).unwrap();
+        assert_eq!(lots.lots().iter().map(|lot| lot.id).collect::<Vec<_>>(), vec![back_dated, recent]);
+
+        let sold = lots.sell(date(2024, 9, 3), dec!(10), usd_money!(4_200), &LotSelection::FirstInFirstOut).unwrap();
+        assert_eq!(sold[0].lot_id, back_dated);
+        assert!(sold[0].long_term);
+        assert_eq!(sold[0].gain, usd_money!(2_700));
+    }
+
+    #[test]
+    fn share_counts_must_be_positive() {
+        let mut lots = TaxLots::new("MSFT");
+        assert_eq!(lots.buy(date(2024, 1, 2), dec!(0), usd_money!(0)).unwrap_err(), TaxError::NonPositiveShares(dec!(0)));
+        lots.buy(date(2024, 1, 2), dec!(10), usd_money!(1_000)).unwrap();
+        assert_eq!(lots.buy(date(2024, 1, 3), dec!(-1), usd_money!(100)).unwrap_err(), TaxError::NonPositiveShares(dec!(-1)));
+
+        for selection in [LotSelection::FirstInFirstOut, LotSelection::HighestInFirstOut] {
+            let sold = lots.sell(date(2024, 6, 3), dec!(-5), usd_money!(-500), &selection);
+            assert_eq!(sold.unwrap_err(), TaxError::NonPositiveShares(dec!(-5)));
+        }
+        assert_eq!(lots.shares(), dec!(10));
+    }
+
+    #[test]
+    fn one_transaction_stream_for_both_countries() {
+        let mut exchange = Exchange::new();
+        exchange.set_rate(Currency::USD, Currency::CAD, dec!(1.25));
+        let transactions = [
+            AcbTransaction { date: date(2023, 1, 5), kind: AcbTransactionKind::Buy { shares: dec!(10), cost: usd_money!(1_000) } },
+            AcbTransaction { date: date(2023, 9, 5), kind: AcbTransactionKind::Buy { shares: dec!(10), cost: usd_money!(2_000) } },
+            AcbTransaction { date: date(2024, 3, 5), kind: AcbTransactionKind::Sell { shares: dec!(10), proceeds: usd_money!(2_500) } },
+        ];
+
+        let mut lots = TaxLots::new("NVDA");
+        let mut acb = AdjustedCostBase::new("NVDA");
+        let mut us_gain = usd_money!(0);
+        for transaction in transactions.iter() {
+            for disposition in lots.record(transaction, &LotSelection::HighestInFirstOut, &exchange).unwrap() {
+                us_gain = us_gain + disposition.gain;
+            }
+            acb.record(transaction, &exchange).unwrap();
+        }
+
+        assert_eq!(us_gain, usd_money!(500));
+        assert_eq!(acb.net_gain_for_year(2024), cad_money!(1_250));
+    }
+}
@@ -0,0 +1,36 @@
+mod amt;
+mod capital_losses;
+mod equity;
+mod estimated_tax;
+mod federal;
+mod fica;
+mod foreign_tax_credit;
+mod jurisdiction;
+mod lots;
+mod qsbs;
+mod retirement;
+pub mod state;
+mod wash_sale;
+mod withholding;
+
+pub use amt::*;
+pub use capital_losses::*;
+pub use equity::*;
+pub use estimated_tax::*;
+pub use federal::*;
+pub use fica::*;
+pub use foreign_tax_credit::*;
+pub use jurisdiction::*;
+pub use lots::*;
+pub use qsbs::*;
+pub use retirement::*;
+pub use wash_sale::*;
+pub use withholding::*;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum FilingStatus {
+    Single,
+    MarriedFilingJointly,
+    MarriedFilingSeparately,
+    HeadOfHousehold,
+}
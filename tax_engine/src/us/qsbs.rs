@@ -0,0 +1,252 @@
+use chrono::{Months, NaiveDate};
+use rust_decimal::prelude::*;
+use rust_decimal_macros::*;
+use simple_money::*;
+use std::collections::HashMap;
+use crate::TaxError;
+use super::{amount_over, is_long_term, UsIncome};
+
+/// Share of excluded gain that is a preference item for the AMT when less
+/// than the whole gain is excluded.
+const AMT_PREFERENCE_RATE: Decimal = dec!(0.07);
+
+fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(year, month, day).unwrap()
+}
+
+/// A sale of qualified small business stock of one issuer.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct QsbsSale {
+    pub issuer: String,
+    pub acquisition_date: NaiveDate,
+    pub sale_date: NaiveDate,
+    pub adjusted_basis: Money,
+    pub proceeds: Money,
+}
+
+impl QsbsSale {
+    fn gain(&self) -> Money {
+        self.proceeds - self.adjusted_basis
+    }
+
+    /// Stock issued after July 4, 2025 earns a partial exclusion after three
+    /// years. Older stock must be held more than five years, and the rate
+    /// depends on when it was issued.
+    pub fn exclusion_rate(&self) -> Decimal {
+        if self.acquisition_date > date(2025, 7, 4) {
+            if self.sale_date >= self.acquisition_date + Months::new(60) {
+                dec!(1)
+            }else if self.sale_date >= self.acquisition_date + Months::new(48) {
+                dec!(0.75)
+            }else if self.sale_date >= self.acquisition_date + Months::new(36) {
+                dec!(0.5)
+            }else{
+                dec!(0)
+            }
+        }else if self.sale_date <= self.acquisition_date + Months::new(60) {
+            dec!(0)
+        }else if self.acquisition_date > date(2010, 9, 27) {
+            dec!(1)
+        }else if self.acquisition_date > date(2009, 2, 17) {
+            dec!(0.75)
+        }else{
+            dec!(0.5)
+        }
+    }
+
+    /// The per-issuer dollar limit, raised to $15M for stock issued after
+    /// July 4, 2025.
+    fn dollar_limit(&self) -> Money {
+        if self.acquisition_date > date(2025, 7, 4) {
+            usd_money!(15_000_000)
+        }else{
+            usd_money!(10_000_000)
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct QsbsExclusion {
+    pub gain: Money,
+    /// Gain taken into account under the per-issuer limit, before the
+    /// exclusion rate.
+    pub eligible_gain: Money,
+    pub excluded_gain: Money,
+    pub taxable_gain: Money,
+    /// Added to AMTI as an exclusion item.
+    pub amt_preference: Money,
+    long_term: bool,
+}
+
+impl QsbsExclusion {
+    /// Adds the gain that was not excluded to a year's `UsIncome`.
+    pub fn apply_to(&self, income: &mut UsIncome) {
+        if self.long_term {
+            income.long_term_capital_gains = income.long_term_capital_gains + self.taxable_gain;
+        }else{
+            income.short_term_capital_gains = income.short_term_capital_gains + self.taxable_gain;
+        }
+    }
+}
+
+/// A taxpayer's §1202 exclusions, tracked per issuer across years.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct QualifiedSmallBusinessStock {
+    eligible_by_issuer: HashMap<String, Money>,
+}
+
+impl QualifiedSmallBusinessStock {
+    pub fn new() -> QualifiedSmallBusinessStock {
+        QualifiedSmallBusinessStock { eligible_by_issuer: HashMap::new() }
+    }
+
+    /// Gain on an issuer's stock taken into account in earlier years, which
+    /// uses up its dollar limit.
+    pub fn eligible_gain_to_date(&self, issuer: &str) -> Money {
+        self.eligible_by_issuer.get(issuer).copied().unwrap_or(usd_money!(0))
+    }
+
+    /// Excludes gain on one tax year's sales, in the order given, and records
+    /// the eligible gain against each issuer.
+    ///
+    /// Each issuer's gain taken into account in the year is capped at the
+    /// greater of the dollar limit less eligible gain in earlier years, or ten
+    /// times the basis of that issuer's stock sold in the year. The exclusion
+    /// rate then applies to the capped gain.
+    pub fn exclude(&mut self, sales: &[QsbsSale]) -> Result<Vec<QsbsExclusion>, TaxError> {
+        if sales.iter().any(|sale| sale.adjusted_basis.currency != Currency::USD || sale.proceeds.currency != Currency::USD) {
+            return Err(TaxError::MismatchedCurrencies);
+        }
+
+        let mut basis_by_issuer: HashMap<&str, Money> = HashMap::new();
+        for sale in sales.iter().filter(|sale| !sale.exclusion_rate().is_zero()) {
+            let basis = basis_by_issuer.entry(sale.issuer.as_str()).or_insert(usd_money!(0));
+            *basis = *basis + sale.adjusted_basis;
+        }
+
+        let mut eligible_this_year: HashMap<&str, Money> = HashMap::new();
+        let mut exclusions = Vec::with_capacity(sales.len());
+        for sale in sales.iter() {
+            let gain = sale.gain();
+            let rate = sale.exclusion_rate();
+            let prior = self.eligible_gain_to_date(&sale.issuer);
+            let this_year = eligible_this_year.entry(sale.issuer.as_str()).or_insert(usd_money!(0));
+
+            let basis_limit = basis_by_issuer.get(sale.issuer.as_str()).copied().unwrap_or(usd_money!(0)) * dec!(10);
+            let remaining = amount_over(sale.dollar_limit(), prior + *this_year)
+                .max(amount_over(basis_limit, *this_year));
+            let eligible_gain = if rate.is_zero() {
+                usd_money!(0)
+            }else{
+                amount_over(gain, usd_money!(0)).min(remaining)
+            };
+            *this_year = *this_year + eligible_gain;
+            let excluded_gain = eligible_gain * rate;
+
+            let amt_preference = if rate < dec!(1) {
+                excluded_gain * AMT_PREFERENCE_RATE
+            }else{
+                usd_money!(0)
+            };
+            exclusions.push(QsbsExclusion {
+                gain,
+                eligible_gain,
+                excluded_gain,
+                taxable_gain: gain - excluded_gain,
+                amt_preference,
+                long_term: is_long_term(sale.acquisition_date, sale.sale_date),
+            });
+        }
+
+        for (issuer, eligible) in eligible_this_year {
+            let total = self.eligible_by_issuer.entry(issuer.to_string()).or_insert(usd_money!(0));
+            *total = *total + eligible;
+        }
+
+        Ok(exclusions)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn founder_sale(sale_date: NaiveDate, adjusted_basis: Money, proceeds: Money) -> QsbsSale {
+        QsbsSale {
+            issuer: "Startup Inc".to_string(),
+            acquisition_date: date(2015, 1, 1),
+            sale_date,
+            adjusted_basis,
+            proceeds,
+        }
+    }
+
+    #[test]
+    fn dollar_limit_then_ten_times_basis() {
+        let mut qsbs = QualifiedSmallBusinessStock::new();
+        let exit = founder_sale(date(2024, 6, 1), usd_money!(10_000), usd_money!(25_000_000));
+        let exclusions = qsbs.exclude(&[exit]).unwrap();
+        assert_eq!(exclusions[0].excluded_gain, usd_money!(10_000_000));
+        assert_eq!(exclusions[0].taxable_gain, usd_money!(14_990_000));
+        assert_eq!(qsbs.eligible_gain_to_date("Startup Inc"), usd_money!(10_000_000));
+
+        let mut income = UsIncome::from_wages(usd_money!(0));
+        exclusions[0].apply_to(&mut income);
+        assert_eq!(income.long_term_capital_gains, usd_money!(14_990_000));
+
+        // The dollar limit is used up, but basis sold this year still shelters
+        // ten times itself.
+        let later = founder_sale(date(2025, 2, 1), usd_money!(1_000_000), usd_money!(5_000_000));
+        let exclusions = qsbs.exclude(&[later]).unwrap();
+        assert_eq!(exclusions[0].excluded_gain, usd_money!(4_000_000));
+        assert_eq!(qsbs.eligible_gain_to_date("Startup Inc"), usd_money!(14_000_000));
+    }
+
+    #[test]
+    fn holding_period_and_exclusion_rates() {
+        let five_years = QsbsSale {
+            acquisition_date: date(2020, 3, 1),
+            ..founder_sale(date(2025, 3, 1), usd_money!(0), usd_money!(0))
+        };
+        assert_eq!(five_years.exclusion_rate(), dec!(0));
+        assert_eq!(QsbsSale { sale_date: date(2025, 3, 2), ..five_years.clone() }.exclusion_rate(), dec!(1));
+
+        let pre_2009 = QsbsSale { acquisition_date: date(2008, 5, 1), ..five_years.clone() };
+        assert_eq!(pre_2009.exclusion_rate(), dec!(0.5));
+
+        let four_years = QsbsSale {
+            acquisition_date: date(2025, 8, 1),
+            sale_date: date(2029, 8, 1),
+            ..five_years.clone()
+        };
+        assert_eq!(four_years.exclusion_rate(), dec!(0.75));
+    }
+
+    #[test]
+    fn partial_exclusion_is_an_amt_preference() {
+        let mut qsbs = QualifiedSmallBusinessStock::new();
+        let sale = QsbsSale {
+            acquisition_date: date(2025, 8, 1),
+            ..founder_sale(date(2029, 1, 15), usd_money!(100_000), usd_money!(2_100_000))
+        };
+        let exclusions = qsbs.exclude(&[sale]).unwrap();
+        assert_eq!(exclusions[0].excluded_gain, usd_money!(1_000_000));
+        assert_eq!(exclusions[0].taxable_gain, usd_money!(1_000_000));
+        assert_eq!(exclusions[0].amt_preference, usd_money!(70_000));
+    }
+
+    #[test]
+    fn dollar_limit_caps_gain_before_the_exclusion_rate() {
+        let mut qsbs = QualifiedSmallBusinessStock::new();
+        let sale = QsbsSale {
+            acquisition_date: date(2008, 5, 1),
+            ..founder_sale(date(2024, 6, 1), usd_money!(10_000), usd_money!(30_010_000))
+        };
+        let exclusions = qsbs.exclude(&[sale]).unwrap();
+        assert_eq!(exclusions[0].eligible_gain, usd_money!(10_000_000));
+        assert_eq!(exclusions[0].excluded_gain, usd_money!(5_000_000));
+        assert_eq!(exclusions[0].taxable_gain, usd_money!(25_000_000));
+        assert_eq!(exclusions[0].amt_preference, usd_money!(350_000));
+        assert_eq!(qsbs.eligible_gain_to_date("Startup Inc"), usd_money!(10_000_000));
+    }
+}
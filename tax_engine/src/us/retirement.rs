@@ -0,0 +1,501 @@
+use rust_decimal::prelude::*;
+use rust_decimal_macros::*;
+use simple_money::*;
+use crate::TaxError;
+use super::{amount_over, FilingStatus, UsIncome};
+
+/// Age by the end of the year at which catch-up contributions to a 401(k) or
+/// IRA are allowed.
+const CATCH_UP_AGE: u32 = 50;
+
+/// Ages by the end of the year that get the larger 401(k) catch-up added by
+/// SECURE 2.0 from 2025.
+const SUPER_CATCH_UP_AGES: std::ops::RangeInclusive<u32> = 60..=63;
+
+/// Age by the end of the year at which the HSA catch-up is allowed.
+const HSA_CATCH_UP_AGE: u32 = 55;
+
+/// Phased-out IRA limits are rounded up to a multiple of this.
+const PHASE_OUT_ROUNDING: Decimal = dec!(10);
+
+/// A phased-out IRA limit that isn't zero is at least this much.
+const PHASE_OUT_MINIMUM: Money = Money { amount: dec!(200), currency: Currency::USD };
+
+/// Dollar limits on contributions to tax-advantaged accounts for a year.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RetirementLimits {
+    pub year: u32,
+    /// §402(g) limit on pre-tax and Roth elective deferrals.
+    pub elective_deferral: Money,
+    pub elective_deferral_catch_up: Money,
+    /// The catch-up for ages 60 to 63, the same as the regular one before
+    /// 2025.
+    pub elective_deferral_catch_up_60_to_63: Money,
+    /// §415(c) limit on all additions to a defined contribution plan,
+    /// excluding catch-up contributions.
+    pub annual_additions: Money,
+    pub ira: Money,
+    pub ira_catch_up: Money,
+    pub hsa_self_only: Money,
+    pub hsa_family: Money,
+    pub hsa_catch_up: Money,
+}
+
+impl RetirementLimits {
+    pub fn for_year(year: u32) -> Result<RetirementLimits, TaxError> {
+        let (elective_deferral, annual_additions, ira, hsa_self_only, hsa_family) = match year {
+            2023 => (usd_money!(22_500), usd_money!(66_000), usd_money!(6_500), usd_money!(3_850), usd_money!(7_750)),
+            2024 => (usd_money!(23_000), usd_money!(69_000), usd_money!(7_000), usd_money!(4_150), usd_money!(8_300)),
+            2025 => (usd_money!(23_500), usd_money!(70_000), usd_money!(7_000), usd_money!(4_300), usd_money!(8_550)),
+            _ => return Err(TaxError::UnsupportedYear(year)),
+        };
+
+        Ok(RetirementLimits {
+            year,
+            elective_deferral,
+            elective_deferral_catch_up: usd_money!(7_500),
+            elective_deferral_catch_up_60_to_63: if year >= 2025 { usd_money!(11_250) }else{ usd_money!(7_500) },
+            annual_additions,
+            ira,
+            ira_catch_up: usd_money!(1_000),
+            hsa_self_only,
+            hsa_family,
+            hsa_catch_up: usd_money!(1_000),
+        })
+    }
+
+    /// The catch-up part of the deferral limit for someone `age` at the end
+    /// of the year.
+    fn deferral_catch_up(&self, age: u32) -> Money {
+        if SUPER_CATCH_UP_AGES.contains(&age) {
+            self.elective_deferral_catch_up_60_to_63
+        }else if age >= CATCH_UP_AGE {
+            self.elective_deferral_catch_up
+        }else{
+            usd_money!(0)
+        }
+    }
+
+    pub fn elective_deferral_limit(&self, age: u32) -> Money {
+        self.elective_deferral + self.deferral_catch_up(age)
+    }
+
+    /// The combined limit on traditional and Roth IRA contributions.
+    pub fn ira_limit(&self, age: u32) -> Money {
+        if age >= CATCH_UP_AGE {
+            self.ira + self.ira_catch_up
+        }else{
+            self.ira
+        }
+    }
+
+    pub fn hsa_limit(&self, coverage: HsaCoverage, age: u32) -> Money {
+        let limit = match coverage {
+            HsaCoverage::SelfOnly => self.hsa_self_only,
+            HsaCoverage::Family => self.hsa_family,
+        };
+        if age >= HSA_CATCH_UP_AGE {
+            limit + self.hsa_catch_up
+        }else{
+            limit
+        }
+    }
+}
+
+/// High deductible health plan coverage, which is required to contribute
+/// to an HSA.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum HsaCoverage {
+    SelfOnly,
+    Family,
+}
+
+/// Whether the taxpayer or their spouse is an active participant in a
+/// workplace retirement plan, which limits the traditional IRA deduction.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct WorkplacePlanCoverage {
+    pub taxpayer_covered: bool,
+    pub spouse_covered: bool,
+}
+
+/// Start and width of the modified AGI range over which a traditional IRA
+/// deduction phases out.
+fn ira_deduction_phase_out(
+    year: u32,
+    filing_status: FilingStatus,
+    coverage: WorkplacePlanCoverage,
+) -> Result<Option<(Money, Money)>, TaxError> {
+    use FilingStatus::*;
+
+    if !coverage.taxpayer_covered && !coverage.spouse_covered {
+        return Ok(None);
+    }
+    if filing_status == MarriedFilingSeparately {
+        return Ok(Some((usd_money!(0), usd_money!(10_000))));
+    }
+    if !coverage.taxpayer_covered && filing_status != MarriedFilingJointly {
+        return Ok(None);
+    }
+
+    let start = match (year, filing_status, coverage.taxpayer_covered) {
+        (2023, MarriedFilingJointly, true) => usd_money!(116_000),
+        (2023, MarriedFilingJointly, false) => usd_money!(218_000),
+        (2023, _, _) => usd_money!(73_000),
+        (2024, MarriedFilingJointly, true) => usd_money!(123_000),
+        (2024, MarriedFilingJointly, false) => usd_money!(230_000),
+        (2024, _, _) => usd_money!(77_000),
+        (2025, MarriedFilingJointly, true) => usd_money!(126_000),
+        (2025, MarriedFilingJointly, false) => usd_money!(236_000),
+        (2025, _, _) => usd_money!(79_000),
+        _ => return Err(TaxError::UnsupportedYear(year)),
+    };
+    let width = if filing_status == MarriedFilingJointly && coverage.taxpayer_covered {
+        usd_money!(20_000)
+    }else{
+        usd_money!(10_000)
+    };
+
+    Ok(Some((start, width)))
+}
+
+/// Start and width of the modified AGI range over which Roth IRA
+/// contributions phase out.
+fn roth_ira_phase_out(year: u32, filing_status: FilingStatus) -> Result<(Money, Money), TaxError> {
+    use FilingStatus::*;
+
+    let phase_out = match (year, filing_status) {
+        (2023..=2025, MarriedFilingSeparately) => (usd_money!(0), usd_money!(10_000)),
+        (2023, MarriedFilingJointly) => (usd_money!(218_000), usd_money!(10_000)),
+        (2023, _) => (usd_money!(138_000), usd_money!(15_000)),
+        (2024, MarriedFilingJointly) => (usd_money!(230_000), usd_money!(10_000)),
+        (2024, _) => (usd_money!(146_000), usd_money!(15_000)),
+        (2025, MarriedFilingJointly) => (usd_money!(236_000), usd_money!(10_000)),
+        (2025, _) => (usd_money!(150_000), usd_money!(15_000)),
+        _ => return Err(TaxError::UnsupportedYear(year)),
+    };
+
+    Ok(phase_out)
+}
+
+/// Reduces `limit` in proportion to how far `modified_agi` is into the
+/// phase-out range, rounding up to the next $10 with a $200 floor.
+fn phase_out_limit(limit: Money, modified_agi: Money, (start, width): (Money, Money)) -> Money {
+    let end = start + width;
+    if modified_agi <= start {
+        return limit;
+    }
+    if modified_agi >= end {
+        return usd_money!(0);
+    }
+    let reduced = limit.amount * (end - modified_agi).amount / width.amount;
+    let rounded = (reduced / PHASE_OUT_ROUNDING).ceil() * PHASE_OUT_ROUNDING;
+    Money { amount: rounded, currency: Currency::USD }.max(PHASE_OUT_MINIMUM)
+}
+
+/// A year's contributions by one taxpayer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RetirementContributions {
+    pub pre_tax_deferrals: Money,
+    pub roth_deferrals: Money,
+    pub employer_contributions: Money,
+    /// After-tax 401(k) contributions, usually converted to Roth in the
+    /// mega backdoor.
+    pub after_tax_contributions: Money,
+    pub traditional_ira: Money,
+    pub roth_ira: Money,
+    /// HSA contributions the employee made directly, which are deductible.
+    pub employee_hsa: Money,
+    /// HSA contributions by the employer, including salary reductions
+    /// through a cafeteria plan. They are already left out of wages, so they
+    /// only count against the limit.
+    pub employer_hsa: Money,
+}
+
+impl Default for RetirementContributions {
+    fn default() -> Self {
+        RetirementContributions {
+            pre_tax_deferrals: usd_money!(0),
+            roth_deferrals: usd_money!(0),
+            employer_contributions: usd_money!(0),
+            after_tax_contributions: usd_money!(0),
+            traditional_ira: usd_money!(0),
+            roth_ira: usd_money!(0),
+            employee_hsa: usd_money!(0),
+            employer_hsa: usd_money!(0),
+        }
+    }
+}
+
+impl RetirementContributions {
+    fn validate(&self) -> Result<(), TaxError> {
+        let amounts = [
+            self.pre_tax_deferrals,
+            self.roth_deferrals,
+            self.employer_contributions,
+            self.after_tax_contributions,
+            self.traditional_ira,
+            self.roth_ira,
+            self.employee_hsa,
+            self.employer_hsa,
+        ];
+        if amounts.iter().all(|money| money.currency == Currency::USD) {
+            Ok(())
+        }else{
+            Err(TaxError::MismatchedCurrencies)
+        }
+    }
+}
+
+/// How a year's contributions measure up against the limits.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RetirementContributionSummary {
+    /// Pre-tax deferrals within the §402(g) limit, excluded from wages.
+    pub excludable_deferrals: Money,
+    /// Deferrals over the §402(g) limit, taxable unless returned by April 15.
+    pub excess_deferrals: Money,
+    /// After-tax contributions the §415(c) limit still leaves room for.
+    pub after_tax_room: Money,
+    /// Plan additions over the §415(c) limit.
+    pub excess_annual_additions: Money,
+    pub deductible_ira: Money,
+    /// Traditional IRA contributions that add to basis on Form 8606.
+    pub nondeductible_ira: Money,
+    pub roth_ira_limit: Money,
+    /// IRA contributions over the combined or Roth limit, subject to the 6%
+    /// excise tax until withdrawn.
+    pub excess_ira: Money,
+    /// Employee HSA contributions within what the employer's contributions
+    /// leave of the limit.
+    pub hsa_deduction: Money,
+    /// Employee and employer HSA contributions over the limit.
+    pub excess_hsa: Money,
+}
+
+impl RetirementContributionSummary {
+    /// Removes excludable deferrals from wages and takes the IRA and HSA
+    /// deductions above the line. `income.wages` must be gross pay before
+    /// any deferrals.
+    pub fn apply_to(&self, income: &mut UsIncome) {
+        income.wages = income.wages - self.excludable_deferrals;
+        income.other_ordinary_income = income.other_ordinary_income - self.deductible_ira - self.hsa_deduction;
+    }
+}
+
+/// The retirement and health savings rules for one tax year and filing
+/// status.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct UsRetirementPlans {
+    pub filing_status: FilingStatus,
+    pub limits: RetirementLimits,
+}
+
+impl UsRetirementPlans {
+    pub fn new(year: u32, filing_status: FilingStatus) -> Result<UsRetirementPlans, TaxError> {
+        Ok(UsRetirementPlans { filing_status, limits: RetirementLimits::for_year(year)? })
+    }
+
+    pub fn traditional_ira_deduction_limit(
+        &self,
+        modified_agi: Money,
+        age: u32,
+        coverage: WorkplacePlanCoverage,
+    ) -> Result<Money, TaxError> {
+        let limit = self.limits.ira_limit(age);
+        match ira_deduction_phase_out(self.limits.year, self.filing_status, coverage)? {
+            Some(phase_out) => Ok(phase_out_limit(limit, modified_agi, phase_out)),
+            None => Ok(limit),
+        }
+    }
+
+    pub fn roth_ira_limit(&self, modified_agi: Money, age: u32) -> Result<Money, TaxError> {
+        let phase_out = roth_ira_phase_out(self.limits.year, self.filing_status)?;
+        Ok(phase_out_limit(self.limits.ira_limit(age), modified_agi, phase_out))
+    }
+
+    /// Checks a year's contributions against every limit. `age` is the age at
+    /// the end of the year, `modified_agi` is computed before the IRA
+    /// deduction, and `hsa_coverage` is `None` without an HDHP.
+    pub fn evaluate(
+        &self,
+        contributions: &RetirementContributions,
+        age: u32,
+        modified_agi: Money,
+        coverage: WorkplacePlanCoverage,
+        hsa_coverage: Option<HsaCoverage>,
+    ) -> Result<RetirementContributionSummary, TaxError> {
+        contributions.validate()?;
+        let zero = usd_money!(0);
+        let limits = &self.limits;
+
+        let deferrals = contributions.pre_tax_deferrals + contributions.roth_deferrals;
+        let excess_deferrals = amount_over(deferrals, limits.elective_deferral_limit(age));
+        let excludable_deferrals = amount_over(contributions.pre_tax_deferrals, excess_deferrals);
+        let catch_up = amount_over(deferrals - excess_deferrals, limits.elective_deferral);
+        let annual_additions = deferrals - excess_deferrals - catch_up + contributions.employer_contributions;
+        let after_tax_room = amount_over(limits.annual_additions, annual_additions);
+        let excess_annual_additions = amount_over(annual_additions + contributions.after_tax_contributions, limits.annual_additions);
+
+        let ira_limit = limits.ira_limit(age);
+        let traditional_allowed = contributions.traditional_ira.min(ira_limit);
+        let deductible_ira = traditional_allowed.min(self.traditional_ira_deduction_limit(modified_agi, age, coverage)?);
+        let roth_ira_limit = self.roth_ira_limit(modified_agi, age)?.min(ira_limit - traditional_allowed);
+        let excess_ira = amount_over(contributions.traditional_ira, ira_limit)
+            + amount_over(contributions.roth_ira, roth_ira_limit);
+
+        let hsa_limit = hsa_coverage.map_or(zero, |coverage| limits.hsa_limit(coverage, age));
+        let hsa_deduction = contributions.employee_hsa.min(amount_over(hsa_limit, contributions.employer_hsa));
+
+        Ok(RetirementContributionSummary {
+            excludable_deferrals,
+            excess_deferrals,
+            after_tax_room,
+            excess_annual_additions,
+            deductible_ira,
+            nondeductible_ira: traditional_allowed - deductible_ira,
+            roth_ira_limit,
+            excess_ira,
+            hsa_deduction,
+            excess_hsa: amount_over(contributions.employee_hsa + contributions.employer_hsa, hsa_limit),
+        })
+    }
+}
+
+/// A conversion from a traditional IRA to a Roth IRA, split by the pro-rata
+/// rule on Form 8606.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RothConversion {
+    pub taxable: Money,
+    pub nontaxable: Money,
+    /// Nondeductible basis left in the traditional IRAs.
+    pub remaining_basis: Money,
+}
+
+impl RothConversion {
+    /// Adds the taxable part of the conversion to a year's `UsIncome`.
+    pub fn apply_to(&self, income: &mut UsIncome) {
+        income.other_ordinary_income = income.other_ordinary_income + self.taxable;
+    }
+}
+
+/// Splits a backdoor Roth conversion. Basis is spread over every traditional,
+/// SEP and SIMPLE IRA, so pre-tax money still held at the end of the year
+/// makes most of a conversion taxable.
+pub fn roth_conversion(
+    conversion: Money,
+    basis: Money,
+    year_end_traditional_balance: Money,
+) -> Result<RothConversion, TaxError> {
+    if conversion.currency != Currency::USD
+        || basis.currency != Currency::USD
+        || year_end_traditional_balance.currency != Currency::USD
+    {
+        return Err(TaxError::MismatchedCurrencies);
+    }
+    let total = year_end_traditional_balance + conversion;
+    let nontaxable = if total.amount.is_zero() {
+        usd_money!(0)
+    }else{
+        Money { amount: conversion.amount * basis.amount / total.amount, currency: Currency::USD }.min(conversion)
+    };
+
+    Ok(RothConversion {
+        taxable: conversion - nontaxable,
+        nontaxable,
+        remaining_basis: basis - nontaxable,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deferrals_catch_up_and_mega_backdoor_room() {
+        let plans = UsRetirementPlans::new(2024, FilingStatus::Single).unwrap();
+        let contributions = RetirementContributions {
+            pre_tax_deferrals: usd_money!(32_000),
+            employer_contributions: usd_money!(10_000),
+            after_tax_contributions: usd_money!(40_000),
+            ..RetirementContributions::default()
+        };
+
+        let summary = plans
+            .evaluate(&contributions, 52, usd_money!(200_000), WorkplacePlanCoverage::default(), None)
+            .unwrap();
+        assert_eq!(summary.excess_deferrals, usd_money!(1_500));
+        assert_eq!(summary.excludable_deferrals, usd_money!(30_500));
+        assert_eq!(summary.after_tax_room, usd_money!(36_000));
+        assert_eq!(summary.excess_annual_additions, usd_money!(4_000));
+
+        let mut income = UsIncome::from_wages(usd_money!(200_000));
+        summary.apply_to(&mut income);
+        assert_eq!(income.adjusted_gross_income(), usd_money!(169_500));
+    }
+
+    #[test]
+    fn ira_phase_outs_round_up_with_a_floor() {
+        let single = UsRetirementPlans::new(2024, FilingStatus::Single).unwrap();
+        let covered = WorkplacePlanCoverage { taxpayer_covered: true, spouse_covered: false };
+        assert_eq!(single.traditional_ira_deduction_limit(usd_money!(80_000), 40, covered).unwrap(), usd_money!(4_900));
+        assert_eq!(single.traditional_ira_deduction_limit(usd_money!(86_950), 40, covered).unwrap(), usd_money!(200));
+        assert_eq!(single.roth_ira_limit(usd_money!(150_000), 55).unwrap(), usd_money!(5_870));
+
+        let joint = UsRetirementPlans::new(2024, FilingStatus::MarriedFilingJointly).unwrap();
+        let spouse_covered = WorkplacePlanCoverage { taxpayer_covered: false, spouse_covered: true };
+        let contributions = RetirementContributions { traditional_ira: usd_money!(7_000), ..RetirementContributions::default() };
+        let summary = joint
+            .evaluate(&contributions, 40, usd_money!(235_000), spouse_covered, None)
+            .unwrap();
+        assert_eq!(summary.deductible_ira, usd_money!(3_500));
+        assert_eq!(summary.nondeductible_ira, usd_money!(3_500));
+        assert_eq!(summary.roth_ira_limit, usd_money!(0));
+    }
+
+    #[test]
+    fn pro_rata_conversion_and_hsa_limit() {
+        let conversion = roth_conversion(usd_money!(7_000), usd_money!(7_000), usd_money!(63_000)).unwrap();
+        assert_eq!(conversion.nontaxable, usd_money!(700));
+        assert_eq!(conversion.taxable, usd_money!(6_300));
+        assert_eq!(conversion.remaining_basis, usd_money!(6_300));
+
+        let plans = UsRetirementPlans::new(2024, FilingStatus::MarriedFilingJointly).unwrap();
+        let contributions = RetirementContributions { employee_hsa: usd_money!(10_000), ..RetirementContributions::default() };
+        let summary = plans
+            .evaluate(&contributions, 56, usd_money!(150_000), WorkplacePlanCoverage::default(), Some(HsaCoverage::Family))
+            .unwrap();
+        assert_eq!(summary.hsa_deduction, usd_money!(9_300));
+        assert_eq!(summary.excess_hsa, usd_money!(700));
+
+        // Employer money uses up the limit but is never deducted.
+        let contributions = RetirementContributions {
+            employee_hsa: usd_money!(6_000),
+            employer_hsa: usd_money!(3_000),
+            ..RetirementContributions::default()
+        };
+        let summary = plans
+            .evaluate(&contributions, 56, usd_money!(150_000), WorkplacePlanCoverage::default(), Some(HsaCoverage::Family))
+            .unwrap();
+        assert_eq!(summary.hsa_deduction, usd_money!(6_000));
+        assert_eq!(summary.excess_hsa, usd_money!(0));
+
+        let contributions = RetirementContributions { employer_hsa: usd_money!(4_000), ..contributions };
+        let summary = plans
+            .evaluate(&contributions, 56, usd_money!(150_000), WorkplacePlanCoverage::default(), Some(HsaCoverage::Family))
+            .unwrap();
+        assert_eq!(summary.hsa_deduction, usd_money!(5_300));
+        assert_eq!(summary.excess_hsa, usd_money!(700));
+    }
+
+    #[test]
+    fn larger_catch_up_for_ages_60_to_63_from_2025() {
+        let plans = UsRetirementPlans::new(2025, FilingStatus::Single).unwrap();
+        let contributions = RetirementContributions { pre_tax_deferrals: usd_money!(34_750), ..RetirementContributions::default() };
+        let evaluate = |age| plans.evaluate(&contributions, age, usd_money!(200_000), WorkplacePlanCoverage::default(), None).unwrap();
+        assert_eq!(evaluate(61).excess_deferrals, usd_money!(0));
+        assert_eq!(evaluate(64).excess_deferrals, usd_money!(3_750));
+        assert_eq!(evaluate(55).excess_deferrals, usd_money!(3_750));
+
+        let earlier = RetirementLimits::for_year(2024).unwrap();
+        assert_eq!(earlier.elective_deferral_limit(61), usd_money!(30_500));
+    }
+}
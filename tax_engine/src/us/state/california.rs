@@ -0,0 +1,120 @@
+use rust_decimal::prelude::*;
+use rust_decimal_macros::*;
+use simple_money::*;
+use crate::{TaxError, TaxSchedule};
+use crate::us::{
+    amount_over, FilingStatus, IncomeTaxJurisdiction, JurisdictionTax, UsIncome, UsTaxComponent,
+};
+use super::taxable_income;
+
+const CALIFORNIA_RATES: [Decimal; 9] = [
+    dec!(0.01),
+    dec!(0.02),
+    dec!(0.04),
+    dec!(0.06),
+    dec!(0.08),
+    dec!(0.093),
+    dec!(0.103),
+    dec!(0.113),
+    dec!(0.123),
+];
+
+/// The Mental Health Services Tax applies at the same threshold for every
+/// filing status and is not indexed.
+const MENTAL_HEALTH_SERVICES_THRESHOLD: Decimal = dec!(1_000_000);
+const MENTAL_HEALTH_SERVICES_RATE: Decimal = dec!(0.01);
+
+#[derive(Debug, Clone)]
+pub struct California {
+    pub year: u32,
+    pub filing_status: FilingStatus,
+    schedule: TaxSchedule,
+    standard_deduction: Money,
+    sdi_rate: Decimal,
+}
+
+impl California {
+    pub fn new(year: u32, filing_status: FilingStatus) -> Result<California, TaxError> {
+        use FilingStatus::*;
+
+        let (thresholds, standard_deduction) = match (year, filing_status) {
+            (2024, Single) | (2024, MarriedFilingSeparately) => (
+                [dec!(10_756), dec!(25_499), dec!(40_245), dec!(55_866), dec!(70_606), dec!(360_659), dec!(432_787), dec!(721_314)],
+                usd_money!(5_540),
+            ),
+            (2024, MarriedFilingJointly) => (
+                [dec!(21_512), dec!(50_998), dec!(80_490), dec!(111_732), dec!(141_212), dec!(721_318), dec!(865_574), dec!(1_442_628)],
+                usd_money!(11_080),
+            ),
+            (2024, HeadOfHousehold) => (
+                [dec!(21_527), dec!(51_000), dec!(65_744), dec!(81_364), dec!(96_107), dec!(490_493), dec!(588_593), dec!(980_987)],
+                usd_money!(11_080),
+            ),
+            _ => return Err(TaxError::UnsupportedYear(year)),
+        };
+
+        Ok(California {
+            year,
+            filing_status,
+            schedule: TaxSchedule::from_thresholds(Currency::USD, &thresholds, &CALIFORNIA_RATES)?,
+            standard_deduction,
+            // SDI has had no wage ceiling since 2024.
+            sdi_rate: dec!(0.011),
+        })
+    }
+}
+
+impl IncomeTaxJurisdiction for California {
+    fn name(&self) -> &'static str {
+        "California"
+    }
+
+    fn calculate(&self, income: &UsIncome) -> Result<JurisdictionTax, TaxError> {
+        income.validate()?;
+        let taxable_income = taxable_income(
+            income.adjusted_gross_income(),
+            self.standard_deduction,
+            income.itemized_deductions,
+        );
+        let mental_health_threshold = Money { amount: MENTAL_HEALTH_SERVICES_THRESHOLD, currency: Currency::USD };
+
+        Ok(JurisdictionTax {
+            components: vec![
+                (UsTaxComponent::IncomeTax, self.schedule.calculate_tax(taxable_income)),
+                (
+                    UsTaxComponent::MentalHealthServicesTax,
+                    amount_over(taxable_income, mental_health_threshold) * MENTAL_HEALTH_SERVICES_RATE,
+                ),
+                (UsTaxComponent::StateDisabilityInsurance, income.wages * self.sdi_rate),
+            ],
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn single_engineer_in_the_bay_area() {
+        let california = California::new(2024, FilingStatus::Single).unwrap();
+        let tax = california.calculate(&UsIncome::from_wages(usd_money!(200_000))).unwrap();
+
+        assert_eq!(tax.component(UsTaxComponent::IncomeTax), usd_money!(14_627.142));
+        assert_eq!(tax.component(UsTaxComponent::MentalHealthServicesTax), usd_money!(0));
+        assert_eq!(tax.component(UsTaxComponent::StateDisabilityInsurance), usd_money!(2_200));
+    }
+
+    #[test]
+    fn mental_health_services_tax_over_one_million() {
+        let california = California::new(2024, FilingStatus::MarriedFilingJointly).unwrap();
+        let income = UsIncome {
+            long_term_capital_gains: usd_money!(1_111_080),
+            ..UsIncome::from_wages(usd_money!(400_000))
+        };
+        let tax = california.calculate(&income).unwrap();
+
+        assert_eq!(tax.component(UsTaxComponent::MentalHealthServicesTax), usd_money!(5_000));
+        assert_eq!(tax.component(UsTaxComponent::StateDisabilityInsurance), usd_money!(4_400));
+    }
+}
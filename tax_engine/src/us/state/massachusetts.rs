@@ -0,0 +1,101 @@
+use rust_decimal::prelude::*;
+use rust_decimal_macros::*;
+use simple_money::*;
+use crate::TaxError;
+use crate::us::{amount_over, FilingStatus, IncomeTaxJurisdiction, JurisdictionTax, UsIncome, UsTaxComponent};
+
+/// Massachusetts taxes most income at a flat rate after the personal
+/// exemption, short-term capital gains at a higher flat rate, plus the 4% millionaire surtax on taxable income above an
+/// indexed threshold. The threshold applies per return, so married couples
+/// filing jointly share one.
+#[derive(Debug, Clone)]
+pub struct Massachusetts {
+    pub year: u32,
+    pub filing_status: FilingStatus,
+    rate: Decimal,
+    short_term_capital_gains_rate: Decimal,
+    personal_exemption: Money,
+    surtax_rate: Decimal,
+    surtax_threshold: Money,
+}
+
+impl Massachusetts {
+    pub fn new(year: u32, filing_status: FilingStatus) -> Result<Massachusetts, TaxError> {
+        let surtax_threshold = match year {
+            2023 => usd_money!(1_000_000),
+            2024 => usd_money!(1_053_750),
+            _ => return Err(TaxError::UnsupportedYear(year)),
+        };
+        let personal_exemption = match filing_status {
+            FilingStatus::Single | FilingStatus::MarriedFilingSeparately => usd_money!(4_400),
+            FilingStatus::HeadOfHousehold => usd_money!(6_800),
+            FilingStatus::MarriedFilingJointly => usd_money!(8_800),
+        };
+
+        Ok(Massachusetts {
+            year,
+            filing_status,
+            rate: dec!(0.05),
+            short_term_capital_gains_rate: dec!(0.085),
+            personal_exemption,
+            surtax_rate: dec!(0.04),
+            surtax_threshold,
+        })
+    }
+}
+
+impl IncomeTaxJurisdiction for Massachusetts {
+    fn name(&self) -> &'static str {
+        "Massachusetts"
+    }
+
+    fn calculate(&self, income: &UsIncome) -> Result<JurisdictionTax, TaxError> {
+        income.validate()?;
+        let short_term_gains = amount_over(income.short_term_capital_gains, usd_money!(0));
+        let taxable_income = amount_over(income.adjusted_gross_income() - short_term_gains, self.personal_exemption);
+        let income_tax = taxable_income * self.rate + short_term_gains * self.short_term_capital_gains_rate;
+
+        Ok(JurisdictionTax {
+            components: vec![
+                (UsTaxComponent::IncomeTax, income_tax),
+                (
+                    UsTaxComponent::Surtax,
+                    amount_over(taxable_income + short_term_gains, self.surtax_threshold) * self.surtax_rate,
+                ),
+            ],
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn millionaire_surtax() {
+        let massachusetts = Massachusetts::new(2024, FilingStatus::MarriedFilingJointly).unwrap();
+        let income = UsIncome {
+            long_term_capital_gains: usd_money!(1_762_550),
+            ..UsIncome::from_wages(usd_money!(300_000))
+        };
+        let tax = massachusetts.calculate(&income).unwrap();
+
+        assert_eq!(tax.component(UsTaxComponent::IncomeTax), usd_money!(102_687.50));
+        assert_eq!(tax.component(UsTaxComponent::Surtax), usd_money!(40_000));
+
+        let below = massachusetts.calculate(&UsIncome::from_wages(usd_money!(200_000))).unwrap();
+        assert_eq!(below.component(UsTaxComponent::Surtax), usd_money!(0));
+    }
+
+    #[test]
+    fn short_term_gains_at_higher_rate() {
+        let massachusetts = Massachusetts::new(2024, FilingStatus::Single).unwrap();
+        let income = UsIncome {
+            short_term_capital_gains: usd_money!(10_000),
+            ..UsIncome::from_wages(usd_money!(104_400))
+        };
+        let tax = massachusetts.calculate(&income).unwrap();
+
+        assert_eq!(tax.component(UsTaxComponent::IncomeTax), usd_money!(5_850));
+    }
+}
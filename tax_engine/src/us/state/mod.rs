@@ -0,0 +1,24 @@
+//! State income taxes. Each state implements `IncomeTaxJurisdiction` so it can
+//! be combined with `FederalIncomeTax` for a resident's return.
+
+mod california;
+mod massachusetts;
+mod new_york;
+mod washington;
+
+pub use california::*;
+pub use massachusetts::*;
+pub use new_york::*;
+pub use washington::*;
+
+use simple_money::*;
+use super::amount_over;
+
+fn taxable_income(adjusted_gross_income: Money, standard_deduction: Money, itemized_deductions: Option<Money>) -> Money {
+    let deduction = match itemized_deductions {
+        Some(itemized) => itemized.max(standard_deduction),
+        None => standard_deduction,
+    };
+
+    amount_over(adjusted_gross_income, deduction)
+}
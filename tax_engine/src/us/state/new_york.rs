@@ -0,0 +1,186 @@
+use rust_decimal::prelude::*;
+use rust_decimal_macros::*;
+use simple_money::*;
+use crate::{TaxError, TaxSchedule};
+use crate::us::{FilingStatus, IncomeTaxJurisdiction, JurisdictionTax, UsIncome, UsTaxComponent};
+use super::taxable_income;
+
+const NEW_YORK_RATES: [Decimal; 9] = [
+    dec!(0.04),
+    dec!(0.045),
+    dec!(0.0525),
+    dec!(0.055),
+    dec!(0.06),
+    dec!(0.0685),
+    dec!(0.0965),
+    dec!(0.103),
+    dec!(0.109),
+];
+
+const NEW_YORK_CITY_RATES: [Decimal; 4] = [
+    dec!(0.03078),
+    dec!(0.03762),
+    dec!(0.03819),
+    dec!(0.03876),
+];
+
+/// New York AGI above which the benefit of the lower brackets is recaptured.
+const RECAPTURE_THRESHOLD: Decimal = dec!(107_650);
+
+/// Each recapture is phased in over this much New York AGI.
+const RECAPTURE_PHASE_IN: Decimal = dec!(50_000);
+
+/// Above this New York AGI all taxable income is taxed at the top rate.
+const TOP_RATE_THRESHOLD: Decimal = dec!(25_000_000);
+
+/// New York State income tax, plus the New York City resident tax when the
+/// taxpayer lived in the city for the year. State tax includes the
+/// supplemental tax that recaptures the benefit of the lower brackets once New
+/// York AGI passes $107,650; the city has no such recapture.
+#[derive(Debug, Clone)]
+pub struct NewYork {
+    pub year: u32,
+    pub filing_status: FilingStatus,
+    pub new_york_city_resident: bool,
+    schedule: TaxSchedule,
+    thresholds: [Decimal; 8],
+    /// Bracket whose rate applies to all taxable income up to the top of it
+    /// once the first recapture is fully phased in.
+    first_recapture_bracket: usize,
+    city_schedule: TaxSchedule,
+    standard_deduction: Money,
+}
+
+impl NewYork {
+    pub fn new(year: u32, filing_status: FilingStatus, new_york_city_resident: bool) -> Result<NewYork, TaxError> {
+        use FilingStatus::*;
+
+        let (thresholds, city_thresholds, standard_deduction, first_recapture_bracket) = match (year, filing_status) {
+            (2024, Single) | (2024, MarriedFilingSeparately) => (
+                [dec!(8_500), dec!(11_700), dec!(13_900), dec!(80_650), dec!(215_400), dec!(1_077_550), dec!(5_000_000), dec!(25_000_000)],
+                [dec!(12_000), dec!(25_000), dec!(50_000)],
+                usd_money!(8_000),
+                4,
+            ),
+            (2024, MarriedFilingJointly) => (
+                [dec!(17_150), dec!(23_600), dec!(27_900), dec!(161_550), dec!(323_200), dec!(2_155_350), dec!(5_000_000), dec!(25_000_000)],
+                [dec!(21_600), dec!(45_000), dec!(90_000)],
+                usd_money!(16_050),
+                3,
+            ),
+            (2024, HeadOfHousehold) => (
+                [dec!(12_800), dec!(17_650), dec!(20_900), dec!(107_650), dec!(269_300), dec!(1_616_450), dec!(5_000_000), dec!(25_000_000)],
+                [dec!(14_400), dec!(30_000), dec!(60_000)],
+                usd_money!(11_200),
+                3,
+            ),
+            _ => return Err(TaxError::UnsupportedYear(year)),
+        };
+
+        Ok(NewYork {
+            year,
+            filing_status,
+            new_york_city_resident,
+            schedule: TaxSchedule::from_thresholds(Currency::USD, &thresholds, &NEW_YORK_RATES)?,
+            thresholds,
+            first_recapture_bracket,
+            city_schedule: TaxSchedule::from_thresholds(Currency::USD, &city_thresholds, &NEW_YORK_CITY_RATES)?,
+            standard_deduction,
+        })
+    }
+
+    /// State tax as on the Tax Computation Worksheets. Once New York AGI
+    /// passes $107,650, the benefit of the brackets below the one taxable
+    /// income falls in is phased out over the next $50,000 of AGI, until all
+    /// of it is taxed at that bracket's rate.
+    fn state_tax(&self, adjusted_gross_income: Money, taxable_income: Money) -> Money {
+        let tax = self.schedule.calculate_tax(taxable_income);
+        let agi = adjusted_gross_income.amount;
+        if agi <= RECAPTURE_THRESHOLD {
+            return tax;
+        }
+        if agi > TOP_RATE_THRESHOLD {
+            return taxable_income * NEW_YORK_RATES[NEW_YORK_RATES.len() - 1];
+        }
+
+        let phase_in = |start: Decimal| ((agi - start) / RECAPTURE_PHASE_IN).round_dp(4).max(dec!(0)).min(dec!(1));
+        let bracket = self.thresholds.iter().filter(|threshold| taxable_income.amount > **threshold).count();
+        if bracket <= self.first_recapture_bracket {
+            let flat_tax = taxable_income * NEW_YORK_RATES[self.first_recapture_bracket];
+            return tax + (flat_tax - tax) * phase_in(RECAPTURE_THRESHOLD);
+        }
+
+        // Higher brackets keep the recapture of the bracket below in full and
+        // phase in the difference between the two rates on income up to here.
+        let start = Money { amount: self.thresholds[bracket - 1], currency: Currency::USD };
+        let recapture_base = start * NEW_YORK_RATES[bracket - 1] - self.schedule.calculate_tax(start);
+        let incremental_benefit = start * (NEW_YORK_RATES[bracket] - NEW_YORK_RATES[bracket - 1]);
+        tax + recapture_base + incremental_benefit * phase_in(start.amount)
+    }
+}
+
+impl IncomeTaxJurisdiction for NewYork {
+    fn name(&self) -> &'static str {
+        if self.new_york_city_resident {
+            "New York City"
+        }else{
+            "New York"
+        }
+    }
+
+    fn calculate(&self, income: &UsIncome) -> Result<JurisdictionTax, TaxError> {
+        income.validate()?;
+        let adjusted_gross_income = income.adjusted_gross_income();
+        let taxable_income = taxable_income(
+            adjusted_gross_income,
+            self.standard_deduction,
+            income.itemized_deductions,
+        );
+
+        let mut components = vec![(UsTaxComponent::IncomeTax, self.state_tax(adjusted_gross_income, taxable_income))];
+        if self.new_york_city_resident {
+            components.push((UsTaxComponent::NewYorkCityTax, self.city_schedule.calculate_tax(taxable_income)));
+        }
+
+        Ok(JurisdictionTax { components })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::round_cents;
+
+    #[test]
+    fn state_and_city_tax() {
+        let income = UsIncome::from_wages(usd_money!(100_000));
+
+        let upstate = NewYork::new(2024, FilingStatus::Single, false).unwrap();
+        let upstate_tax = upstate.calculate(&income).unwrap();
+        assert_eq!(upstate_tax.total(), usd_money!(4_951.75));
+        assert_eq!(upstate_tax.component(UsTaxComponent::NewYorkCityTax), usd_money!(0));
+
+        let city = NewYork::new(2024, FilingStatus::Single, true).unwrap();
+        let city_tax = city.calculate(&income).unwrap();
+        assert_eq!(city_tax.component(UsTaxComponent::IncomeTax), usd_money!(4_951.75));
+        assert_eq!(city_tax.component(UsTaxComponent::NewYorkCityTax), usd_money!(3_441.09));
+    }
+
+    #[test]
+    fn lower_bracket_benefit_is_recaptured_above_107_650() {
+        let single = NewYork::new(2024, FilingStatus::Single, false).unwrap();
+        // Schedule tax 6,751.75, plus 44.7% of the 568.25 it saves against
+        // a flat 6%.
+        let tax = single.calculate(&UsIncome::from_wages(usd_money!(130_000))).unwrap();
+        assert_eq!(round_cents(tax.total()), usd_money!(7_005.76));
+
+        // Fully phased in, everything is taxed at the rate of the bracket
+        // taxable income reaches.
+        let joint = NewYork::new(2024, FilingStatus::MarriedFilingJointly, false).unwrap();
+        let tax = joint.calculate(&UsIncome::from_wages(usd_money!(300_000))).unwrap();
+        assert_eq!(tax.total(), usd_money!(283_950) * dec!(0.06));
+
+        let tax = single.calculate(&UsIncome::from_wages(usd_money!(30_000_000))).unwrap();
+        assert_eq!(tax.total(), usd_money!(29_992_000) * dec!(0.109));
+    }
+}
@@ -0,0 +1,65 @@
+use rust_decimal::prelude::*;
+use rust_decimal_macros::*;
+use simple_money::*;
+use crate::TaxError;
+use crate::us::{amount_over, IncomeTaxJurisdiction, JurisdictionTax, UsIncome, UsTaxComponent};
+
+/// Washington has no tax on wages. Its capital gains excise applies to
+/// long-term gains above a standard deduction that is the same for individuals
+/// and married couples, so filing status does not matter.
+#[derive(Debug, Clone)]
+pub struct Washington {
+    pub year: u32,
+    capital_gains_rate: Decimal,
+    capital_gains_deduction: Money,
+}
+
+impl Washington {
+    pub fn new(year: u32) -> Result<Washington, TaxError> {
+        let capital_gains_deduction = match year {
+            2022 => usd_money!(250_000),
+            2023 => usd_money!(262_000),
+            2024 => usd_money!(270_000),
+            _ => return Err(TaxError::UnsupportedYear(year)),
+        };
+
+        Ok(Washington {
+            year,
+            capital_gains_rate: dec!(0.07),
+            capital_gains_deduction,
+        })
+    }
+}
+
+impl IncomeTaxJurisdiction for Washington {
+    fn name(&self) -> &'static str {
+        "Washington"
+    }
+
+    fn calculate(&self, income: &UsIncome) -> Result<JurisdictionTax, TaxError> {
+        income.validate()?;
+        let taxable_gains = amount_over(income.long_term_capital_gains, self.capital_gains_deduction);
+
+        Ok(JurisdictionTax {
+            components: vec![(UsTaxComponent::CapitalGainsExcise, taxable_gains * self.capital_gains_rate)],
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_capital_gains_are_taxed() {
+        let washington = Washington::new(2024).unwrap();
+        let income = UsIncome {
+            long_term_capital_gains: usd_money!(500_000),
+            ..UsIncome::from_wages(usd_money!(400_000))
+        };
+
+        let tax = washington.calculate(&income).unwrap();
+        assert_eq!(tax.total(), usd_money!(16_100));
+        assert_eq!(tax.component(UsTaxComponent::IncomeTax), usd_money!(0));
+    }
+}
@@ -0,0 +1,277 @@
+use chrono::{Duration, NaiveDate};
+use rust_decimal::prelude::*;
+use rust_decimal_macros::*;
+use simple_money::*;
+use std::collections::{HashMap, HashSet};
+use crate::{AccountOwner, TaxError};
+use crate::ca::{AcbTransaction, AcbTransactionKind};
+use super::{LotSelection, TaxLots};
+
+/// Purchases this many days either side of a loss sale are replacements.
+const WINDOW_DAYS: i64 = 30;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum UsAccountType {
+    Taxable,
+    TraditionalIra,
+    RothIra,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct UsAccount {
+    pub owner: AccountOwner,
+    pub account_type: UsAccountType,
+}
+
+impl UsAccount {
+    pub fn is_taxable(&self) -> bool {
+        self.account_type == UsAccountType::Taxable
+    }
+}
+
+/// A loss on one lot disallowed because replacement shares were bought
+/// within 30 days. Transactions are identified by their position in the
+/// history.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WashSale {
+    pub sale: usize,
+    pub account: UsAccount,
+    pub lot_id: usize,
+    /// The loss on the lot before the rule, as a positive amount.
+    pub loss: Money,
+    pub disallowed_loss: Money,
+    /// Purchases matched as replacements, with the shares matched from each.
+    pub replacements: Vec<(usize, Decimal)>,
+    /// Disallowed loss matched to purchases in an IRA, which never gets
+    /// added to basis.
+    pub permanently_disallowed: Money,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WashSaleAnalysis {
+    pub accounts: HashMap<UsAccount, TaxLots>,
+    pub wash_sales: Vec<WashSale>,
+}
+
+/// A disallowed loss waiting for its replacement purchase to be recorded.
+struct PendingAdjustment {
+    shares: Decimal,
+    disallowed_loss: Money,
+    holding_days: i64,
+}
+
+fn shares_bought(transaction: &AcbTransaction) -> Decimal {
+    match transaction.kind {
+        AcbTransactionKind::Buy { shares, .. } | AcbTransactionKind::ReinvestedDistribution { shares, .. } => shares,
+        _ => dec!(0),
+    }
+}
+
+/// Runs one security's history across a couple's accounts, in date order,
+/// through tax lots per account and applies the wash sale rule to losses in
+/// taxable accounts. Sales use `selection`, and `exchange_on` gives the rates
+/// for a transaction date.
+///
+/// Loss shares are matched one for one with replacement shares in purchase
+/// order, and each replacement share is used once. The disallowed loss and
+/// the sold shares' holding period move to the replacement lot.
+pub fn apply_wash_sale_rule<'a, F>(
+    security: &str,
+    history: &[(UsAccount, AcbTransaction)],
+    selection: &LotSelection,
+    exchange_on: F,
+) -> Result<WashSaleAnalysis, TaxError>
+where
+    F: Fn(NaiveDate) -> &'a Exchange,
+{
+    let mut accounts: HashMap<UsAccount, TaxLots> = HashMap::new();
+    let mut wash_sales = vec![];
+    let mut lot_of_purchase: HashMap<usize, usize> = HashMap::new();
+    let mut purchase_of_lot: HashMap<(UsAccount, usize), usize> = HashMap::new();
+    let mut used_shares: HashMap<usize, Decimal> = HashMap::new();
+    let mut pending: HashMap<usize, Vec<PendingAdjustment>> = HashMap::new();
+
+    for (index, (account, transaction)) in history.iter().enumerate() {
+        let lots = accounts.entry(*account).or_insert_with(|| TaxLots::new(security));
+        let first_disposition = lots.dispositions().len();
+        let dispositions = lots.record(transaction, selection, exchange_on(transaction.date))?;
+
+        if shares_bought(transaction) > dec!(0) {
+            let lot_id = lots.lots().iter().map(|lot| lot.id).max().unwrap();
+            lot_of_purchase.insert(index, lot_id);
+            purchase_of_lot.insert((*account, lot_id), index);
+            for adjustment in pending.remove(&index).unwrap_or_default() {
+                lots.apply_wash_sale(lot_id, adjustment.shares, adjustment.disallowed_loss, adjustment.holding_days)?;
+            }
+        }
+        if !account.is_taxable() {
+            continue;
+        }
+
+        let sold_purchases: HashSet<usize> = dispositions
+            .iter()
+            .filter_map(|disposition| purchase_of_lot.get(&(*account, disposition.lot_id)).copied())
+            .collect();
+        let window_start = transaction.date - Duration::days(WINDOW_DAYS);
+        let window_end = transaction.date + Duration::days(WINDOW_DAYS);
+        let candidates: Vec<usize> = (0..history.len())
+            .filter(|other| {
+                let purchase = &history[*other].1;
+                *other != index
+                    && !sold_purchases.contains(other)
+                    && purchase.date >= window_start
+                    && purchase.date <= window_end
+                    && shares_bought(purchase) > dec!(0)
+            })
+            .collect();
+
+        for (offset, disposition) in dispositions.iter().enumerate() {
+            if disposition.gain.amount >= dec!(0) || disposition.shares.is_zero() {
+                continue;
+            }
+            let loss = Money { amount: -disposition.gain.amount, currency: Currency::USD };
+            let holding_days = (transaction.date - disposition.acquisition_date).num_days();
+
+            let mut remaining = disposition.shares;
+            let mut replacements = vec![];
+            let mut disallowed_loss = usd_money!(0);
+            let mut permanently_disallowed = usd_money!(0);
+            for other in candidates.iter() {
+                if remaining.is_zero() {
+                    break;
+                }
+                let replacement_account = history[*other].0;
+                let used = used_shares.entry(*other).or_insert(dec!(0));
+                let mut available = shares_bought(&history[*other].1) - *used;
+                if *other < index && replacement_account.is_taxable() {
+                    let open_shares = lot_of_purchase
+                        .get(other)
+                        .and_then(|lot_id| accounts[&replacement_account].lots().iter().find(|lot| lot.id == *lot_id))
+                        .map_or(dec!(0), |lot| lot.shares);
+                    available = available.min(open_shares);
+                }
+                let matched = remaining.min(available);
+                if matched <= dec!(0) {
+                    continue;
+                }
+
+                let disallowed = Money { amount: loss.amount * matched / disposition.shares, currency: Currency::USD };
+                if !replacement_account.is_taxable() {
+                    permanently_disallowed = permanently_disallowed + disallowed;
+                }else if *other < index {
+                    let lot_id = lot_of_purchase[other];
+                    accounts
+                        .get_mut(&replacement_account)
+                        .unwrap()
+                        .apply_wash_sale(lot_id, matched, disallowed, holding_days)?;
+                }else{
+                    pending.entry(*other).or_default().push(PendingAdjustment {
+                        shares: matched,
+                        disallowed_loss: disallowed,
+                        holding_days,
+                    });
+                }
+                *used += matched;
+                remaining -= matched;
+                disallowed_loss = disallowed_loss + disallowed;
+                replacements.push((*other, matched));
+            }
+
+            if replacements.is_empty() {
+                continue;
+            }
+            accounts
+                .get_mut(account)
+                .unwrap()
+                .disallow_loss(first_disposition + offset, disallowed_loss);
+            wash_sales.push(WashSale {
+                sale: index,
+                account: *account,
+                lot_id: disposition.lot_id,
+                loss,
+                disallowed_loss,
+                replacements,
+                permanently_disallowed,
+            });
+        }
+    }
+
+    Ok(WashSaleAnalysis { accounts, wash_sales })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TAXABLE: UsAccount = UsAccount { owner: AccountOwner::Taxpayer, account_type: UsAccountType::Taxable };
+    const SPOUSE_IRA: UsAccount = UsAccount { owner: AccountOwner::Spouse, account_type: UsAccountType::TraditionalIra };
+
+    fn date(month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, month, day).unwrap()
+    }
+
+    fn buy(account: UsAccount, month: u32, day: u32, shares: Decimal, cost: Money) -> (UsAccount, AcbTransaction) {
+        (account, AcbTransaction { date: date(month, day), kind: AcbTransactionKind::Buy { shares, cost } })
+    }
+
+    fn sell(month: u32, day: u32) -> (UsAccount, AcbTransaction) {
+        let kind = AcbTransactionKind::Sell { shares: dec!(100), proceeds: usd_money!(3_000) };
+        (TAXABLE, AcbTransaction { date: date(month, day), kind })
+    }
+
+    #[test]
+    fn loss_and_holding_period_move_to_the_replacement_lot() {
+        let usd = Exchange::new();
+        let history = vec![
+            buy(TAXABLE, 1, 2, dec!(100), usd_money!(5_000)),
+            sell(3, 1),
+            buy(TAXABLE, 3, 15, dec!(100), usd_money!(3_200)),
+        ];
+
+        let analysis = apply_wash_sale_rule("SPY", &history, &LotSelection::FirstInFirstOut, |_| &usd).unwrap();
+        let wash_sale = &analysis.wash_sales[0];
+        assert_eq!(wash_sale.disallowed_loss, usd_money!(2_000));
+        assert_eq!(wash_sale.replacements, vec![(2, dec!(100))]);
+
+        let lots = &analysis.accounts[&TAXABLE];
+        assert_eq!(lots.dispositions()[0].gain, usd_money!(0));
+        assert_eq!(lots.lots().len(), 1);
+        assert_eq!(lots.lots()[0].basis, usd_money!(5_200));
+        assert_eq!(lots.lots()[0].acquisition_date, date(1, 16));
+    }
+
+    #[test]
+    fn partial_replacement_and_spouse_ira() {
+        let usd = Exchange::new();
+        let history = vec![
+            buy(TAXABLE, 1, 2, dec!(100), usd_money!(5_000)),
+            buy(SPOUSE_IRA, 2, 20, dec!(30), usd_money!(950)),
+            sell(3, 1),
+            buy(TAXABLE, 3, 20, dec!(50), usd_money!(1_600)),
+        ];
+
+        let analysis = apply_wash_sale_rule("SPY", &history, &LotSelection::FirstInFirstOut, |_| &usd).unwrap();
+        let wash_sale = &analysis.wash_sales[0];
+        assert_eq!(wash_sale.replacements, vec![(1, dec!(30)), (3, dec!(50))]);
+        assert_eq!(wash_sale.disallowed_loss, usd_money!(1_600));
+        assert_eq!(wash_sale.permanently_disallowed, usd_money!(600));
+
+        let lots = &analysis.accounts[&TAXABLE];
+        assert_eq!(lots.dispositions()[0].gain, usd_money!(-400));
+        assert_eq!(lots.lots()[0].basis, usd_money!(2_600));
+    }
+
+    #[test]
+    fn purchases_outside_the_window_are_not_replacements() {
+        let usd = Exchange::new();
+        let history = vec![
+            buy(TAXABLE, 1, 2, dec!(100), usd_money!(5_000)),
+            sell(3, 1),
+            buy(TAXABLE, 4, 1, dec!(100), usd_money!(3_200)),
+        ];
+
+        let analysis = apply_wash_sale_rule("SPY", &history, &LotSelection::FirstInFirstOut, |_| &usd).unwrap();
+        assert!(analysis.wash_sales.is_empty());
+        assert_eq!(analysis.accounts[&TAXABLE].dispositions()[0].gain, usd_money!(-2_000));
+    }
+}
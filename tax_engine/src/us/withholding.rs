@@ -0,0 +1,279 @@
+use rust_decimal::prelude::*;
+use rust_decimal_macros::*;
+use simple_money::*;
+use crate::{round_cents, PayFrequency, TaxError};
+use super::{
+    amount_over, FederalIncomeTax, FicaParameters, FilingStatus, ADDITIONAL_MEDICARE_WITHHOLDING_THRESHOLD,
+};
+
+/// Optional flat rate for supplemental wages, such as bonuses and RSU vests,
+/// that are identified separately from regular wages.
+pub const SUPPLEMENTAL_WITHHOLDING_RATE: Decimal = dec!(0.22);
+
+/// Required rate on supplemental wages above $1 million in a calendar year.
+pub const MANDATORY_SUPPLEMENTAL_WITHHOLDING_RATE: Decimal = dec!(0.37);
+
+const MANDATORY_SUPPLEMENTAL_THRESHOLD: Money = Money { amount: dec!(1_000_000), currency: Currency::USD };
+
+/// Amount Worksheet 1A subtracts from annual wages when the Step 2 box is
+/// not checked. Part of the standard deduction is built into the tables.
+fn withholding_allowance(filing_status: FilingStatus) -> Money {
+    match filing_status {
+        FilingStatus::MarriedFilingJointly => usd_money!(12_900),
+        _ => usd_money!(8_600),
+    }
+}
+
+/// An employee's Form W-4 in the format used since 2020.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FormW4 {
+    pub filing_status: FilingStatus,
+    /// Step 2(c): the employee holds two jobs, or files jointly with a
+    /// working spouse, and checked the box.
+    pub multiple_jobs: bool,
+    /// Step 3: credits claimed for dependents and other dependents.
+    pub dependents: Money,
+    /// Step 4(a): other income not subject to withholding.
+    pub other_income: Money,
+    /// Step 4(b): deductions expected beyond the standard deduction.
+    pub deductions: Money,
+    /// Step 4(c): extra withholding for each pay period.
+    pub extra_withholding: Money,
+}
+
+impl FormW4 {
+    /// A W-4 with only Step 1 filled in.
+    pub fn new(filing_status: FilingStatus) -> FormW4 {
+        FormW4 {
+            filing_status,
+            multiple_jobs: false,
+            dependents: usd_money!(0),
+            other_income: usd_money!(0),
+            deductions: usd_money!(0),
+            extra_withholding: usd_money!(0),
+        }
+    }
+}
+
+/// Amounts withheld from one payment.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct UsPayStub {
+    pub gross: Money,
+    pub federal_withholding: Money,
+    pub social_security: Money,
+    /// Includes Additional Medicare withholding.
+    pub medicare: Money,
+    pub net_pay: Money,
+}
+
+/// How the year's withholding compares with the tax it was meant to cover.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WithholdingEstimate {
+    /// Income tax after dependent credits, plus Additional Medicare Tax.
+    pub tax_liability: Money,
+    pub withheld: Money,
+    pub refund: Money,
+    pub balance_owing: Money,
+}
+
+/// Withholds federal income tax and FICA from an employee's pay with the
+/// percentage method of IRS Publication 15-T, for W-4s from 2020 onward.
+#[derive(Clone, Debug)]
+pub struct UsPayroll {
+    frequency: PayFrequency,
+    w4: FormW4,
+    /// Married filing separately employees use the single tables.
+    withholding_tax: FederalIncomeTax,
+    income_tax: FederalIncomeTax,
+    fica: FicaParameters,
+    wages: Money,
+    taxable_wages: Money,
+    federal_withheld: Money,
+    supplemental_wages: Money,
+    additional_medicare_withheld: Money,
+    ledger: Vec<UsPayStub>,
+}
+
+impl UsPayroll {
+    pub fn new(year: u32, frequency: PayFrequency, w4: FormW4) -> Result<UsPayroll, TaxError> {
+        let table_status = match w4.filing_status {
+            FilingStatus::MarriedFilingSeparately => FilingStatus::Single,
+            filing_status => filing_status,
+        };
+        Ok(UsPayroll {
+            frequency,
+            w4,
+            withholding_tax: FederalIncomeTax::new(year, table_status)?,
+            income_tax: FederalIncomeTax::new(year, w4.filing_status)?,
+            fica: FicaParameters::for_year(year)?,
+            wages: usd_money!(0),
+            taxable_wages: usd_money!(0),
+            federal_withheld: usd_money!(0),
+            supplemental_wages: usd_money!(0),
+            additional_medicare_withheld: usd_money!(0),
+            ledger: vec![],
+        })
+    }
+
+    pub fn ledger(&self) -> &[UsPayStub] {
+        &self.ledger
+    }
+
+    /// Federal income tax to withhold from one period's taxable wages, with
+    /// Worksheet 1A.
+    pub fn periodic_withholding(&self, taxable_wages: Money) -> Money {
+        let periods = Decimal::from(self.frequency.periods_per_year());
+        let standard_deduction = self.withholding_tax.standard_deduction();
+        let mut adjusted_annual_wages = taxable_wages * periods + self.w4.other_income - self.w4.deductions;
+        let annual_withholding = if self.w4.multiple_jobs {
+            // The Step 2 tables halve the brackets and the standard deduction,
+            // assuming the other job pays about the same.
+            self.withholding_tax
+                .calculate_tax(amount_over(adjusted_annual_wages, standard_deduction * dec!(0.5)) * dec!(2))
+                * dec!(0.5)
+        }else{
+            let allowance = withholding_allowance(self.w4.filing_status);
+            adjusted_annual_wages = adjusted_annual_wages - allowance;
+            self.withholding_tax.calculate_tax(amount_over(adjusted_annual_wages, standard_deduction - allowance))
+        };
+
+        let tentative = (annual_withholding - self.w4.dependents) * (dec!(1) / periods);
+        round_cents(tentative.max(usd_money!(0)) + self.w4.extra_withholding)
+    }
+
+    /// Withholding on regular wages. `pre_tax_deductions`, such as 401(k)
+    /// deferrals, reduce the wages subject to income tax but not FICA.
+    pub fn pay(&mut self, gross: Money, pre_tax_deductions: Money) -> Result<UsPayStub, TaxError> {
+        check_currency(&[gross, pre_tax_deductions])?;
+        let taxable_wages = gross - pre_tax_deductions;
+        let federal_withholding = self.periodic_withholding(taxable_wages);
+        Ok(self.record(gross, taxable_wages, federal_withholding))
+    }
+
+    /// Withholding on supplemental wages paid separately, such as a bonus
+    /// or the value of vesting RSUs. The flat rate applies and Step 4(c)
+    /// extra withholding does not.
+    pub fn pay_supplemental(&mut self, gross: Money) -> Result<UsPayStub, TaxError> {
+        check_currency(&[gross])?;
+        let below_threshold = gross.min(amount_over(MANDATORY_SUPPLEMENTAL_THRESHOLD, self.supplemental_wages));
+        let federal_withholding = below_threshold * SUPPLEMENTAL_WITHHOLDING_RATE
+            + (gross - below_threshold) * MANDATORY_SUPPLEMENTAL_WITHHOLDING_RATE;
+        self.supplemental_wages = self.supplemental_wages + gross;
+        Ok(self.record(gross, gross, round_cents(federal_withholding)))
+    }
+
+    /// Compares what has been withheld with the tax on the wages paid so far,
+    /// the W-4's other income and its deductions. Medicare is reconciled for
+    /// Additional Medicare Tax, which is withheld without regard to filing
+    /// status.
+    pub fn year_end_estimate(&self) -> Result<WithholdingEstimate, TaxError> {
+        let zero = usd_money!(0);
+        let adjusted_gross_income = self.taxable_wages + self.w4.other_income;
+        let taxable_income = self.income_tax.taxable_income(
+            adjusted_gross_income,
+            Some(self.income_tax.standard_deduction() + self.w4.deductions),
+        );
+        let income_tax = (self.income_tax.calculate_tax(taxable_income) - self.w4.dependents).max(zero);
+        let fica = self.fica.calculate(vec![self.wages], self.w4.filing_status)?;
+
+        let tax_liability = income_tax + fica.additional_medicare;
+        let withheld = self.federal_withheld + self.additional_medicare_withheld;
+        Ok(WithholdingEstimate {
+            tax_liability,
+            withheld,
+            refund: amount_over(withheld, tax_liability),
+            balance_owing: amount_over(tax_liability, withheld),
+        })
+    }
+
+    fn record(&mut self, gross: Money, taxable_wages: Money, federal_withholding: Money) -> UsPayStub {
+        let social_security = gross.min(amount_over(self.fica.social_security_wage_base, self.wages))
+            * self.fica.social_security_rate;
+        let additional_threshold = Money { amount: ADDITIONAL_MEDICARE_WITHHOLDING_THRESHOLD, currency: Currency::USD };
+        let additional_medicare = (amount_over(self.wages + gross, additional_threshold)
+            - amount_over(self.wages, additional_threshold))
+            * self.fica.additional_medicare_rate;
+        let social_security = round_cents(social_security);
+        let additional_medicare = round_cents(additional_medicare);
+        let medicare = round_cents(gross * self.fica.medicare_rate) + additional_medicare;
+
+        self.wages = self.wages + gross;
+        self.taxable_wages = self.taxable_wages + taxable_wages;
+        self.federal_withheld = self.federal_withheld + federal_withholding;
+        self.additional_medicare_withheld = self.additional_medicare_withheld + additional_medicare;
+        let stub = UsPayStub {
+            gross,
+            federal_withholding,
+            social_security,
+            medicare,
+            net_pay: gross - federal_withholding - social_security - medicare,
+        };
+        self.ledger.push(stub);
+        stub
+    }
+}
+
+fn check_currency(amounts: &[Money]) -> Result<(), TaxError> {
+    if amounts.iter().any(|money| money.currency != Currency::USD) {
+        return Err(TaxError::MismatchedCurrencies);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn percentage_method_with_w4_adjustments() {
+        let single = UsPayroll::new(2024, PayFrequency::Biweekly, FormW4::new(FilingStatus::Single)).unwrap();
+        assert_eq!(single.periodic_withholding(usd_money!(3_000)), usd_money!(346.19));
+
+        let multiple_jobs = FormW4 { multiple_jobs: true, ..FormW4::new(FilingStatus::Single) };
+        let payroll = UsPayroll::new(2024, PayFrequency::Biweekly, multiple_jobs).unwrap();
+        assert_eq!(payroll.periodic_withholding(usd_money!(3_000)), usd_money!(518.82));
+
+        let adjusted = FormW4 {
+            dependents: usd_money!(2_000),
+            extra_withholding: usd_money!(50),
+            ..FormW4::new(FilingStatus::Single)
+        };
+        let payroll = UsPayroll::new(2024, PayFrequency::Biweekly, adjusted).unwrap();
+        assert_eq!(payroll.periodic_withholding(usd_money!(3_000)), usd_money!(319.27));
+
+        let joint = UsPayroll::new(2024, PayFrequency::Biweekly, FormW4::new(FilingStatus::MarriedFilingJointly)).unwrap();
+        assert_eq!(joint.periodic_withholding(usd_money!(3_000)), usd_money!(207.38));
+    }
+
+    #[test]
+    fn supplemental_wages_over_a_million_use_the_mandatory_rate() {
+        let mut payroll = UsPayroll::new(2024, PayFrequency::Monthly, FormW4::new(FilingStatus::Single)).unwrap();
+        let vest = payroll.pay_supplemental(usd_money!(900_000)).unwrap();
+        assert_eq!(vest.federal_withholding, usd_money!(198_000));
+        assert_eq!(vest.social_security, usd_money!(10_453.20));
+        assert_eq!(vest.medicare, usd_money!(19_350));
+
+        let bonus = payroll.pay_supplemental(usd_money!(200_000)).unwrap();
+        assert_eq!(bonus.federal_withholding, usd_money!(59_000));
+        assert_eq!(bonus.social_security, usd_money!(0));
+        assert_eq!(payroll.pay(cad_money!(1_000), usd_money!(0)).unwrap_err(), TaxError::MismatchedCurrencies);
+    }
+
+    #[test]
+    fn flat_rate_bonus_leaves_a_balance_owing() {
+        let mut payroll = UsPayroll::new(2024, PayFrequency::Monthly, FormW4::new(FilingStatus::Single)).unwrap();
+        for _ in 0..12 {
+            assert_eq!(payroll.pay(usd_money!(10_000), usd_money!(0)).unwrap().federal_withholding, usd_money!(1_528.21));
+        }
+        let estimate = payroll.year_end_estimate().unwrap();
+        assert_eq!(estimate.refund, usd_money!(0.02));
+
+        // The bonus is withheld at 22% but taxed in the 24% bracket.
+        payroll.pay_supplemental(usd_money!(10_000)).unwrap();
+        let estimate = payroll.year_end_estimate().unwrap();
+        assert_eq!(estimate.tax_liability, usd_money!(20_738.50));
+        assert_eq!(estimate.withheld, usd_money!(20_538.52));
+        assert_eq!(estimate.balance_owing, usd_money!(199.98));
+        assert_eq!(estimate.refund, usd_money!(0));
+    }
+}